@@ -0,0 +1,125 @@
+#ifndef RUSTY_SCOUT_H
+#define RUSTY_SCOUT_H
+
+/* Generated by cbindgen from ffi/src/lib.rs; don't edit by hand. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+// Bumped whenever a declaration in the header changes incompatibly.
+#define RUSTY_SCOUT_ABI_VERSION 1
+
+// What `rusty_scout_search_poll` found.
+typedef enum RsStatus {
+  // `result` holds the next result.
+  RS_STATUS_RESULT = 0,
+  // Nothing new yet; only returned when not waiting.
+  RS_STATUS_PENDING = 1,
+  // The search is over and every result has been returned.
+  RS_STATUS_DONE = 2,
+  // The search failed; see `rusty_scout_search_error`.
+  RS_STATUS_ERROR = -1,
+} RsStatus;
+
+// A running search.
+typedef struct RsSearch RsSearch;
+
+// A match's byte range within its line.
+typedef struct RsMatch {
+  size_t start;
+  size_t end;
+} RsMatch;
+
+// One result. Its pointers stay valid until the next poll of the same
+// search, or until it's freed.
+typedef struct RsResult {
+  const char *path;
+  size_t path_len;
+  const char *line;
+  size_t line_len;
+  // Counting from 1, or 0 for results about a whole file.
+  size_t line_number;
+  const struct RsMatch *matches;
+  size_t match_count;
+} RsResult;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+// The `RUSTY_SCOUT_ABI_VERSION` the library was built with, to check
+// against the header's.
+uint32_t rusty_scout_abi_version(void);
+
+// Starts searching `path`, a file or directory, on another thread.
+//
+// `args` holds `arg_count` options as they'd be given on the command line,
+// pattern included, e.g. `{"TODO", "--ignore-case"}`. Returns NULL if they
+// aren't valid, setting `*error`, unless `error` is NULL, to a message to
+// free with `rusty_scout_string_free`.
+//
+// # Safety
+//
+// `path` and the `arg_count` strings in `args` must be valid C strings,
+// and `error` must be NULL or valid to write to.
+struct RsSearch *rusty_scout_search_start(const char *path,
+                                          const char *const *args,
+                                          size_t arg_count,
+                                          char **error);
+
+// Fills in `result` with the next result, if there is one. When `wait` is
+// true, this blocks until there is one or the search is over, and never
+// returns `RS_STATUS_PENDING`.
+//
+// # Safety
+//
+// `search` must come from `rusty_scout_search_start` and not have been
+// freed, and `result` must be valid to write to.
+enum RsStatus rusty_scout_search_poll(const struct RsSearch *search,
+                                      bool wait,
+                                      struct RsResult *result);
+
+// What went wrong, after a poll returned `RS_STATUS_ERROR`, or else NULL.
+// Valid until the search is freed.
+//
+// # Safety
+//
+// `search` must come from `rusty_scout_search_start` and not have been
+// freed.
+const char *rusty_scout_search_error(const struct RsSearch *search);
+
+// Stops the search. Polls then return the results already found, and
+// then `RS_STATUS_DONE`. Can be called from any thread, even while
+// another is waiting in a poll.
+//
+// # Safety
+//
+// `search` must come from `rusty_scout_search_start` and not have been
+// freed.
+void rusty_scout_search_cancel(const struct RsSearch *search);
+
+// Stops the search if it's still running and frees it, along with its
+// last result and error. Does nothing if `search` is NULL.
+//
+// # Safety
+//
+// `search` must be NULL or come from `rusty_scout_search_start`, and not
+// have been freed already.
+void rusty_scout_search_free(struct RsSearch *search);
+
+// Frees a string handed out by this library. Does nothing if `string` is
+// NULL.
+//
+// # Safety
+//
+// `string` must be NULL or a string from this library, not freed already.
+void rusty_scout_string_free(char *string);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* RUSTY_SCOUT_H */
@@ -0,0 +1,345 @@
+//! A C interface to rusty-scout: start a search on its own thread, poll it
+//! for results, and cancel it. The declarations are in
+//! `include/rusty_scout.h`, generated from this file with cbindgen.
+//!
+//! Strings passed in must be NUL-terminated UTF-8. Strings handed out are
+//! NUL-terminated and come with their length, since a line may itself
+//! contain NUL bytes.
+
+use rusty_scout::{cli::Args, Cancel, SearchError, SearchResult, Searcher};
+use std::ffi::{c_char, CStr, CString};
+use std::path::PathBuf;
+use std::ptr;
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::sync::Mutex;
+
+/// Bumped whenever a declaration in the header changes incompatibly.
+pub const RUSTY_SCOUT_ABI_VERSION: u32 = 1;
+
+/// Results that can wait to be polled before the search blocks.
+const RESULT_BUFFER: usize = 256;
+
+/// What `rusty_scout_search_poll` found.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RsStatus {
+    /// `result` holds the next result.
+    Result = 0,
+    /// Nothing new yet; only returned when not waiting.
+    Pending = 1,
+    /// The search is over and every result has been returned.
+    Done = 2,
+    /// The search failed; see `rusty_scout_search_error`.
+    Error = -1,
+}
+
+/// A match's byte range within its line.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RsMatch {
+    pub start: usize,
+    pub end: usize,
+}
+
+/// One result. Its pointers stay valid until the next poll of the same
+/// search, or until it's freed.
+#[repr(C)]
+pub struct RsResult {
+    pub path: *const c_char,
+    pub path_len: usize,
+    pub line: *const c_char,
+    pub line_len: usize,
+    /// Counting from 1, or 0 for results about a whole file.
+    pub line_number: usize,
+    pub matches: *const RsMatch,
+    pub match_count: usize,
+}
+
+/// A running search.
+pub struct RsSearch {
+    cancel: Cancel,
+    /// Locked by polls, so `cancel` can be used while one waits.
+    state: Mutex<State>,
+}
+
+struct State {
+    results: Receiver<Result<SearchResult, SearchError>>,
+    /// What the last `RsResult` handed out points into.
+    path: Vec<u8>,
+    line: Vec<u8>,
+    matches: Vec<RsMatch>,
+    error: Option<CString>,
+}
+
+impl State {
+    fn hold(&mut self, result: SearchResult) -> RsResult {
+        set_nul_terminated(
+            &mut self.path,
+            result.file_path.as_os_str().as_encoded_bytes(),
+        );
+        set_nul_terminated(&mut self.line, result.line.as_bytes());
+        self.matches.clear();
+        self.matches.extend(
+            result
+                .matches
+                .iter()
+                .map(|&(start, end)| RsMatch { start, end }),
+        );
+        RsResult {
+            path: self.path.as_ptr().cast(),
+            path_len: self.path.len() - 1,
+            line: self.line.as_ptr().cast(),
+            line_len: self.line.len() - 1,
+            line_number: result.line_number,
+            matches: self.matches.as_ptr(),
+            match_count: self.matches.len(),
+        }
+    }
+}
+
+fn set_nul_terminated(buffer: &mut Vec<u8>, bytes: &[u8]) {
+    buffer.clear();
+    buffer.extend_from_slice(bytes);
+    buffer.push(0);
+}
+
+/// A message as a C string, with any NUL bytes in it dropped.
+fn c_string(message: String) -> CString {
+    CString::new(message.replace('\0', "")).unwrap()
+}
+
+unsafe fn string(s: *const c_char) -> Result<String, String> {
+    if s.is_null() {
+        return Err("NULL string".to_string());
+    }
+    CStr::from_ptr(s)
+        .to_str()
+        .map(str::to_string)
+        .map_err(|e| e.to_string())
+}
+
+unsafe fn start(
+    path: *const c_char,
+    args: *const *const c_char,
+    arg_count: usize,
+) -> Result<RsSearch, String> {
+    let path = PathBuf::from(string(path)?);
+    let argv = (0..arg_count)
+        .map(|i| string(*args.add(i)))
+        .collect::<Result<Vec<_>, _>>()?;
+    let args = Args::from_argv(argv).map_err(|e| e.to_string())?;
+    let searcher = Searcher::new(&args).map_err(|e| e.to_string())?;
+
+    let found = searcher.search_iter(path);
+    let cancel = found.cancel_handle();
+    // Passed on by a thread of its own so that polls needn't block.
+    let (sender, results) = mpsc::sync_channel(RESULT_BUFFER);
+    std::thread::spawn(move || {
+        for result in found {
+            if sender.send(result).is_err() {
+                return;
+            }
+        }
+    });
+    Ok(RsSearch {
+        cancel,
+        state: Mutex::new(State {
+            results,
+            path: Vec::new(),
+            line: Vec::new(),
+            matches: Vec::new(),
+            error: None,
+        }),
+    })
+}
+
+/// The `RUSTY_SCOUT_ABI_VERSION` the library was built with, to check
+/// against the header's.
+#[no_mangle]
+pub extern "C" fn rusty_scout_abi_version() -> u32 {
+    RUSTY_SCOUT_ABI_VERSION
+}
+
+/// Starts searching `path`, a file or directory, on another thread.
+///
+/// `args` holds `arg_count` options as they'd be given on the command line,
+/// pattern included, e.g. `{"TODO", "--ignore-case"}`. Returns NULL if they
+/// aren't valid, setting `*error`, unless `error` is NULL, to a message to
+/// free with `rusty_scout_string_free`.
+///
+/// # Safety
+///
+/// `path` and the `arg_count` strings in `args` must be valid C strings,
+/// and `error` must be NULL or valid to write to.
+#[no_mangle]
+pub unsafe extern "C" fn rusty_scout_search_start(
+    path: *const c_char,
+    args: *const *const c_char,
+    arg_count: usize,
+    error: *mut *mut c_char,
+) -> *mut RsSearch {
+    match start(path, args, arg_count) {
+        Ok(search) => Box::into_raw(Box::new(search)),
+        Err(message) => {
+            if !error.is_null() {
+                *error = c_string(message).into_raw();
+            }
+            ptr::null_mut()
+        }
+    }
+}
+
+/// Fills in `result` with the next result, if there is one. When `wait` is
+/// true, this blocks until there is one or the search is over, and never
+/// returns `RS_STATUS_PENDING`.
+///
+/// # Safety
+///
+/// `search` must come from `rusty_scout_search_start` and not have been
+/// freed, and `result` must be valid to write to.
+#[no_mangle]
+pub unsafe extern "C" fn rusty_scout_search_poll(
+    search: *const RsSearch,
+    wait: bool,
+    result: *mut RsResult,
+) -> RsStatus {
+    let mut state = (*search).state.lock().unwrap();
+    let next = if wait {
+        state.results.recv().map_err(|_| TryRecvError::Disconnected)
+    } else {
+        state.results.try_recv()
+    };
+    match next {
+        Ok(Ok(found)) => {
+            *result = state.hold(found);
+            RsStatus::Result
+        }
+        Ok(Err(e)) => {
+            state.error = Some(c_string(e.to_string()));
+            RsStatus::Error
+        }
+        Err(TryRecvError::Empty) => RsStatus::Pending,
+        Err(TryRecvError::Disconnected) => RsStatus::Done,
+    }
+}
+
+/// What went wrong, after a poll returned `RS_STATUS_ERROR`, or else NULL.
+/// Valid until the search is freed.
+///
+/// # Safety
+///
+/// `search` must come from `rusty_scout_search_start` and not have been
+/// freed.
+#[no_mangle]
+pub unsafe extern "C" fn rusty_scout_search_error(search: *const RsSearch) -> *const c_char {
+    let state = (*search).state.lock().unwrap();
+    state.error.as_ref().map_or(ptr::null(), |e| e.as_ptr())
+}
+
+/// Stops the search. Polls then return the results already found, and
+/// then `RS_STATUS_DONE`. Can be called from any thread, even while
+/// another is waiting in a poll.
+///
+/// # Safety
+///
+/// `search` must come from `rusty_scout_search_start` and not have been
+/// freed.
+#[no_mangle]
+pub unsafe extern "C" fn rusty_scout_search_cancel(search: *const RsSearch) {
+    (*search).cancel.cancel();
+}
+
+/// Stops the search if it's still running and frees it, along with its
+/// last result and error. Does nothing if `search` is NULL.
+///
+/// # Safety
+///
+/// `search` must be NULL or come from `rusty_scout_search_start`, and not
+/// have been freed already.
+#[no_mangle]
+pub unsafe extern "C" fn rusty_scout_search_free(search: *mut RsSearch) {
+    if !search.is_null() {
+        let search = Box::from_raw(search);
+        search.cancel.cancel();
+    }
+}
+
+/// Frees a string handed out by this library. Does nothing if `string` is
+/// NULL.
+///
+/// # Safety
+///
+/// `string` must be NULL or a string from this library, not freed already.
+#[no_mangle]
+pub unsafe extern "C" fn rusty_scout_string_free(string: *mut c_char) {
+    if !string.is_null() {
+        drop(CString::from_raw(string));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::tempdir;
+
+    fn start_search(path: &str, args: &[&str]) -> Result<*mut RsSearch, String> {
+        let path = CString::new(path).unwrap();
+        let args: Vec<CString> = args.iter().map(|a| CString::new(*a).unwrap()).collect();
+        let pointers: Vec<*const c_char> = args.iter().map(|a| a.as_ptr()).collect();
+        let mut error = ptr::null_mut();
+        unsafe {
+            let search = rusty_scout_search_start(
+                path.as_ptr(),
+                pointers.as_ptr(),
+                pointers.len(),
+                &mut error,
+            );
+            if search.is_null() {
+                let message = CStr::from_ptr(error).to_string_lossy().into_owned();
+                rusty_scout_string_free(error);
+                return Err(message);
+            }
+            Ok(search)
+        }
+    }
+
+    #[test]
+    fn test_search() -> Result<(), Box<dyn std::error::Error>> {
+        let dir = tempdir()?;
+        let file = dir.path().join("a.txt");
+        fs::write(&file, "a needle\nhay\n")?;
+
+        let search = start_search(dir.path().to_str().unwrap(), &["needle"])?;
+        let mut result = unsafe { std::mem::zeroed::<RsResult>() };
+        unsafe {
+            assert_eq!(
+                rusty_scout_search_poll(search, true, &mut result),
+                RsStatus::Result
+            );
+            let path = std::slice::from_raw_parts(result.path.cast::<u8>(), result.path_len);
+            assert_eq!(path, file.to_str().unwrap().as_bytes());
+            assert_eq!(CStr::from_ptr(result.line).to_str()?, "a needle");
+            assert_eq!(result.line_number, 1);
+            let matches = std::slice::from_raw_parts(result.matches, result.match_count);
+            assert_eq!(matches, [RsMatch { start: 2, end: 8 }]);
+
+            assert_eq!(
+                rusty_scout_search_poll(search, true, &mut result),
+                RsStatus::Done
+            );
+            assert!(rusty_scout_search_error(search).is_null());
+            rusty_scout_search_cancel(search);
+            rusty_scout_search_free(search);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_start_error() {
+        let error = start_search(".", &["(", "--regex"]).unwrap_err();
+        assert!(error.contains("regex"), "{}", error);
+        assert!(start_search(".", &["x", "--no-such-option"]).is_err());
+    }
+}
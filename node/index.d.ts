@@ -0,0 +1,56 @@
+/* auto-generated by NAPI-RS */
+/* eslint-disable */
+/** A match's range within its line, in UTF-16 code units like JavaScript
+ * strings, so `line.slice(start, end)` is the matched text. */
+export interface Match {
+  start: number
+  end: number
+}
+
+/**
+ * Searches `path`, a file or directory, for `pattern`, which may also be an
+ * array of patterns. Resolves to the results, or, when `onMatch` is given,
+ * hands it each result as it's found and resolves to how many there were.
+ */
+export declare function search(pattern: string | string[], path?: string, options?: SearchOptions, onMatch?: (result: SearchResult) => boolean | void): Promise<SearchResult[] | number>
+
+/**
+ * The options `search` takes, named as in the Rust `SearchOptions` but in
+ * camelCase. Any left out keep the command line's defaults.
+ */
+export interface SearchOptions {
+  regex?: boolean
+  ignoreCase?: boolean
+  lineRegexp?: boolean
+  fuzzy?: boolean
+  maxErrors?: number
+  hidden?: boolean
+  noIgnore?: boolean
+  followLinks?: boolean
+  oneFileSystem?: boolean
+  maxDepth?: number
+  globs?: Array<string>
+  exclude?: Array<string>
+  excludeDirs?: Array<string>
+  types?: Array<string>
+  maxFilesize?: number
+  maxCount?: number
+  limit?: number
+  threads?: number
+  text?: boolean
+  binary?: boolean
+  replace?: string
+}
+
+/** A match, and the line it was found on. */
+export interface SearchResult {
+  path: string
+  /** Counting from 1, or 0 for results about a whole file. */
+  lineNumber: number
+  line: string
+  matches: Array<Match>
+  /** How good a fuzzy match is; higher is better. */
+  score?: number
+  /** The line with its matches replaced, when searching with `replace`. */
+  replacement?: string
+}
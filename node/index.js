@@ -0,0 +1,2 @@
+// The addon built by `npm run build`; its types are in index.d.ts.
+module.exports = require("./rusty-scout.node");
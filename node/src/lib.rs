@@ -0,0 +1,275 @@
+//! The `rusty-scout` Node.js addon, built with `npm run build` from this
+//! directory:
+//!
+//! ```js
+//! const { search } = require("rusty-scout");
+//!
+//! const found = await search("TODO", "src", { ignoreCase: true }, (result) => {
+//!   console.log(result.path, result.lineNumber, result.line);
+//! });
+//! ```
+//!
+//! Searches run on libuv's thread pool, so the event loop is free meanwhile.
+
+use napi::bindgen_prelude::{AsyncTask, Either};
+use napi::threadsafe_function::{ThreadsafeFunction, ThreadsafeFunctionCallMode};
+use napi::{Env, Error, Status, Task};
+use napi_derive::napi;
+use rusty_scout::{SearchIter, SearcherBuilder};
+use std::path::PathBuf;
+use std::sync::mpsc;
+
+/// A match's range within its line, in UTF-16 code units like JavaScript
+/// strings, so `line.slice(start, end)` is the matched text.
+#[napi(object)]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Match {
+    pub start: u32,
+    pub end: u32,
+}
+
+/// A match, and the line it was found on.
+#[napi(object)]
+#[derive(Clone, Debug)]
+pub struct SearchResult {
+    pub path: String,
+    /// Counting from 1, or 0 for results about a whole file.
+    pub line_number: u32,
+    pub line: String,
+    pub matches: Vec<Match>,
+    /// How good a fuzzy match is; higher is better.
+    pub score: Option<i64>,
+    /// The line with its matches replaced, when searching with `replace`.
+    pub replacement: Option<String>,
+}
+
+impl From<rusty_scout::SearchResult> for SearchResult {
+    fn from(result: rusty_scout::SearchResult) -> Self {
+        let units = |byte| utf16_offset(&result.line, byte);
+        SearchResult {
+            matches: result
+                .matches
+                .iter()
+                .map(|&(start, end)| Match {
+                    start: units(start),
+                    end: units(end),
+                })
+                .collect(),
+            path: result.file_path.display().to_string(),
+            line_number: result.line_number as u32,
+            score: result.score,
+            replacement: result.replacement.map(|replacement| replacement.line),
+            line: result.line,
+        }
+    }
+}
+
+/// How many UTF-16 code units of `line` come before byte `byte`.
+fn utf16_offset(line: &str, byte: usize) -> u32 {
+    line[..byte.min(line.len())].encode_utf16().count() as u32
+}
+
+/// The options `search` takes, named as in the Rust `SearchOptions` but in
+/// camelCase. Any left out keep the command line's defaults.
+#[napi(object)]
+#[derive(Clone, Debug, Default)]
+pub struct SearchOptions {
+    pub regex: Option<bool>,
+    pub ignore_case: Option<bool>,
+    pub line_regexp: Option<bool>,
+    pub fuzzy: Option<bool>,
+    pub max_errors: Option<u32>,
+    pub hidden: Option<bool>,
+    pub no_ignore: Option<bool>,
+    pub follow_links: Option<bool>,
+    pub one_file_system: Option<bool>,
+    pub max_depth: Option<u32>,
+    pub globs: Option<Vec<String>>,
+    pub exclude: Option<Vec<String>>,
+    pub exclude_dirs: Option<Vec<String>>,
+    pub types: Option<Vec<String>>,
+    pub max_filesize: Option<u32>,
+    pub max_count: Option<u32>,
+    pub limit: Option<u32>,
+    pub threads: Option<u32>,
+    pub text: Option<bool>,
+    pub binary: Option<bool>,
+    pub replace: Option<String>,
+}
+
+impl SearchOptions {
+    fn into_options(self, patterns: Vec<String>) -> rusty_scout::SearchOptions {
+        let count = |n: Option<u32>| n.map(|n| n as usize);
+        rusty_scout::SearchOptions {
+            patterns,
+            regex: self.regex.unwrap_or_default(),
+            ignore_case: self.ignore_case.unwrap_or_default(),
+            line_regexp: self.line_regexp.unwrap_or_default(),
+            fuzzy: self.fuzzy.unwrap_or_default(),
+            max_errors: count(self.max_errors),
+            hidden: self.hidden.unwrap_or_default(),
+            no_ignore: self.no_ignore.unwrap_or_default(),
+            follow_links: self.follow_links.unwrap_or_default(),
+            one_file_system: self.one_file_system.unwrap_or_default(),
+            max_depth: count(self.max_depth),
+            globs: self.globs.unwrap_or_default(),
+            exclude: self.exclude.unwrap_or_default(),
+            exclude_dirs: self.exclude_dirs.unwrap_or_default(),
+            types: self.types.unwrap_or_default(),
+            max_filesize: count(self.max_filesize),
+            max_count: count(self.max_count),
+            limit: count(self.limit),
+            threads: count(self.threads).unwrap_or_default(),
+            text: self.text.unwrap_or_default(),
+            binary: self.binary.unwrap_or_default(),
+            replace: self.replace,
+        }
+    }
+}
+
+/// Called with each result as it's found; returning `false` stops the
+/// search.
+type OnMatch = ThreadsafeFunction<SearchResult, Option<bool>, SearchResult, Status, false>;
+
+/// A search waiting for, or running on, a libuv thread.
+pub struct Search {
+    options: rusty_scout::SearchOptions,
+    path: PathBuf,
+    on_match: Option<OnMatch>,
+}
+
+impl Task for Search {
+    type Output = Either<Vec<SearchResult>, u32>;
+    type JsValue = Either<Vec<SearchResult>, u32>;
+
+    fn compute(&mut self) -> napi::Result<Self::Output> {
+        let searcher = SearcherBuilder::from_options(self.options.clone())
+            .build()
+            .map_err(|e| Error::from_reason(e.to_string()))?;
+        let found = searcher.search_iter(self.path.clone());
+        match &self.on_match {
+            Some(on_match) => deliver(found, |result| call(on_match, result)).map(Either::B),
+            None => found
+                .map(|result| result.map(SearchResult::from).map_err(to_napi))
+                .collect::<napi::Result<_>>()
+                .map(Either::A),
+        }
+    }
+
+    fn resolve(&mut self, _env: Env, output: Self::Output) -> napi::Result<Self::JsValue> {
+        Ok(output)
+    }
+}
+
+/// Hands `on_match` each of `found`'s results until it returns `false`, which
+/// stops the search, returning how many it was handed.
+fn deliver(found: SearchIter, mut on_match: impl FnMut(SearchResult) -> bool) -> napi::Result<u32> {
+    let mut delivered = 0;
+    for result in found {
+        delivered += 1;
+        if !on_match(SearchResult::from(result.map_err(to_napi)?)) {
+            break;
+        }
+    }
+    Ok(delivered)
+}
+
+/// Calls `on_match` with `result` on the JavaScript thread and waits for
+/// what it returns, so a slow callback holds the search back instead of
+/// results queueing up, and none reach it after it's said to stop.
+fn call(on_match: &OnMatch, result: SearchResult) -> bool {
+    let (sender, returned) = mpsc::channel();
+    on_match.call_with_return_value(
+        result,
+        ThreadsafeFunctionCallMode::Blocking,
+        move |value, _| {
+            let _ = sender.send(value);
+            Ok(())
+        },
+    );
+    // The sender is dropped unsent if the call couldn't be made, as when
+    // Node is exiting.
+    !matches!(returned.recv(), Ok(Ok(Some(false))) | Err(_))
+}
+
+fn to_napi(e: rusty_scout::SearchError) -> Error {
+    Error::from_reason(e.to_string())
+}
+
+/// Searches `path`, a file or directory, for `pattern`, which may also be an
+/// array of patterns. Resolves to the results, or, when `onMatch` is given,
+/// hands it each result as it's found and resolves to how many there were.
+#[napi(
+    ts_args_type = "pattern: string | string[], path?: string, options?: SearchOptions, onMatch?: (result: SearchResult) => boolean | void",
+    ts_return_type = "Promise<SearchResult[] | number>"
+)]
+pub fn search(
+    pattern: Either<String, Vec<String>>,
+    path: Option<String>,
+    options: Option<SearchOptions>,
+    on_match: Option<OnMatch>,
+) -> AsyncTask<Search> {
+    let patterns = match pattern {
+        Either::A(pattern) => vec![pattern],
+        Either::B(patterns) => patterns,
+    };
+    AsyncTask::new(Search {
+        options: options.unwrap_or_default().into_options(patterns),
+        path: PathBuf::from(path.unwrap_or_else(|| ".".to_string())),
+        on_match,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_search() -> Result<(), Box<dyn std::error::Error>> {
+        let dir = tempdir()?;
+        fs::write(dir.path().join("a.txt"), "😀 NEEDLE\nhay\n")?;
+        fs::write(dir.path().join("b.js"), "needle\n")?;
+
+        let mut search = Search {
+            options: SearchOptions {
+                ignore_case: Some(true),
+                globs: Some(vec!["*.txt".to_string()]),
+                ..SearchOptions::default()
+            }
+            .into_options(vec!["needle".to_string()]),
+            path: dir.path().to_path_buf(),
+            on_match: None,
+        };
+        let Either::A(results) = search.compute()? else {
+            panic!("expected results");
+        };
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].line_number, 1);
+        // "😀" is four bytes but two UTF-16 code units.
+        assert_eq!(results[0].matches, [Match { start: 3, end: 9 }]);
+
+        search.options.regex = true;
+        search.options.fuzzy = true;
+        assert!(search.compute().is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_deliver_stops() -> Result<(), Box<dyn std::error::Error>> {
+        let dir = tempdir()?;
+        for i in 0..100 {
+            fs::write(dir.path().join(format!("{}.txt", i)), "needle\n".repeat(10))?;
+        }
+        let searcher = SearcherBuilder::new().pattern("needle").build()?;
+        let mut handed = 0;
+        let delivered = deliver(searcher.search_iter(dir.path()), |_| {
+            handed += 1;
+            false
+        })?;
+        assert_eq!((delivered, handed), (1, 1));
+        Ok(())
+    }
+}
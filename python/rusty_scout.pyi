@@ -0,0 +1,38 @@
+from os import PathLike
+from pathlib import Path
+from typing import Optional, Sequence, Union
+
+class SearchResult:
+    path: Path
+    line_number: int
+    line: str
+    matches: list[tuple[int, int]]
+    score: Optional[int]
+    replacement: Optional[str]
+
+def search(
+    pattern: Union[str, Sequence[str]],
+    path: Union[str, PathLike[str]] = ".",
+    *,
+    regex: bool = False,
+    ignore_case: bool = False,
+    line_regexp: bool = False,
+    fuzzy: bool = False,
+    max_errors: Optional[int] = None,
+    hidden: bool = False,
+    no_ignore: bool = False,
+    follow_links: bool = False,
+    one_file_system: bool = False,
+    max_depth: Optional[int] = None,
+    globs: Sequence[str] = (),
+    exclude: Sequence[str] = (),
+    exclude_dirs: Sequence[str] = (),
+    types: Sequence[str] = (),
+    max_filesize: Optional[int] = None,
+    max_count: Optional[int] = None,
+    limit: Optional[int] = None,
+    threads: int = 0,
+    text: bool = False,
+    binary: bool = False,
+    replace: Optional[str] = None,
+) -> list[SearchResult]: ...
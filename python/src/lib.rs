@@ -0,0 +1,199 @@
+//! The `rusty_scout` Python module, built with `maturin build` from this
+//! directory:
+//!
+//! ```python
+//! import rusty_scout
+//!
+//! for result in rusty_scout.search("TODO", "src", ignore_case=True, types=["rust"]):
+//!     print(result.path, result.line_number, result.line)
+//! ```
+//!
+//! Without the `python` feature this crate is empty.
+#![cfg(feature = "python")]
+
+use pyo3::exceptions::{PyTypeError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use rusty_scout::{SearchOptions, SearchResult, SearcherBuilder};
+use std::path::PathBuf;
+
+/// A match, and the line it was found on.
+#[pyclass(
+    frozen,
+    get_all,
+    skip_from_py_object,
+    name = "SearchResult",
+    module = "rusty_scout"
+)]
+#[derive(Clone, Debug)]
+struct PySearchResult {
+    path: PathBuf,
+    /// Counting from 1, or 0 for results about a whole file.
+    line_number: usize,
+    line: String,
+    /// (start, end) of each match, in characters, so `line[start:end]` is
+    /// the matched text.
+    matches: Vec<(usize, usize)>,
+    /// How good a fuzzy match is; higher is better.
+    score: Option<i64>,
+    /// The line with its matches replaced, when searching with `replace`.
+    replacement: Option<String>,
+}
+
+#[pymethods]
+impl PySearchResult {
+    fn __repr__(&self) -> String {
+        format!(
+            "SearchResult(path={:?}, line_number={}, line={:?})",
+            self.path.display().to_string(),
+            self.line_number,
+            self.line
+        )
+    }
+}
+
+impl From<SearchResult> for PySearchResult {
+    fn from(result: SearchResult) -> Self {
+        let chars = |byte| char_offset(&result.line, byte);
+        PySearchResult {
+            matches: result
+                .matches
+                .iter()
+                .map(|&(start, end)| (chars(start), chars(end)))
+                .collect(),
+            path: result.file_path,
+            line_number: result.line_number,
+            score: result.score,
+            replacement: result.replacement.map(|replacement| replacement.line),
+            line: result.line,
+        }
+    }
+}
+
+/// How many characters of `line` come before byte `byte`.
+fn char_offset(line: &str, byte: usize) -> usize {
+    line.char_indices().take_while(|&(i, _)| i < byte).count()
+}
+
+/// One pattern or several, any of which may match.
+#[derive(FromPyObject)]
+enum Patterns {
+    One(String),
+    Many(Vec<String>),
+}
+
+/// Sets the search option `key`, named as in `SearchOptions`, to `value`.
+fn set_option(options: &mut SearchOptions, key: &str, value: &Bound<'_, PyAny>) -> PyResult<()> {
+    match key {
+        "regex" => options.regex = value.extract()?,
+        "ignore_case" => options.ignore_case = value.extract()?,
+        "line_regexp" => options.line_regexp = value.extract()?,
+        "fuzzy" => options.fuzzy = value.extract()?,
+        "max_errors" => options.max_errors = value.extract()?,
+        "hidden" => options.hidden = value.extract()?,
+        "no_ignore" => options.no_ignore = value.extract()?,
+        "follow_links" => options.follow_links = value.extract()?,
+        "one_file_system" => options.one_file_system = value.extract()?,
+        "max_depth" => options.max_depth = value.extract()?,
+        "globs" => options.globs = value.extract()?,
+        "exclude" => options.exclude = value.extract()?,
+        "exclude_dirs" => options.exclude_dirs = value.extract()?,
+        "types" => options.types = value.extract()?,
+        "max_filesize" => options.max_filesize = value.extract()?,
+        "max_count" => options.max_count = value.extract()?,
+        "limit" => options.limit = value.extract()?,
+        "threads" => options.threads = value.extract()?,
+        "text" => options.text = value.extract()?,
+        "binary" => options.binary = value.extract()?,
+        "replace" => options.replace = value.extract()?,
+        _ => {
+            return Err(PyTypeError::new_err(format!(
+                "search() got an unexpected keyword argument '{}'",
+                key
+            )))
+        }
+    }
+    Ok(())
+}
+
+/// Searches `path`, a file or directory, for `pattern`, which may also be a
+/// list of patterns. Keyword arguments are the fields of the Rust
+/// `SearchOptions`, e.g. `ignore_case=True` or `globs=["*.py"]`.
+#[pyfunction]
+#[pyo3(signature = (pattern, path = PathBuf::from("."), **options))]
+fn search(
+    py: Python<'_>,
+    pattern: Patterns,
+    path: PathBuf,
+    options: Option<&Bound<'_, PyDict>>,
+) -> PyResult<Vec<PySearchResult>> {
+    let mut search_options = SearchOptions {
+        patterns: match pattern {
+            Patterns::One(pattern) => vec![pattern],
+            Patterns::Many(patterns) => patterns,
+        },
+        ..SearchOptions::default()
+    };
+    for (key, value) in options.into_iter().flatten() {
+        set_option(&mut search_options, &key.extract::<String>()?, &value)?;
+    }
+    let searcher = SearcherBuilder::from_options(search_options)
+        .build()
+        .map_err(|e| PyValueError::new_err(e.to_string()))?;
+    // Other Python threads can run while this one searches.
+    let results = py
+        .detach(|| searcher.search(&path).map_err(|e| e.to_string()))
+        .map_err(PyValueError::new_err)?;
+    Ok(results.into_iter().map(PySearchResult::from).collect())
+}
+
+#[pymodule(name = "rusty_scout")]
+mod rusty_scout_module {
+    #[pymodule_export]
+    use super::{search, PySearchResult};
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_search() -> Result<(), Box<dyn std::error::Error>> {
+        let dir = tempdir()?;
+        fs::write(dir.path().join("a.txt"), "café NEEDLE\nhay\n")?;
+        fs::write(dir.path().join("b.py"), "needle\n")?;
+
+        Python::initialize();
+        Python::attach(|py| -> PyResult<()> {
+            let options = PyDict::new(py);
+            options.set_item("ignore_case", true)?;
+            options.set_item("globs", vec!["*.txt"])?;
+            let results = search(
+                py,
+                Patterns::One("needle".to_string()),
+                dir.path().to_path_buf(),
+                Some(&options),
+            )?;
+            assert_eq!(results.len(), 1);
+            assert_eq!(results[0].path, dir.path().join("a.txt"));
+            assert_eq!(results[0].line_number, 1);
+            // "é" is two bytes but one character.
+            assert_eq!(results[0].matches, [(5, 11)]);
+
+            options.set_item("colour", true)?;
+            let error = search(
+                py,
+                Patterns::Many(vec!["a".to_string()]),
+                dir.path().to_path_buf(),
+                Some(&options),
+            )
+            .unwrap_err();
+            assert!(error.is_instance_of::<PyTypeError>(py));
+            Ok(())
+        })?;
+
+        Ok(())
+    }
+}
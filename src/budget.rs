@@ -0,0 +1,83 @@
+use std::sync::{Arc, Condvar, Mutex};
+
+/// A number of bytes shared between threads, for `--max-memory`. Threads
+/// reserve what they're about to hold and wait while there isn't room.
+pub struct MemoryBudget {
+    limit: usize,
+    used: Mutex<usize>,
+    freed: Condvar,
+}
+
+/// Bytes held from a `MemoryBudget` until this is dropped.
+pub struct Reservation {
+    budget: Arc<MemoryBudget>,
+    size: usize,
+}
+
+impl MemoryBudget {
+    pub fn new(limit: usize) -> Arc<Self> {
+        Arc::new(MemoryBudget {
+            limit,
+            used: Mutex::new(0),
+            freed: Condvar::new(),
+        })
+    }
+
+    pub fn limit(&self) -> usize {
+        self.limit
+    }
+
+    /// Waits until `size` more bytes fit and reserves them. Something larger
+    /// than the whole budget waits until nothing else is reserved instead of
+    /// waiting forever.
+    pub fn reserve(self: &Arc<Self>, size: usize) -> Reservation {
+        let mut used = self.used.lock().unwrap();
+        while *used > 0 && *used + size > self.limit {
+            used = self.freed.wait(used).unwrap();
+        }
+        *used += size;
+        Reservation {
+            budget: Arc::clone(self),
+            size,
+        }
+    }
+
+    #[cfg(test)]
+    pub fn used(&self) -> usize {
+        *self.used.lock().unwrap()
+    }
+}
+
+impl Drop for Reservation {
+    fn drop(&mut self) {
+        *self.budget.used.lock().unwrap() -= self.size;
+        self.budget.freed.notify_all();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_reserve_waits_for_room() {
+        let budget = MemoryBudget::new(100);
+        let first = budget.reserve(80);
+
+        let waiter = {
+            let budget = Arc::clone(&budget);
+            std::thread::spawn(move || budget.reserve(50).size)
+        };
+        std::thread::sleep(Duration::from_millis(50));
+        assert_eq!(budget.used(), 80);
+        drop(first);
+        assert_eq!(waiter.join().unwrap(), 50);
+        assert_eq!(budget.used(), 0);
+
+        // Larger than the whole budget, but nothing else is held.
+        let huge = budget.reserve(500);
+        assert_eq!(budget.used(), 500);
+        drop(huge);
+    }
+}
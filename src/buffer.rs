@@ -0,0 +1,70 @@
+use std::cell::RefCell;
+use std::ops::{Deref, DerefMut};
+
+/// Buffers that grew past this are freed rather than kept for reuse, so one
+/// big file doesn't pin its size for the rest of the run.
+const MAX_RETAINED: usize = 1024 * 1024;
+
+thread_local! {
+    /// Each worker thread's spare buffer.
+    static SPARE: RefCell<Vec<u8>> = const { RefCell::new(Vec::new()) };
+}
+
+/// A byte buffer borrowed from the current thread, handed back for the next
+/// file when dropped instead of being freed.
+pub struct PooledBuffer(Vec<u8>);
+
+impl PooledBuffer {
+    /// Takes this thread's spare buffer (empty, but with its old capacity).
+    pub fn take() -> Self {
+        PooledBuffer(SPARE.with(|spare| std::mem::take(&mut *spare.borrow_mut())))
+    }
+}
+
+impl Drop for PooledBuffer {
+    fn drop(&mut self) {
+        if self.0.capacity() <= MAX_RETAINED {
+            let mut buffer = std::mem::take(&mut self.0);
+            buffer.clear();
+            // A thread that's exiting has nowhere to keep it.
+            let _ = SPARE.try_with(|spare| *spare.borrow_mut() = buffer);
+        }
+    }
+}
+
+impl Deref for PooledBuffer {
+    type Target = Vec<u8>;
+
+    fn deref(&self) -> &Vec<u8> {
+        &self.0
+    }
+}
+
+impl DerefMut for PooledBuffer {
+    fn deref_mut(&mut self) -> &mut Vec<u8> {
+        &mut self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_buffer_reused() {
+        let mut buffer = PooledBuffer::take();
+        buffer.extend_from_slice(&[1; 4096]);
+        let capacity = buffer.capacity();
+        drop(buffer);
+
+        let buffer = PooledBuffer::take();
+        assert!(buffer.is_empty());
+        assert_eq!(buffer.capacity(), capacity);
+        drop(buffer);
+
+        let mut huge = PooledBuffer::take();
+        huge.reserve(MAX_RETAINED * 2);
+        drop(huge);
+        assert!(PooledBuffer::take().capacity() <= MAX_RETAINED);
+    }
+}
@@ -0,0 +1,1511 @@
+#[cfg(unix)]
+use crate::daemon;
+use crate::query::QueryScope;
+use crate::searcher::{Event, Searcher, RESULT_CHANNEL_CAPACITY};
+use crate::sink::{deliver, feed, Counter, Sink};
+#[cfg(feature = "syntax")]
+use crate::syntax;
+#[cfg(feature = "self-update")]
+use crate::update;
+use crate::walker::parse_file_list;
+use crate::{
+    clipboard, config, doctor, edit, history, http, logging, mcp, perms, rpc, stats, types, units,
+};
+use clap::{CommandFactory, Parser, ValueEnum};
+use clap_complete::engine::{ArgValueCompleter, CompletionCandidate};
+use clap_complete::env::Shells;
+use clap_complete::Shell;
+use colored::*;
+use indicatif::ProgressDrawTarget;
+use regex::Regex;
+use serde::Deserialize;
+use std::error::Error;
+use std::fs;
+use std::io::{BufRead, IsTerminal, Write};
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::sync::mpsc;
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
+
+#[derive(Parser, Debug)]
+#[command(
+    name = "Rusty Scout",
+    about = "A blazingly fast file search tool written in Rust",
+    version = "1.0.0",
+    author = "Your Name",
+    // So flags given later, as on the command line after
+    // RUSTY_SCOUT_DEFAULT_FLAGS, replace ones given earlier.
+    args_override_self = true
+)]
+pub struct Args {
+    /// Pattern to search for, when no -p or other pattern option is given
+    #[arg(value_name = "PATTERN")]
+    pub(crate) pattern_arg: Option<String>,
+
+    /// Files or directories to search (default: current directory)
+    #[arg(value_name = "PATH")]
+    pub(crate) paths: Vec<PathBuf>,
+
+    /// Search the files listed in FILE (or stdin for `-`), one per line or NUL-separated
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["paths", "directory"])]
+    pub(crate) files_from: Option<PathBuf>,
+
+    /// Print each file that would be searched, without searching it
+    #[arg(
+        long,
+        conflicts_with_all = ["pattern", "pattern_file", "query", "bytes", "near", "rules", "identifier", "entropy_scan", "match_paths"]
+    )]
+    pub(crate) files: bool,
+
+    /// Directory to search in, in addition to any PATH arguments
+    #[arg(short, long)]
+    pub(crate) directory: Option<PathBuf>,
+
+    /// Pattern to search for (can be repeated)
+    #[arg(short, long)]
+    pub(crate) pattern: Vec<String>,
+
+    /// Read additional patterns from a file, one per line
+    #[arg(long)]
+    pub(crate) pattern_file: Option<PathBuf>,
+
+    /// File extensions (rs, tar.gz) or exact file names (Makefile) to search, comma-separated
+    #[arg(short, long, default_value = "*")]
+    pub(crate) extensions: String,
+
+    /// Use regex for pattern matching
+    #[arg(short = 'E', long, visible_alias = "extended-regexp")]
+    pub(crate) regex: bool,
+
+    /// Treat `*` and `?` in the pattern as shell-style wildcards
+    #[arg(long, conflicts_with = "regex")]
+    pub(crate) glob_pattern: bool,
+
+    /// Match approximately: pattern characters must appear in order; results are ranked by score
+    #[arg(long, conflicts_with_all = ["regex", "glob_pattern"])]
+    pub(crate) fuzzy: bool,
+
+    /// Match the pattern with up to N character insertions, deletions or substitutions
+    #[arg(long, value_name = "N", conflicts_with_all = ["regex", "glob_pattern", "fuzzy"])]
+    pub(crate) max_errors: Option<usize>,
+
+    /// Boolean query of terms combined with AND, OR, NOT and parentheses
+    #[arg(long, conflicts_with_all = ["pattern", "pattern_file", "fuzzy"])]
+    pub(crate) query: Option<String>,
+
+    /// Search for raw bytes, given as hex ("DE AD BE EF") or `\xDE\xAD` escapes
+    #[arg(long, conflicts_with_all = ["pattern", "pattern_file", "query"])]
+    pub(crate) bytes: Option<String>,
+
+    /// Report places where two patterns occur within N lines of each other ("foo,bar,5")
+    #[arg(long, value_name = "A,B,N", conflicts_with_all = ["pattern", "pattern_file", "query", "bytes"])]
+    pub(crate) near: Option<NearSpec>,
+
+    /// Scan with every rule (name, regex, severity, description) in a TOML or YAML file
+    #[arg(
+        long,
+        value_name = "FILE",
+        conflicts_with_all = ["pattern", "pattern_file", "query", "bytes", "near"]
+    )]
+    pub(crate) rules: Option<PathBuf>,
+
+    /// Match NAME only as a complete identifier, optionally qualified with `::` or `.`
+    #[arg(
+        long,
+        value_name = "NAME",
+        conflicts_with_all = ["pattern", "pattern_file", "query", "bytes", "near", "rules", "fuzzy"]
+    )]
+    pub(crate) identifier: Option<String>,
+
+    /// Flag high-entropy tokens that look like credentials or keys, without any pattern
+    #[arg(
+        long,
+        conflicts_with_all = ["pattern", "pattern_file", "query", "bytes", "near", "rules", "identifier"]
+    )]
+    pub(crate) entropy_scan: bool,
+
+    /// Minimum Shannon entropy, in bits per character, for --entropy-scan to flag a token
+    #[arg(
+        long,
+        value_name = "BITS",
+        default_value_t = 4.0,
+        requires = "entropy_scan"
+    )]
+    pub(crate) entropy_threshold: f64,
+
+    /// Shortest token --entropy-scan considers
+    #[arg(
+        long,
+        value_name = "N",
+        default_value_t = 20,
+        requires = "entropy_scan"
+    )]
+    pub(crate) entropy_min_length: usize,
+
+    /// Match a code template against syntax trees; `$X` matches any node and `$$$X` any run of nodes
+    #[cfg(feature = "syntax")]
+    #[arg(
+        long,
+        value_name = "CODE",
+        conflicts_with_all = ["pattern", "pattern_file", "query", "bytes", "near", "rules", "identifier", "entropy_scan", "scope", "files"]
+    )]
+    pub(crate) structural: Option<String>,
+
+    /// Only search files that contain this pattern somewhere (can be repeated)
+    #[arg(long, value_name = "PATTERN")]
+    pub(crate) file_contains: Vec<String>,
+
+    /// Only search files that don't contain this pattern anywhere (can be repeated)
+    #[arg(long, value_name = "PATTERN")]
+    pub(crate) file_not_contains: Vec<String>,
+
+    /// Only search files whose contents look like this MIME type, e.g. text/* (comma-separated or repeated)
+    #[arg(long, value_name = "TYPE", value_delimiter = ',')]
+    pub(crate) mime: Vec<String>,
+
+    /// Only search files of this type, e.g. rust or py (can be repeated; see --type-list)
+    #[arg(short = 't', long = "type", value_name = "TYPE", add = ArgValueCompleter::new(complete_types))]
+    pub(crate) types: Vec<String>,
+
+    /// Define a file type for --type, or add globs to one ('web:*.html,*.css,*.js'; can be repeated)
+    #[arg(long, value_name = "NAME:GLOBS")]
+    pub(crate) type_add: Vec<types::TypeDefinition>,
+
+    /// List the file types known to --type and exit
+    #[arg(long)]
+    pub(crate) type_list: bool,
+
+    /// Print a completion script for this shell (bash, zsh, fish, powershell or elvish) and exit
+    #[arg(long, value_name = "SHELL")]
+    pub(crate) completions: Option<Shell>,
+
+    /// Print the rusty-scout(1) man page, in roff, and exit
+    #[arg(long)]
+    pub(crate) generate_man: bool,
+
+    /// Print the JSON Schema of --json's results and exit
+    #[arg(long)]
+    pub(crate) schema: bool,
+
+    /// Report the config and ignore files, colors and threads a search of the paths given would use, try a small search, and exit
+    #[arg(long)]
+    pub(crate) doctor: bool,
+
+    /// Apply the options saved as this profile in a config file, before the command line's
+    #[arg(long, value_name = "NAME", add = ArgValueCompleter::new(complete_profiles))]
+    pub(crate) profile: Option<String>,
+
+    /// Save the rest of the command line's options as this profile in the user config file and exit
+    #[arg(long, value_name = "NAME", conflicts_with = "profile")]
+    pub(crate) profile_save: Option<String>,
+
+    /// List the profiles in the config files and exit
+    #[arg(long)]
+    pub(crate) profile_list: bool,
+
+    /// Replace this binary with the latest release's, after checking its checksum, and exit
+    #[arg(long)]
+    pub(crate) self_update: bool,
+
+    /// List recent searches, numbered for --repeat, and exit
+    #[arg(long)]
+    pub(crate) history: bool,
+
+    /// Run the Nth most recent search again (default: 1, the last), with any other options given added to it
+    #[arg(long, value_name = "N", num_args = 0..=1, default_missing_value = "1")]
+    pub(crate) repeat: Option<usize>,
+
+    /// What was given on the command line, for --profile-save and the history.
+    #[arg(skip)]
+    pub(crate) command_line: Vec<String>,
+
+    /// The flags parsed: `RUSTY_SCOUT_DEFAULT_FLAGS`'s, then a --profile's,
+    /// then the command line's, for --connect to hand to the daemon.
+    #[arg(skip)]
+    pub(crate) resolved: Vec<String>,
+
+    /// Whether searches are added to the history, as they are when run
+    /// from the binary.
+    #[arg(skip)]
+    pub(crate) record_history: bool,
+
+    /// Only search files matching this glob, or skip them if it starts with `!` (can be repeated)
+    #[arg(
+        short = 'g',
+        long = "glob",
+        visible_alias = "include",
+        value_name = "GLOB"
+    )]
+    pub(crate) globs: Vec<String>,
+
+    /// Skip files and directories matching this glob, e.g. 'target/**' (can be repeated)
+    #[arg(long, value_name = "GLOB")]
+    pub(crate) exclude: Vec<String>,
+
+    /// Don't descend into directories with this name, e.g. node_modules (can be repeated)
+    #[arg(long, value_name = "NAME")]
+    pub(crate) exclude_dir: Vec<String>,
+
+    /// Search hidden files and directories (names starting with `.`)
+    #[arg(long, overrides_with = "no_hidden")]
+    pub(crate) hidden: bool,
+
+    /// Leave hidden files and directories out, undoing --hidden or a config file's `hidden`
+    #[arg(long, overrides_with = "hidden")]
+    pub(crate) no_hidden: bool,
+
+    /// Don't respect ignore files (.gitignore, .ignore, .rscoutignore and the like)
+    #[arg(long, overrides_with = "ignore")]
+    pub(crate) no_ignore: bool,
+
+    /// Respect ignore files, undoing --no-ignore or a config file's `no_ignore`
+    #[arg(long, overrides_with = "no_ignore")]
+    pub(crate) ignore: bool,
+
+    /// Don't respect version control ignore files (.gitignore, .git/info/exclude, the global gitignore)
+    #[arg(long, overrides_with = "ignore_vcs")]
+    pub(crate) no_ignore_vcs: bool,
+
+    /// Respect version control ignore files, undoing --no-ignore-vcs or a config file's `no_ignore_vcs`
+    #[arg(long, overrides_with = "no_ignore_vcs")]
+    pub(crate) ignore_vcs: bool,
+
+    /// Don't respect ignore files in directories above the search root
+    #[arg(long, overrides_with = "ignore_parent")]
+    pub(crate) no_ignore_parent: bool,
+
+    /// Respect ignore files above the search root, undoing --no-ignore-parent or a config file's `no_ignore_parent`
+    #[arg(long, overrides_with = "no_ignore_parent")]
+    pub(crate) ignore_parent: bool,
+
+    /// Search every hard link to a file, not just the first one found
+    #[arg(long)]
+    pub(crate) no_hardlink_dedup: bool,
+
+    /// Don't descend into directories on other filesystems or mount points
+    #[arg(long)]
+    pub(crate) one_file_system: bool,
+
+    /// Follow symbolic links to files and directories
+    #[arg(short = 'L', long)]
+    pub(crate) follow: bool,
+
+    /// Skip files larger than this (e.g. 10M), reporting them as skipped
+    #[arg(long, value_name = "SIZE", value_parser = units::parse_size)]
+    pub(crate) max_filesize: Option<usize>,
+
+    /// Only search files modified after this date or duration ago (e.g. 2d, 2024-01-01)
+    #[arg(long, value_name = "WHEN", value_parser = units::parse_time)]
+    pub(crate) newer_than: Option<SystemTime>,
+
+    /// Only search files modified before this date or duration ago (e.g. 2d, 2024-01-01)
+    #[arg(long, value_name = "WHEN", value_parser = units::parse_time)]
+    pub(crate) older_than: Option<SystemTime>,
+
+    /// Only search files owned by this user, by name or ID (Unix only)
+    #[arg(long, value_name = "USER")]
+    pub(crate) owner: Option<perms::Owner>,
+
+    /// Only search files whose mode is exactly MODE, has all of -MODE's bits or any of /MODE's (Unix only)
+    #[arg(long, value_name = "MODE", allow_hyphen_values = true)]
+    pub(crate) perm: Option<perms::PermFilter>,
+
+    /// Descend at most N directories below the search root (0 searches only the root itself)
+    #[arg(long, value_name = "N")]
+    pub(crate) max_depth: Option<usize>,
+
+    /// Only search files whose path matches this regex (e.g. 'src/.*handler.*\.rs')
+    #[arg(long, value_name = "REGEX")]
+    pub(crate) path_pattern: Option<Regex>,
+
+    /// Match the pattern against file paths instead of file contents
+    #[arg(long, conflicts_with_all = ["bytes", "near", "rules"])]
+    #[cfg_attr(feature = "syntax", arg(conflicts_with_all = ["structural", "scope"]))]
+    pub(crate) match_paths: bool,
+
+    /// Only report matches inside this part of the code (Rust, Python and JavaScript files)
+    #[cfg(feature = "syntax")]
+    #[arg(long, value_enum)]
+    pub(crate) scope: Option<syntax::Scope>,
+
+    /// Evaluate --query against each line or against the whole file
+    #[arg(long, value_enum, default_value_t = QueryScope::Line)]
+    pub(crate) query_scope: QueryScope,
+
+    /// Only report lines that the pattern matches in their entirety
+    #[arg(short = 'x', long, conflicts_with = "fuzzy")]
+    pub(crate) line_regexp: bool,
+
+    /// Ignore case when searching
+    #[arg(short, long)]
+    pub(crate) ignore_case: bool,
+
+    /// Use full Unicode case folding and NFC normalization for literal patterns
+    #[arg(long)]
+    pub(crate) unicode: bool,
+
+    /// Print only the path of each file with a match (reading each only up to its first)
+    #[arg(short = 'l', long)]
+    pub(crate) files_with_matches: bool,
+
+    /// Print only how many lines match in each file with a match, as `PATH:COUNT`
+    #[arg(short = 'c', long, conflicts_with_all = ["json", "files", "files_with_matches", "match_paths", "quiet"])]
+    pub(crate) count: bool,
+
+    /// Does nothing: directories are always searched recursively (for grep compatibility)
+    #[arg(short = 'r', long)]
+    pub(crate) recursive: bool,
+
+    /// Does nothing: line numbers are always shown (for grep compatibility)
+    #[arg(short = 'n', long)]
+    pub(crate) line_number: bool,
+
+    /// Does nothing: file names are always shown (for grep compatibility)
+    #[arg(short = 'H', long)]
+    pub(crate) with_filename: bool,
+
+    /// Copy the results to the clipboard as well, as printed but without colors
+    #[arg(long, conflicts_with_all = ["quiet", "count", "bench", "connect"])]
+    pub(crate) clipboard: bool,
+
+    /// Afterwards, open the files with matches in $VISUAL or $EDITOR, each at its first match
+    #[arg(long, conflicts_with_all = ["json", "quiet", "count", "bench", "connect", "files"])]
+    pub(crate) edit: bool,
+
+    /// With --edit, open the files one at a time, each once the editor has exited from the last
+    #[arg(long, requires = "edit")]
+    pub(crate) edit_each: bool,
+
+    /// Don't report files and directories that can't be read, nor let them make the exit status 2
+    #[arg(short = 's', long)]
+    pub(crate) no_messages: bool,
+
+    /// Print nothing; exit with status 0 on the first match anywhere, or 1 if there is none
+    #[arg(short, long)]
+    pub(crate) quiet: bool,
+
+    /// Explain on stderr why files were left out, and how long each step took
+    // No -v, which grep uses for inverting the match.
+    #[arg(long)]
+    pub(crate) verbose: bool,
+
+    /// Log what --verbose does and more, such as each file searched
+    #[arg(long)]
+    pub(crate) debug: bool,
+
+    /// Stop searching a file after this many matching lines
+    #[arg(short, long, value_name = "N")]
+    pub(crate) max_count: Option<usize>,
+
+    /// Only search lines START:END of each file (1-based, inclusive; either end may be omitted)
+    #[arg(long, value_name = "START:END")]
+    pub(crate) line_range: Option<LineRange>,
+
+    /// Treat lines longer than this many bytes as --long-lines says, e.g. in minified files
+    #[arg(long, value_name = "BYTES", value_parser = units::parse_size)]
+    pub(crate) max_line_length: Option<usize>,
+
+    /// Whether lines over --max-line-length are truncated or skipped
+    #[arg(long, value_enum, default_value_t = LongLines::Truncate, requires = "max_line_length")]
+    pub(crate) long_lines: LongLines,
+
+    /// Stop the whole search once this many results have been collected
+    #[arg(long, value_name = "N")]
+    pub(crate) limit: Option<usize>,
+
+    /// Regex engine to use with --regex
+    #[arg(long, value_enum, default_value_t = Engine::Default)]
+    pub(crate) engine: Engine,
+
+    /// Maximum size of a compiled regex (e.g. 10M)
+    #[arg(long, value_name = "SIZE", value_parser = units::parse_size)]
+    pub(crate) regex_size_limit: Option<usize>,
+
+    /// Maximum size of the regex DFA cache (e.g. 10M)
+    #[arg(long, value_name = "SIZE", value_parser = units::parse_size)]
+    pub(crate) dfa_size_limit: Option<usize>,
+
+    /// Search with at most N threads (default: 0, one per core)
+    #[arg(short = 'j', long, value_name = "N", default_value_t = 0)]
+    pub(crate) threads: usize,
+
+    /// Always read files into memory instead of memory-mapping large ones
+    #[arg(long)]
+    pub(crate) no_mmap: bool,
+
+    /// Memory-map files at least this large instead of reading them
+    #[arg(long, value_name = "SIZE", value_parser = units::parse_size, default_value = "4M")]
+    pub(crate) mmap_threshold: usize,
+
+    /// Search files at least this large line by line as they're read, when the search allows it
+    #[arg(long, value_name = "SIZE", value_parser = units::parse_size, default_value = "64M")]
+    pub(crate) stream_threshold: usize,
+
+    /// How files are read: `std`, or `uring` when built with the `io-uring` feature on Linux
+    #[arg(long, value_enum, default_value_t = IoBackend::Std, conflicts_with = "max_memory")]
+    pub(crate) io_backend: IoBackend,
+
+    /// Search binary files too, reporting only whether each one matched
+    #[arg(long, conflicts_with = "text")]
+    pub(crate) binary: bool,
+
+    /// Search every file as text, even ones that look binary
+    #[arg(short = 'a', long)]
+    pub(crate) text: bool,
+
+    /// Search the most recently modified files first, printing results as each file finishes
+    #[arg(long)]
+    pub(crate) recent_first: bool,
+
+    /// Print results as JSON Lines, one object per matching line
+    #[arg(long)]
+    pub(crate) json: bool,
+
+    /// When to color output: auto (when printing to a terminal), always or never
+    #[arg(long, value_name = "WHEN")]
+    pub(crate) color: Option<ColorChoice>,
+
+    /// Show each matching line with its matches replaced by TEXT
+    #[arg(
+        long,
+        value_name = "TEXT",
+        conflicts_with_all = ["bytes", "near", "rules", "entropy_scan", "files", "match_paths", "files_with_matches", "quiet"]
+    )]
+    #[cfg_attr(feature = "syntax", arg(conflicts_with = "structural"))]
+    pub(crate) replace: Option<String>,
+
+    /// Rewrite the files with the `--replace` text instead of only showing it
+    #[arg(long, requires = "replace", conflicts_with_all = ["limit", "binary"])]
+    pub(crate) write: bool,
+
+    /// Match the pattern in any case and give each replacement the case of the text it replaces
+    #[arg(long, requires = "replace")]
+    pub(crate) preserve_case: bool,
+
+    /// Replace only the Kth occurrence of the pattern in each file
+    #[arg(long, value_name = "K", requires = "replace")]
+    pub(crate) replace_nth: Option<std::num::NonZeroUsize>,
+
+    /// Replace at most N occurrences in the whole run
+    #[arg(long, value_name = "N", requires = "replace")]
+    pub(crate) replace_max: Option<usize>,
+
+    /// Write the `--replace` changes to FILE as a patch for `git apply` instead
+    #[arg(
+        long,
+        value_name = "FILE",
+        requires = "replace",
+        conflicts_with = "write"
+    )]
+    pub(crate) diff_output: Option<PathBuf>,
+
+    /// Keep the modification time of files `--write` changes
+    #[arg(long, requires = "write")]
+    pub(crate) preserve_mtime: bool,
+
+    /// Keep a copy of each file `--write` changes, as FILE.bak beside it
+    #[arg(long, requires = "write")]
+    pub(crate) backup: bool,
+
+    /// Suffix added to backup copies (default: .bak, or none with --backup-dir)
+    #[arg(long, value_name = "SUFFIX", requires = "write")]
+    pub(crate) backup_suffix: Option<String>,
+
+    /// Keep backup copies under DIR, at the same relative paths, instead of beside each file
+    #[arg(long, value_name = "DIR", requires = "write")]
+    pub(crate) backup_dir: Option<PathBuf>,
+
+    /// Cap the memory held by file contents and unprinted results (e.g. 512M); searching waits for room
+    #[arg(long, value_name = "SIZE", value_parser = units::parse_size, conflicts_with = "fuzzy")]
+    pub(crate) max_memory: Option<usize>,
+
+    /// Give up on a file whose matching takes longer than this (e.g. 5s, 500ms)
+    #[arg(long, value_name = "DURATION", value_parser = units::parse_duration)]
+    pub(crate) match_timeout: Option<Duration>,
+
+    /// Time the search over N runs (default 10) and report its speed instead of the results
+    #[arg(long, value_name = "N", num_args = 0..=1, default_missing_value = "10")]
+    pub(crate) bench: Option<usize>,
+
+    /// Untimed runs before the --bench runs, to warm caches
+    #[arg(long, value_name = "N", default_value_t = 1, requires = "bench")]
+    pub(crate) bench_warmup: usize,
+
+    /// Give up on a file whose whole search, reading included, takes longer than this (e.g. 5s)
+    #[arg(long, value_name = "DURATION", value_parser = units::parse_duration)]
+    pub(crate) file_timeout: Option<Duration>,
+
+    /// Serve JSON-RPC requests read from stdin, one per line, instead of searching
+    #[arg(long, conflicts_with_all = ["pattern_arg", "paths", "pattern", "files"])]
+    pub(crate) rpc: bool,
+
+    /// Serve MCP tools on stdin and stdout for coding agents, confined to the directory given (default: the current one)
+    #[arg(long, conflicts_with_all = ["pattern", "files", "rpc", "daemon", "connect"])]
+    pub(crate) mcp: bool,
+
+    /// Serve an HTTP search API on this address (e.g. 127.0.0.1:7878) over the directory given (default: the current one)
+    #[arg(long, value_name = "ADDR", conflicts_with_all = ["pattern", "files", "rpc", "mcp", "daemon", "connect"])]
+    pub(crate) serve: Option<String>,
+
+    /// Serve searches from --connect on a local socket, keeping file listings and compiled patterns between them (Unix only)
+    #[arg(long, conflicts_with_all = ["pattern_arg", "paths", "pattern", "files", "rpc"])]
+    pub(crate) daemon: bool,
+
+    /// Have a running --daemon do the search, instead of this process (Unix only)
+    #[arg(long, conflicts_with_all = ["files_from", "write", "diff_output", "bench", "rpc"])]
+    pub(crate) connect: bool,
+
+    /// Socket for --daemon and --connect (default $XDG_RUNTIME_DIR/rusty-scout.sock)
+    #[arg(long, value_name = "PATH")]
+    pub(crate) socket: Option<PathBuf>,
+}
+
+impl Args {
+    /// Parses options as given on the command line, without the program
+    /// name, e.g. `["TODO", "src", "--ignore-case"]`.
+    pub fn from_argv<I, T>(argv: I) -> Result<Self, clap::Error>
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<std::ffi::OsString> + Clone,
+    {
+        let argv: Vec<std::ffi::OsString> = argv.into_iter().map(Into::into).collect();
+        let mut args = Args::try_parse_from(
+            std::iter::once("rusty-scout".into()).chain(argv.iter().cloned()),
+        )?;
+        args.command_line = argv
+            .iter()
+            .map(|arg| arg.to_string_lossy().into_owned())
+            .collect();
+        args.resolved = args.command_line.clone();
+        args.resolve_positionals();
+        Ok(args)
+    }
+
+    /// Parses the process's command line, after the flags in
+    /// `RUSTY_SCOUT_DEFAULT_FLAGS`, which it overrides. Exits on errors, as
+    /// [`Parser::parse`] does.
+    pub fn parse_with_env() -> Self {
+        let mut argv = std::env::args_os();
+        let program = argv.next().unwrap_or_else(|| "rusty-scout".into());
+        let mut command_line: Vec<std::ffi::OsString> = argv.collect();
+        let defaults = match std::env::var_os(config::DEFAULT_FLAGS) {
+            Some(flags) => match flags.to_str().and_then(shlex::split) {
+                Some(flags) => flags.into_iter().map(Into::into).collect(),
+                None => exit_with(format!("{} isn't valid shell words", config::DEFAULT_FLAGS)),
+            },
+            None => Vec::new(),
+        };
+        // The default flags come first and the command line's last, so its
+        // win over a profile's, which win over the defaults.
+        let parse = |profile: &[std::ffi::OsString], command_line: &[std::ffi::OsString]| {
+            Args::parse_from(
+                std::iter::once(&program)
+                    .chain(&defaults)
+                    .chain(profile)
+                    .chain(command_line),
+            )
+        };
+        let lossy = |args: &[std::ffi::OsString]| -> Vec<String> {
+            args.iter()
+                .map(|arg| arg.to_string_lossy().into_owned())
+                .collect()
+        };
+
+        let mut args = parse(&[], &command_line);
+        let mut profile = Vec::new();
+        if let Some(back) = args.repeat {
+            let mut repeated = history::path()
+                .ok_or_else(|| "--repeat needs HOME or XDG_STATE_HOME".into())
+                .and_then(|path| history::entry(&path, back))
+                .unwrap_or_else(|e| exit_with(e));
+            // Options given with --repeat are added to the search's.
+            repeated.extend(without_option(&lossy(&command_line), "--repeat"));
+            command_line = repeated.into_iter().map(Into::into).collect();
+            args = parse(&[], &command_line);
+        }
+        if let Some(name) = args.profile.take() {
+            args.resolve_positionals();
+            profile = config::Config::profiles(&args.search_roots()[0])
+                .and_then(|mut profiles| {
+                    profiles.remove(&name).ok_or_else(|| {
+                        format!("no profile named {:?}; see --profile-list", name).into()
+                    })
+                })
+                .unwrap_or_else(|e| exit_with(e))
+                .into_iter()
+                .map(Into::into)
+                .collect();
+            args = parse(&profile, &command_line);
+        }
+        args.command_line = lossy(&command_line);
+        // Already applied, so the profile isn't looked up again.
+        args.resolved = lossy(&defaults)
+            .into_iter()
+            .chain(lossy(&profile))
+            .chain(without_option(&args.command_line, "--profile"))
+            .collect();
+        args.record_history = true;
+        args
+    }
+
+    /// Every option at its default, as with an empty command line.
+    pub(crate) fn defaults() -> Self {
+        Args::try_parse_from(["rusty-scout"]).expect("defaults are valid")
+    }
+
+    /// Whether a pattern, or something standing in for one, was given as an
+    /// option rather than positionally.
+    pub(crate) fn has_pattern_option(&self) -> bool {
+        #[cfg(feature = "syntax")]
+        let structural = self.structural.is_some();
+        #[cfg(not(feature = "syntax"))]
+        let structural = false;
+
+        !self.pattern.is_empty()
+            || self.files
+            || self.pattern_file.is_some()
+            || self.query.is_some()
+            || self.bytes.is_some()
+            || self.near.is_some()
+            || self.rules.is_some()
+            || self.identifier.is_some()
+            || structural
+            || !self.file_contains.is_empty()
+            || !self.file_not_contains.is_empty()
+            || self.entropy_scan
+            || self.mcp
+            || self.serve.is_some()
+            || self.doctor
+    }
+
+    /// Whether to match case-insensitively; `--preserve-case` needs every
+    /// casing found to keep it.
+    pub(crate) fn ignore_case(&self) -> bool {
+        self.ignore_case || self.preserve_case
+    }
+
+    /// Treats the first positional argument as a path when the pattern came
+    /// from an option, as in `rusty-scout -p foo src/`.
+    pub(crate) fn resolve_positionals(&mut self) {
+        if let Some(first) = self.pattern_arg.take() {
+            if self.has_pattern_option() {
+                self.paths.insert(0, PathBuf::from(first));
+            } else {
+                self.pattern.push(first);
+            }
+        }
+    }
+
+    /// Whether stdout is left to the results alone, as with `--json` or
+    /// grep's `-l` and `-c`, for other programs to read.
+    pub(crate) fn results_only(&self) -> bool {
+        self.json || self.files || self.files_with_matches || self.count
+    }
+
+    /// The files and directories to search.
+    pub(crate) fn search_roots(&self) -> Vec<PathBuf> {
+        let mut roots: Vec<PathBuf> = self.directory.iter().cloned().collect();
+        roots.extend(self.paths.iter().cloned());
+        if roots.is_empty() {
+            roots.push(PathBuf::from("."));
+        }
+        roots
+    }
+}
+
+/// When `--color` colors output.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum ColorChoice {
+    /// When printing to a terminal, unless NO_COLOR is set
+    Auto,
+    Always,
+    Never,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub(crate) enum Engine {
+    /// The `regex` crate: linear time, no lookaround or backreferences
+    Default,
+    /// `fancy-regex`: supports lookaround and backreferences
+    #[cfg(feature = "fancy-regex")]
+    Fancy,
+}
+
+/// How `--io-backend` reads files.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub(crate) enum IoBackend {
+    /// One blocking read per file
+    Std,
+    /// Batches of reads submitted together through io_uring (experimental)
+    #[cfg(all(feature = "io-uring", target_os = "linux"))]
+    Uring,
+}
+
+/// What `--max-line-length` does with longer lines.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub(crate) enum LongLines {
+    /// Search only the start of the line
+    Truncate,
+    /// Don't search the line at all
+    Skip,
+}
+
+/// An inclusive, 1-based range of line numbers.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct LineRange {
+    pub(crate) start: usize,
+    pub(crate) end: Option<usize>,
+}
+
+impl LineRange {
+    pub(crate) fn contains(&self, line_number: usize) -> bool {
+        line_number >= self.start && self.end.is_none_or(|end| line_number <= end)
+    }
+
+    pub(crate) fn is_past(&self, line_number: usize) -> bool {
+        self.end.is_some_and(|end| line_number > end)
+    }
+}
+
+impl std::str::FromStr for LineRange {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (start, end) = s
+            .split_once(':')
+            .ok_or_else(|| format!("expected START:END, got {:?}", s))?;
+        let parse = |part: &str| {
+            part.trim()
+                .parse::<usize>()
+                .map_err(|_| format!("invalid line number {:?}", part))
+        };
+        let start = if start.trim().is_empty() {
+            1
+        } else {
+            parse(start)?
+        };
+        let end = if end.trim().is_empty() {
+            None
+        } else {
+            Some(parse(end)?)
+        };
+        if start == 0 || end.is_some_and(|end| end < start) {
+            return Err(format!("invalid line range {:?}", s));
+        }
+        Ok(LineRange { start, end })
+    }
+}
+
+/// Two patterns and how many lines apart they may be, parsed from `A,B,N`.
+///
+/// The distance is taken from the last comma and the patterns are split at
+/// the first one, so only the second pattern may itself contain commas.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct NearSpec {
+    pub(crate) first: String,
+    pub(crate) second: String,
+    pub(crate) distance: usize,
+}
+
+impl std::str::FromStr for NearSpec {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("expected PATTERN,PATTERN,LINES, got {:?}", s);
+        let (patterns, distance) = s.rsplit_once(',').ok_or_else(invalid)?;
+        let (first, second) = patterns.split_once(',').ok_or_else(invalid)?;
+        let distance = distance
+            .trim()
+            .parse()
+            .map_err(|_| format!("invalid line distance {:?}", distance))?;
+        if first.is_empty() || second.is_empty() {
+            return Err(invalid());
+        }
+        Ok(NearSpec {
+            first: first.to_string(),
+            second: second.to_string(),
+            distance,
+        })
+    }
+}
+
+/// Runs the command line given in `args`, printing as the `rusty-scout`
+/// binary does.
+pub fn run(mut args: Args) -> Result<ExitCode, Box<dyn Error>> {
+    args.resolve_positionals();
+    logging::init(args.verbose, args.debug);
+    apply_config(&mut args)?;
+    match args.color {
+        Some(ColorChoice::Always) => colored::control::set_override(true),
+        Some(ColorChoice::Never) => colored::control::set_override(false),
+        Some(ColorChoice::Auto) | None => {}
+    }
+
+    if args.self_update {
+        #[cfg(feature = "self-update")]
+        return update::self_update().map(|()| ExitCode::SUCCESS);
+        #[cfg(not(feature = "self-update"))]
+        return Err(
+            "this build can't update itself; reinstall it the way it was installed, \
+             or build it with --features self-update"
+                .into(),
+        );
+    }
+    if args.history {
+        let path = history::path().ok_or("--history needs HOME or XDG_STATE_HOME")?;
+        let entries = history::load(&path)?;
+        for (back, entry) in entries.iter().rev().enumerate().rev() {
+            let entry = shlex::try_join(entry.iter().map(String::as_str))
+                .unwrap_or_else(|_| entry.join(" "));
+            println!("{:>4}  {}", (back + 1).to_string().yellow(), entry);
+        }
+        return Ok(ExitCode::SUCCESS);
+    }
+    if args.profile_list {
+        for (name, flags) in config::Config::profiles(&args.search_roots()[0])? {
+            let flags = shlex::try_join(flags.iter().map(String::as_str))
+                .unwrap_or_else(|_| flags.join(" "));
+            println!("{}: {}", name.bold(), flags);
+        }
+        return Ok(ExitCode::SUCCESS);
+    }
+    if let Some(name) = &args.profile_save {
+        let path = config::Config::path().ok_or("--profile-save needs HOME or XDG_CONFIG_HOME")?;
+        let flags = without_option(&args.command_line, "--profile-save");
+        config::Config::save_profile(&path, name, &flags)?;
+        println!("Saved profile {} to {}", name.bold(), path.display());
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    if args.doctor {
+        if !doctor::report(&args, &mut std::io::stdout())? {
+            return Err("the self-test search failed".into());
+        }
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    if args.rpc {
+        rpc::serve(std::io::stdin().lock(), std::io::stdout())?;
+        return Ok(ExitCode::SUCCESS);
+    }
+    if args.mcp {
+        let [root] = args
+            .search_roots()
+            .try_into()
+            .map_err(|_| "--mcp serves one directory")?;
+        mcp::serve(std::io::stdin().lock(), std::io::stdout(), &root)?;
+        return Ok(ExitCode::SUCCESS);
+    }
+    if let Some(address) = &args.serve {
+        let [root] = args
+            .search_roots()
+            .try_into()
+            .map_err(|_| "--serve serves one directory")?;
+        http::serve(address, &root)?;
+        return Ok(ExitCode::SUCCESS);
+    }
+    if args.daemon {
+        #[cfg(unix)]
+        {
+            daemon::serve(&daemon::socket_path(&args))?;
+            return Ok(ExitCode::SUCCESS);
+        }
+        #[cfg(not(unix))]
+        return Err("--daemon needs Unix sockets".into());
+    }
+
+    if args.type_list {
+        for (name, globs) in types::all_types(&args.type_add) {
+            println!("{}: {}", name.bold(), globs.join(", "));
+        }
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    if let Some(shell) = args.completions {
+        write_completions(shell, &mut std::io::stdout())?;
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    if args.generate_man {
+        write_man(&mut std::io::stdout())?;
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    if args.schema {
+        print!("{}", crate::result::JSON_SCHEMA);
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    // Read here rather than by the searcher so it's in what's recorded.
+    let stdin_is_searched = args
+        .files_from
+        .as_ref()
+        .is_some_and(|list| list.as_os_str() == "-")
+        || args
+            .search_roots()
+            .iter()
+            .any(|root| root.as_os_str() == "-");
+    if !args.has_pattern_option() && !stdin_is_searched {
+        let stdin = std::io::stdin();
+        let terminal = stdin.is_terminal();
+        let pattern = read_pattern(&mut stdin.lock(), terminal.then(std::io::stderr))?;
+        args.command_line.push(format!("--pattern={}", pattern));
+        args.resolved.push(format!("--pattern={}", pattern));
+        args.pattern.push(pattern);
+    }
+
+    if args.record_history {
+        if let Some(path) = history::path() {
+            // A search is still worth running when it can't be recorded.
+            if let Err(e) = history::record(&path, &args.command_line) {
+                log::warn!("couldn't add the search to {}: {}", path.display(), e);
+            }
+        }
+    }
+
+    if !args.results_only() && !args.quiet {
+        println!(
+            "{} {} v{}",
+            "🔍".green(),
+            "Rusty Scout".bright_green(),
+            env!("CARGO_PKG_VERSION")
+        );
+    }
+
+    if args.connect {
+        if stdin_is_searched {
+            return Err("--connect can't search stdin; the daemon has its own".into());
+        }
+        #[cfg(unix)]
+        return daemon::connect(&args);
+        #[cfg(not(unix))]
+        return Err("--connect needs Unix sockets".into());
+    }
+
+    // The walker gets its own threads; this pool serves file lists. It's
+    // the run's own, so `run` can be called again in the same process.
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(args.threads)
+        .build()?;
+    pool.install(|| search(&args).map_err(|e| e.to_string()))
+        .map_err(Into::into)
+}
+
+/// The flag Ctrl-C sets: the current run's searcher's.
+#[cfg(not(target_arch = "wasm32"))]
+static INTERRUPTED: std::sync::Mutex<Option<Arc<std::sync::atomic::AtomicBool>>> =
+    std::sync::Mutex::new(None);
+#[cfg(not(target_arch = "wasm32"))]
+static CTRLC_HANDLER: std::sync::Once = std::sync::Once::new();
+
+/// Runs the search `args` describe, once everything else is handled.
+fn search(args: &Args) -> Result<ExitCode, Box<dyn Error>> {
+    if let Some(runs) = args.bench {
+        bench(args, runs)?;
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    let mut clipboard = args
+        .clipboard
+        .then(clipboard::Clipboard::open)
+        .transpose()?;
+    let started = Instant::now();
+    let mut searcher = Searcher::new(args)?;
+    searcher.messages = !args.no_messages;
+    log::info!("compiled the search in {:.1?}", started.elapsed());
+    if args.quiet {
+        run_search(args, &searcher)?;
+        return Ok(exit_status(args, searcher.found(), searcher.errors()));
+    }
+    searcher
+        .progress
+        .set_draw_target(ProgressDrawTarget::stderr());
+    // The first Ctrl-C stops handing out files and keeps what was found; a
+    // second one doesn't wait for the files in progress.
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        *INTERRUPTED.lock().unwrap() = Some(Arc::clone(&searcher.interrupted));
+        // A process gets one handler, so later runs only swap the flag it sets.
+        let mut installed = Ok(());
+        CTRLC_HANDLER.call_once(|| {
+            installed = ctrlc::set_handler(|| {
+                let interrupted = INTERRUPTED.lock().unwrap().clone();
+                if interrupted.is_some_and(|interrupted| {
+                    interrupted.swap(true, std::sync::atomic::Ordering::Relaxed)
+                }) {
+                    std::process::exit(130);
+                }
+            });
+        });
+        installed?;
+    }
+    // Results are printed by their own thread as they arrive, except ranked
+    // ones, which can only be printed once they're all in.
+    let edited = edit::FirstMatches::default();
+    let copied = clipboard::Copied::default();
+    let printing = (!args.fuzzy).then(|| {
+        let (sender, receiver) = mpsc::sync_channel::<Event>(RESULT_CHANNEL_CAPACITY);
+        searcher.stream_to(sender);
+        let mut sink = searcher.sink();
+        if args.edit {
+            sink = Box::new(edit::Noting::new(sink, Arc::clone(&edited)));
+        }
+        if args.clipboard {
+            let printer = searcher.printer;
+            sink = Box::new(clipboard::Copying::new(
+                sink,
+                printer,
+                args.json,
+                Arc::clone(&copied),
+            ));
+        }
+        let progress = Arc::clone(&searcher.progress);
+        std::thread::spawn(move || {
+            for event in receiver {
+                progress.suspend(|| deliver(&mut sink, event))?;
+            }
+            Ok::<_, std::io::Error>(sink)
+        })
+    });
+    let started = Instant::now();
+    let searched = run_search(args, &searcher);
+    log::info!(
+        "searched {} files in {:.1?}",
+        searcher.stats.snapshot().files,
+        started.elapsed()
+    );
+    searcher.close_output();
+    let sink = printing
+        .map(|printing| printing.join().unwrap())
+        .transpose();
+    searched?;
+    match sink {
+        Ok(Some(mut sink)) => sink.on_finish(searcher.found())?,
+        // Whatever was reading the output, like `head`, has all it wants.
+        Err(e) if e.kind() == std::io::ErrorKind::BrokenPipe => return Ok(ExitCode::SUCCESS),
+        Err(e) => return Err(e.into()),
+        Ok(None) => {}
+    }
+    searcher.write_patch()?;
+    searcher.display_results()?;
+    searcher.display_skipped();
+    searcher.display_interrupted();
+    if searcher.was_interrupted() {
+        return Ok(ExitCode::from(130));
+    }
+    if let Some(clipboard) = &mut clipboard {
+        if !searcher.streamed {
+            let mut copying = clipboard::Copying::new(
+                Box::new(Counter::default()),
+                searcher.printer,
+                args.json,
+                Arc::clone(&copied),
+            );
+            feed(&mut copying, &searcher.results.lock().unwrap())?;
+        }
+        let copied = std::mem::take(&mut *copied.lock().unwrap());
+        if !copied.is_empty() {
+            clipboard.copy(&copied)?;
+            // Not on stdout, where it'd be taken for a result.
+            eprintln!("{} Copied the results to the clipboard", "✓".green());
+        }
+    }
+    if args.edit {
+        let files = if searcher.streamed {
+            std::mem::take(&mut *edited.lock().unwrap())
+        } else {
+            edit::first_matches(&searcher.results.lock().unwrap())
+        };
+        edit::open(&files, args.edit_each)?;
+    }
+
+    Ok(exit_status(args, searcher.found(), searcher.errors()))
+}
+
+/// The pattern from the first line of `input`, asked for on `prompt` when
+/// there's a terminal to answer at, whose own line editing then applies.
+fn read_pattern(
+    input: &mut impl BufRead,
+    prompt: Option<impl Write>,
+) -> Result<String, Box<dyn Error>> {
+    if let Some(mut prompt) = prompt {
+        write!(prompt, "Pattern: ")?;
+        prompt.flush()?;
+    }
+    let mut line = String::new();
+    input.read_line(&mut line)?;
+    let pattern = line.trim_end_matches(['\n', '\r']);
+    if pattern.is_empty() {
+        return Err("no pattern given, on the command line or stdin".into());
+    }
+    Ok(pattern.to_string())
+}
+
+/// grep's exit status: 0 when something was found, 1 when nothing was, and
+/// 2 when some of what was to be searched couldn't be, unless
+/// `--no-messages` says not to mind or `--quiet` found something anyway.
+pub(crate) fn exit_status(args: &Args, found: usize, errors: usize) -> ExitCode {
+    if errors > 0 && !args.no_messages && !(args.quiet && found > 0) {
+        ExitCode::from(2)
+    } else if found > 0 {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    }
+}
+
+/// The environment variable that asks rusty-scout to complete a command
+/// line, which the scripts `--completions` prints set.
+const COMPLETE_VAR: &str = "COMPLETE";
+
+/// Keeps the results an earlier `--clipboard` search copied on the
+/// clipboard, when this process was started to, and exits. Does nothing
+/// otherwise.
+pub fn hold_clipboard_from_env() {
+    clipboard::hold_if_asked();
+}
+
+/// Answers a shell's request to complete a command line, when `COMPLETE` is
+/// set, and exits. Does nothing otherwise.
+pub fn complete_from_env() {
+    clap_complete::CompleteEnv::with_factory(Args::command)
+        .var(COMPLETE_VAR)
+        .complete();
+}
+
+/// Writes the script that has `shell` ask rusty-scout for completions as
+/// they're needed, so `--type` and `--profile` offer what's known where the
+/// completing happens.
+pub(crate) fn write_completions(shell: Shell, out: &mut dyn std::io::Write) -> std::io::Result<()> {
+    let shells = Shells::builtins();
+    let completer = shells
+        .completer(&shell.to_string())
+        .expect("every shell --completions takes can complete dynamically");
+    completer.write_registration(
+        COMPLETE_VAR,
+        "rusty-scout",
+        "rusty-scout",
+        "rusty-scout",
+        out,
+    )
+}
+
+/// The names among `names` that start with what's been typed.
+fn candidates(
+    names: impl IntoIterator<Item = String>,
+    current: &std::ffi::OsStr,
+) -> Vec<CompletionCandidate> {
+    let current = current.to_string_lossy();
+    names
+        .into_iter()
+        .filter(|name| name.starts_with(&*current))
+        .map(CompletionCandidate::new)
+        .collect()
+}
+
+/// The types `--type` knows from `root`, with those of the config files.
+fn known_types(root: &std::path::Path) -> Vec<String> {
+    let mut args = Args::defaults();
+    args.paths = vec![root.to_path_buf()];
+    // Completing offers whatever it can; a broken config file shows up
+    // when searching.
+    let _ = apply_config(&mut args);
+    types::all_types(&args.type_add)
+        .into_iter()
+        .map(|(name, _)| name)
+        .collect()
+}
+
+fn complete_types(current: &std::ffi::OsStr) -> Vec<CompletionCandidate> {
+    candidates(known_types(std::path::Path::new(".")), current)
+}
+
+fn complete_profiles(current: &std::ffi::OsStr) -> Vec<CompletionCandidate> {
+    let profiles = config::Config::profiles(std::path::Path::new(".")).unwrap_or_default();
+    candidates(profiles.into_keys(), current)
+}
+
+/// Prints `message` as clap prints its errors, and exits.
+fn exit_with(message: impl std::fmt::Display) -> ! {
+    clap::Error::raw(
+        clap::error::ErrorKind::InvalidValue,
+        format!("{}\n", message),
+    )
+    .exit()
+}
+
+/// `args` without `option` and its value, given either as `option VALUE`
+/// or `option=VALUE`, or left out when the next argument is another option.
+fn without_option(args: &[String], option: &str) -> Vec<String> {
+    let joined = format!("{}=", option);
+    let mut kept = Vec::new();
+    let mut args = args.iter().peekable();
+    while let Some(arg) = args.next() {
+        if arg == option {
+            args.next_if(|value| !value.starts_with('-'));
+        } else if !arg.starts_with(&joined) {
+            kept.push(arg.clone());
+        }
+    }
+    kept
+}
+
+/// Writes the man page, generated from the same definitions as `--help`.
+pub(crate) fn write_man(out: &mut dyn std::io::Write) -> std::io::Result<()> {
+    clap_mangen::Man::new(Args::command().name("rusty-scout")).render(out)
+}
+
+/// Adds the settings of `RUSTY_SCOUT_*` environment variables, then of the
+/// project's `.rusty-scout.toml`, found from the first search root, and
+/// then of the user's config file to `args`.
+pub(crate) fn apply_config(args: &mut Args) -> Result<(), Box<dyn Error>> {
+    apply_config_with(args, |name| std::env::var(name).ok())
+}
+
+/// [`apply_config`], with the environment variables `var` gives.
+pub(crate) fn apply_config_with(
+    args: &mut Args,
+    var: impl Fn(&str) -> Option<String>,
+) -> Result<(), Box<dyn Error>> {
+    // Whichever is applied first wins where both set something, and its
+    // types are put after the other's so they can extend them.
+    config::Config::from_vars(var)?.apply_to(args);
+    if let Some(path) = config::Config::find_project(&args.search_roots()[0]) {
+        log::info!("reading settings from {}", path.display());
+        config::Config::load_from(&path)?.apply_to(args);
+    }
+    if let Some(path) = config::Config::path().filter(|path| path.is_file()) {
+        log::info!("reading settings from {}", path.display());
+    }
+    config::Config::load()?.apply_to(args);
+    Ok(())
+}
+
+/// Runs the search `--bench-warmup` times untimed and then `runs` times
+/// timed, printing how fast it was instead of what it found.
+pub(crate) fn bench(args: &Args, runs: usize) -> Result<(), Box<dyn Error>> {
+    let mut timed = Vec::with_capacity(runs);
+    for run in 0..args.bench_warmup + runs {
+        let searcher = Searcher::new(args)?;
+        let started = Instant::now();
+        run_search(args, &searcher)?;
+        if run >= args.bench_warmup {
+            timed.push((started.elapsed(), searcher.stats.snapshot()));
+        }
+    }
+    println!("{}", stats::bench_report(&timed));
+    Ok(())
+}
+
+pub(crate) fn run_search(args: &Args, searcher: &Searcher) -> Result<(), Box<dyn Error>> {
+    if let Some(list) = &args.files_from {
+        let contents = if list.as_os_str() == "-" {
+            std::io::read_to_string(std::io::stdin())?
+        } else {
+            fs::read_to_string(list)?
+        };
+        searcher.search_listed(parse_file_list(&contents));
+    } else {
+        for root in args.search_roots() {
+            if root.as_os_str() == "-" {
+                searcher.search_input(std::io::stdin().lock())?;
+            } else {
+                searcher.search_dir(&root)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Parses `argv` for tests, panicking on bad options.
+#[cfg(test)]
+pub(crate) fn parse_args(argv: &[&str]) -> Args {
+    Args::from_argv(argv).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_positional_pattern_and_paths() -> Result<(), Box<dyn Error>> {
+        let dir = tempdir()?;
+        for sub in ["a", "b", "c"] {
+            fs::create_dir(dir.path().join(sub))?;
+            fs::write(dir.path().join(sub).join("file.txt"), "needle\n")?;
+        }
+        let a = dir.path().join("a");
+        let b_file = dir.path().join("b/file.txt");
+
+        let args = parse_args(&["needle", a.to_str().unwrap(), b_file.to_str().unwrap()]);
+        assert_eq!(args.pattern, ["needle"]);
+        assert_eq!(args.search_roots(), [a.clone(), b_file.clone()]);
+
+        let searcher = Searcher::new(&args)?;
+        for root in args.search_roots() {
+            searcher.search_dir(&root)?;
+        }
+        assert_eq!(searcher.results.lock().unwrap().len(), 2);
+
+        // With -p, every positional argument is a path.
+        let args = parse_args(&["-p", "needle", a.to_str().unwrap()]);
+        assert_eq!(args.pattern, ["needle"]);
+        assert_eq!(args.search_roots(), [a]);
+        assert_eq!(
+            parse_args(&["-p", "x"]).search_roots(),
+            [PathBuf::from(".")]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_later_flags_override() {
+        let args = Args::from_argv([
+            "x", "-m", "1", "--hidden", "-g", "*.rs", "-m", "2", "--hidden", "-g", "*.md",
+        ])
+        .unwrap();
+        assert_eq!(args.max_count, Some(2));
+        assert!(args.hidden);
+        assert_eq!(args.globs, ["*.rs", "*.md"]);
+
+        let args =
+            Args::from_argv(["x", "--hidden", "--no-hidden", "--ignore", "--no-ignore"]).unwrap();
+        assert!(!args.hidden && args.no_hidden);
+        assert!(args.no_ignore && !args.ignore);
+    }
+
+    #[test]
+    fn test_grep_flags() {
+        let args = Args::from_argv([
+            "-rnHiE",
+            "-c",
+            "--include",
+            "*.rs",
+            "--exclude",
+            "*.lock",
+            "a.b",
+        ])
+        .unwrap();
+        assert!(args.regex && args.ignore_case && args.count);
+        assert_eq!(args.globs, ["*.rs"]);
+        assert_eq!(args.exclude, ["*.lock"]);
+        assert_eq!(args.pattern, ["a.b"]);
+        assert!(Args::from_argv(["-c", "-l", "x"]).is_err());
+        assert!(Args::from_argv(["-v", "x"]).is_err());
+        // Ranked results are all kept, so their memory can't be capped.
+        assert!(Args::from_argv(["--fuzzy", "--max-memory", "1M", "x"]).is_err());
+    }
+
+    #[test]
+    fn test_exit_status() -> Result<(), Box<dyn Error>> {
+        let dir = tempdir()?;
+        let args = parse_args(&["needle"]);
+        let searcher = Searcher::new(&args)?;
+        searcher.search_dir(&dir.path().join("missing"))?;
+        assert_eq!(searcher.errors(), 1);
+
+        assert_eq!(exit_status(&args, 3, 0), ExitCode::SUCCESS);
+        assert_eq!(exit_status(&args, 0, 0), ExitCode::FAILURE);
+        assert_eq!(exit_status(&args, 3, 1), ExitCode::from(2));
+        assert_eq!(
+            exit_status(&parse_args(&["needle", "-s"]), 0, 1),
+            ExitCode::FAILURE
+        );
+        assert_eq!(
+            exit_status(&parse_args(&["needle", "-q"]), 3, 1),
+            ExitCode::SUCCESS
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_run_twice() -> Result<(), Box<dyn Error>> {
+        let dir = tempdir()?;
+        fs::write(dir.path().join("file.txt"), "hay\n")?;
+        // Each run builds its own thread pool and shares the Ctrl-C handler.
+        for _ in 0..2 {
+            let args = parse_args(&["-l", "needle", dir.path().to_str().unwrap()]);
+            assert_eq!(run(args)?, ExitCode::FAILURE);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_pattern() {
+        let mut prompt = Vec::new();
+        let pattern = read_pattern(&mut "fn main\r\nrest\n".as_bytes(), Some(&mut prompt));
+        assert_eq!(pattern.unwrap(), "fn main");
+        assert_eq!(prompt, b"Pattern: ");
+        assert!(read_pattern(&mut "".as_bytes(), None::<Vec<u8>>).is_err());
+        assert!(read_pattern(&mut "\n".as_bytes(), None::<Vec<u8>>).is_err());
+    }
+
+    #[test]
+    fn test_without_option() {
+        let args: Vec<String> = [
+            "-p",
+            "x",
+            "--profile-save",
+            "a",
+            "--json",
+            "--profile-save=b",
+        ]
+        .iter()
+        .map(|arg| arg.to_string())
+        .collect();
+        assert_eq!(
+            without_option(&args, "--profile-save"),
+            ["-p", "x", "--json"]
+        );
+        let args = ["--repeat".to_string(), "--json".to_string()];
+        assert_eq!(without_option(&args, "--repeat"), ["--json"]);
+    }
+
+    #[test]
+    fn test_completions() -> Result<(), Box<dyn Error>> {
+        let mut script = Vec::new();
+        write_completions(Shell::Fish, &mut script)?;
+        let script = String::from_utf8(script)?;
+        assert!(script.contains("COMPLETE=fish"), "{}", script);
+
+        // Types and profiles are looked up when completing, in the
+        // directory it happens in.
+        let dir = tempfile::tempdir()?;
+        fs::write(
+            dir.path().join(config::PROJECT_FILE),
+            "[types]\nproto = [\"*.proto\"]\n",
+        )?;
+        let types = known_types(dir.path());
+        assert!(types.contains(&"proto".to_string()) && types.contains(&"rust".to_string()));
+        let names = |candidates: Vec<CompletionCandidate>| -> Vec<String> {
+            candidates
+                .iter()
+                .map(|candidate| candidate.get_value().to_string_lossy().into_owned())
+                .collect()
+        };
+        assert_eq!(names(candidates(types, "pro".as_ref())), ["proto"]);
+
+        let mut command = Args::command();
+        let completed = clap_complete::engine::complete(
+            &mut command,
+            ["rusty-scout", "--type", "rus"].map(Into::into).to_vec(),
+            2,
+            None,
+        )?;
+        assert!(names(completed).contains(&"rust".to_string()));
+        Ok(())
+    }
+
+    #[test]
+    fn test_man() {
+        let mut page = Vec::new();
+        write_man(&mut page).unwrap();
+        let page = String::from_utf8(page).unwrap();
+        assert!(page.contains(".TH rusty-scout 1"));
+        assert!(page.contains("\\-\\-generate\\-man"));
+    }
+}
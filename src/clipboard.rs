@@ -0,0 +1,225 @@
+//! `--clipboard`: copies the results, as they were printed but without
+//! colors, to the system clipboard once the search is over.
+//!
+//! On X11 and Wayland, copied text is served by the program that copied it,
+//! so it would be gone once rusty-scout exits unless a clipboard manager
+//! took it over. As `xclip` does, a detached copy of the process is left to
+//! serve it until something else is copied.
+
+use crate::output::Printer;
+use crate::result::SearchResult;
+use crate::sink::Sink;
+use std::error::Error;
+use std::io;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+/// The text copied so far.
+pub(crate) type Copied = Arc<Mutex<String>>;
+
+/// `text` without the terminal color codes, like `\x1b[31m`, that only
+/// mean something to a terminal.
+fn without_colors(text: &str) -> String {
+    let mut plain = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            chars.by_ref().find(|&c| c == 'm');
+        } else {
+            plain.push(c);
+        }
+    }
+    plain
+}
+
+/// Passes results on to another sink, keeping the text of each as `--json`
+/// or the printer writes it.
+pub(crate) struct Copying {
+    inner: Box<dyn Sink + Send>,
+    printer: Printer,
+    json: bool,
+    copied: Copied,
+}
+
+impl Copying {
+    pub(crate) fn new(
+        inner: Box<dyn Sink + Send>,
+        printer: Printer,
+        json: bool,
+        copied: Copied,
+    ) -> Self {
+        Copying {
+            inner,
+            printer,
+            json,
+            copied,
+        }
+    }
+}
+
+impl Sink for Copying {
+    fn on_file_start(&mut self, path: &Path) -> io::Result<()> {
+        self.inner.on_file_start(path)
+    }
+
+    fn on_match(&mut self, result: &SearchResult) -> io::Result<()> {
+        let text = if self.json {
+            serde_json::to_string(&result.to_json())? + "\n"
+        } else {
+            without_colors(&self.printer.render(result))
+        };
+        self.copied.lock().unwrap().push_str(&text);
+        self.inner.on_match(result)
+    }
+
+    fn on_file_error(&mut self, path: &Path, error: &str) -> io::Result<()> {
+        self.inner.on_file_error(path, error)
+    }
+
+    fn on_finish(&mut self, found: usize) -> io::Result<()> {
+        self.inner.on_finish(found)
+    }
+}
+
+/// The system clipboard, opened before the search so that not having one
+/// is found out before it rather than after.
+#[cfg(all(feature = "clipboard", not(target_arch = "wasm32")))]
+pub(crate) struct Clipboard(arboard::Clipboard);
+
+#[cfg(all(feature = "clipboard", not(target_arch = "wasm32")))]
+impl Clipboard {
+    pub(crate) fn open() -> Result<Self, Box<dyn Error>> {
+        let clipboard =
+            arboard::Clipboard::new().map_err(|e| format!("can't open the clipboard: {}", e))?;
+        Ok(Clipboard(clipboard))
+    }
+
+    pub(crate) fn copy(&mut self, text: &str) -> Result<(), Box<dyn Error>> {
+        self.0
+            .set_text(text)
+            .map_err(|e| format!("can't copy to the clipboard: {}", e))?;
+        #[cfg(all(
+            unix,
+            not(any(target_os = "macos", target_os = "android", target_os = "emscripten"))
+        ))]
+        if let Err(e) = hold::spawn(text) {
+            log::warn!(
+                "the results may leave the clipboard when rusty-scout exits; \
+                 couldn't start a process to keep them there: {}",
+                e
+            );
+        }
+        Ok(())
+    }
+}
+
+/// The process left behind to keep what was copied on the clipboard.
+#[cfg(all(
+    feature = "clipboard",
+    unix,
+    not(any(target_os = "macos", target_os = "android", target_os = "emscripten"))
+))]
+mod hold {
+    use std::io::{self, Read, Write};
+    use std::os::unix::process::CommandExt;
+    use std::process::{Command, Stdio};
+
+    /// Set for that process, which reads the text from its stdin.
+    const VAR: &str = "RUSTY_SCOUT_HOLD_CLIPBOARD";
+
+    pub(super) fn spawn(text: &str) -> io::Result<()> {
+        // Not waited for: it runs until something else is copied.
+        let mut child = Command::new(std::env::current_exe()?)
+            .env(VAR, "1")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .current_dir("/")
+            // Its own process group, so a Ctrl-C at the terminal leaves it.
+            .process_group(0)
+            .spawn()?;
+        let mut stdin = child.stdin.take().expect("stdin is piped");
+        stdin.write_all(text.as_bytes())
+    }
+
+    /// Serves the text when this is that process, and exits.
+    pub(super) fn serve_if_asked() {
+        use arboard::SetExtLinux;
+
+        if std::env::var_os(VAR).is_none() {
+            return;
+        }
+        let mut text = String::new();
+        let held = io::stdin().read_to_string(&mut text).is_ok()
+            && arboard::Clipboard::new()
+                .and_then(|mut clipboard| clipboard.set().wait().text(text))
+                .is_ok();
+        std::process::exit(if held { 0 } else { 1 });
+    }
+}
+
+/// See [`crate::cli::hold_clipboard_from_env`].
+pub(crate) fn hold_if_asked() {
+    #[cfg(all(
+        feature = "clipboard",
+        unix,
+        not(any(target_os = "macos", target_os = "android", target_os = "emscripten"))
+    ))]
+    hold::serve_if_asked();
+}
+
+#[cfg(not(all(feature = "clipboard", not(target_arch = "wasm32"))))]
+pub(crate) struct Clipboard;
+
+#[cfg(not(all(feature = "clipboard", not(target_arch = "wasm32"))))]
+impl Clipboard {
+    pub(crate) fn open() -> Result<Self, Box<dyn Error>> {
+        Err("this build can't copy to the clipboard; build it with --features clipboard".into())
+    }
+
+    pub(crate) fn copy(&mut self, _text: &str) -> Result<(), Box<dyn Error>> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::parse_args;
+    use crate::searcher::Searcher;
+    use crate::sink::Counter;
+    use std::fs;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_copying() -> Result<(), Box<dyn Error>> {
+        let dir = tempdir()?;
+        let path = dir.path().join("a.rs");
+        fs::write(&path, "fn main() {}\n\nlet x = 1;\n")?;
+        let shown = path.display();
+        for (flag, expected) in [
+            ("-n", format!("{}:3:\n    let x = 1;\n", shown)),
+            ("-l", format!("{}\n", shown)),
+        ] {
+            let args = parse_args(&[flag, "x", dir.path().to_str().unwrap()]);
+            let searcher = Searcher::new(&args)?;
+            searcher.search_dir(dir.path())?;
+            let copied = Copied::default();
+            let mut sink = Copying::new(
+                Box::new(Counter::default()),
+                Printer::new(&args),
+                false,
+                copied.clone(),
+            );
+            for result in searcher.take_results() {
+                sink.on_match(&result)?;
+            }
+            assert_eq!(*copied.lock().unwrap(), expected);
+        }
+        assert_eq!(
+            without_colors("\x1b[34msrc/a.rs\x1b[0m:\x1b[1;33m3\x1b[0m"),
+            "src/a.rs:3"
+        );
+        Ok(())
+    }
+}
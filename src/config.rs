@@ -0,0 +1,336 @@
+use crate::cli::{Args, ColorChoice};
+use crate::types::TypeDefinition;
+use crate::SearchError;
+use clap::ValueEnum;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// A project's config file, found in the search root or a directory above it.
+pub const PROJECT_FILE: &str = ".rusty-scout.toml";
+
+/// Flags parsed before the command line's, which override them.
+pub const DEFAULT_FLAGS: &str = "RUSTY_SCOUT_DEFAULT_FLAGS";
+
+/// Settings read from `~/.config/rusty-scout/config.toml`, or from a
+/// project's `.rusty-scout.toml`.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// Extra file types for `--type`, e.g. `web = ["*.html", "*.css"]`.
+    pub types: BTreeMap<String, Vec<String>>,
+    /// Skip what matches these globs, as with `--exclude`.
+    pub exclude: Vec<String>,
+    /// Don't descend into directories with these names, as with `--exclude-dir`.
+    pub exclude_dir: Vec<String>,
+    /// Search hidden files and directories, as with `--hidden`, or not, as
+    /// with `--no-hidden`.
+    pub hidden: Option<bool>,
+    /// As with `--no-ignore`, `--no-ignore-vcs` and `--no-ignore-parent`, or
+    /// when false, `--ignore`, `--ignore-vcs` and `--ignore-parent`.
+    pub no_ignore: Option<bool>,
+    pub no_ignore_vcs: Option<bool>,
+    pub no_ignore_parent: Option<bool>,
+    /// Extensions searched when `-e` isn't given, e.g. `["rs", "toml"]`.
+    pub extensions: Vec<String>,
+    /// Threads used when `-j` isn't given.
+    pub threads: Option<usize>,
+    /// When to color output, when `--color` isn't given.
+    pub color: Option<ColorChoice>,
+    /// Options applied by `--profile NAME`, e.g.
+    /// `audit = ["--rules", "audit.yml", "--json"]`.
+    pub profiles: BTreeMap<String, Vec<String>>,
+}
+
+impl Config {
+    /// Where the config file lives: `$XDG_CONFIG_HOME/rusty-scout/config.toml`,
+    /// falling back to `~/.config`.
+    pub fn path() -> Option<PathBuf> {
+        let base = std::env::var_os("XDG_CONFIG_HOME")
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+        Some(base.join("rusty-scout").join("config.toml"))
+    }
+
+    /// Loads the config file, or the defaults when there isn't one.
+    pub fn load() -> Result<Self, Box<dyn Error>> {
+        match Self::path() {
+            Some(path) if path.is_file() => Self::load_from(&path),
+            _ => Ok(Config::default()),
+        }
+    }
+
+    /// The nearest `.rusty-scout.toml` in `root` or a directory above it.
+    pub fn find_project(root: &Path) -> Option<PathBuf> {
+        let root = root.canonicalize().unwrap_or_else(|_| root.to_path_buf());
+        root.ancestors()
+            .map(|dir| dir.join(PROJECT_FILE))
+            .find(|path| path.is_file())
+    }
+
+    /// The profiles in the user's config file and in the project's found
+    /// from `root`, whose win where both have one by the same name.
+    pub fn profiles(root: &Path) -> Result<BTreeMap<String, Vec<String>>, Box<dyn Error>> {
+        let mut profiles = Self::load()?.profiles;
+        if let Some(path) = Self::find_project(root) {
+            profiles.extend(Self::load_from(&path)?.profiles);
+        }
+        Ok(profiles)
+    }
+
+    /// Saves `flags` as the profile `name` in the config file at `path`,
+    /// keeping the rest of the file as it was.
+    pub fn save_profile(path: &Path, name: &str, flags: &[String]) -> Result<(), Box<dyn Error>> {
+        let contents = match fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(e.into()),
+        };
+        let invalid = |message: String| {
+            SearchError::new("InvalidConfig", format!("{}: {}", path.display(), message))
+        };
+        let mut document: toml_edit::DocumentMut = contents
+            .parse()
+            .map_err(|e: toml_edit::TomlError| invalid(e.to_string()))?;
+        let profiles = document
+            .entry("profiles")
+            .or_insert(toml_edit::table())
+            .as_table_mut()
+            .ok_or_else(|| invalid("profiles isn't a table".to_string()))?;
+        profiles[name] = toml_edit::value(
+            flags
+                .iter()
+                .map(String::as_str)
+                .collect::<toml_edit::Array>(),
+        );
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, document.to_string())?;
+        Ok(())
+    }
+
+    /// Settings from `RUSTY_SCOUT_THREADS`, `RUSTY_SCOUT_COLORS`,
+    /// `RUSTY_SCOUT_EXTENSIONS` and `RUSTY_SCOUT_HIDDEN`, as `var` gives
+    /// them.
+    pub(crate) fn from_vars(var: impl Fn(&str) -> Option<String>) -> Result<Self, Box<dyn Error>> {
+        let invalid = |name: &str, value: &str| {
+            SearchError::new("InvalidConfig", format!("{}={:?} isn't valid", name, value))
+        };
+        let mut config = Config::default();
+        if let Some(value) = var("RUSTY_SCOUT_THREADS") {
+            let threads = value
+                .parse()
+                .map_err(|_| invalid("RUSTY_SCOUT_THREADS", &value))?;
+            config.threads = Some(threads);
+        }
+        if let Some(value) = var("RUSTY_SCOUT_COLORS") {
+            let color = ColorChoice::from_str(&value, true)
+                .map_err(|_| invalid("RUSTY_SCOUT_COLORS", &value))?;
+            config.color = Some(color);
+        }
+        if let Some(value) = var("RUSTY_SCOUT_EXTENSIONS") {
+            config.extensions = value.split(',').map(str::to_string).collect();
+        }
+        if let Some(value) = var("RUSTY_SCOUT_HIDDEN") {
+            config.hidden = Some(!matches!(&*value, "" | "0" | "false"));
+        }
+        Ok(config)
+    }
+
+    pub fn load_from(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let contents = fs::read_to_string(path)?;
+        Self::parse(&contents).map_err(|e| {
+            SearchError::new("InvalidConfig", format!("{}: {}", path.display(), e)).into()
+        })
+    }
+
+    pub fn parse(contents: &str) -> Result<Self, toml::de::Error> {
+        toml::from_str(contents)
+    }
+
+    /// The `[types]` table as `--type-add` definitions.
+    pub fn type_definitions(&self) -> Vec<TypeDefinition> {
+        self.types
+            .iter()
+            .map(|(name, globs)| TypeDefinition {
+                name: name.clone(),
+                globs: globs.clone(),
+            })
+            .collect()
+    }
+
+    /// Adds these settings to `args`, where its flags don't already say
+    /// otherwise.
+    pub(crate) fn apply_to(self, args: &mut Args) {
+        // Types from the config file come first so --type-add can extend them.
+        args.type_add.splice(0..0, self.type_definitions());
+        args.exclude.extend(self.exclude);
+        args.exclude_dir.extend(self.exclude_dir);
+        switch(&mut args.hidden, &mut args.no_hidden, self.hidden);
+        switch(&mut args.no_ignore, &mut args.ignore, self.no_ignore);
+        switch(
+            &mut args.no_ignore_vcs,
+            &mut args.ignore_vcs,
+            self.no_ignore_vcs,
+        );
+        switch(
+            &mut args.no_ignore_parent,
+            &mut args.ignore_parent,
+            self.no_ignore_parent,
+        );
+        args.color = args.color.or(self.color);
+        // Flags left at their defaults take the config file's values.
+        if args.threads == 0 {
+            args.threads = self.threads.unwrap_or(0);
+        }
+        if args.extensions == "*" && !self.extensions.is_empty() {
+            args.extensions = self.extensions.join(",");
+        }
+    }
+}
+
+/// Sets a flag and its negation, `on` and `off`, to `value`, unless the
+/// command line or settings applied before these already gave one of them.
+fn switch(on: &mut bool, off: &mut bool, value: Option<bool>) {
+    if let Some(value) = value.filter(|_| !*on && !*off) {
+        *on = value;
+        *off = !value;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_types() {
+        let config = Config::parse(
+            r#"
+[types]
+web = ["*.html", "*.css", "*.js"]
+"#,
+        )
+        .unwrap();
+        let definitions = config.type_definitions();
+        assert_eq!(definitions.len(), 1);
+        assert_eq!(definitions[0].name, "web");
+        assert_eq!(definitions[0].globs, ["*.html", "*.css", "*.js"]);
+
+        assert_eq!(Config::parse("hidden = true").unwrap().hidden, Some(true));
+        assert!(Config::parse("").unwrap().types.is_empty());
+        assert!(Config::parse("types = 3").is_err());
+    }
+
+    #[test]
+    fn test_apply_to() {
+        let config = || {
+            Config::parse(
+                r#"
+no_ignore_vcs = true
+extensions = ["rs", "toml"]
+threads = 4
+color = "never"
+"#,
+            )
+            .unwrap()
+        };
+
+        let mut args = Args::from_argv(["TODO"]).unwrap();
+        config().apply_to(&mut args);
+        assert!(args.no_ignore_vcs);
+        assert_eq!(args.extensions, "rs,toml");
+        assert_eq!(args.threads, 4);
+        assert_eq!(args.color, Some(ColorChoice::Never));
+
+        let mut args =
+            Args::from_argv(["TODO", "-e", "md", "-j", "2", "--color", "always"]).unwrap();
+        config().apply_to(&mut args);
+        assert_eq!(args.extensions, "md");
+        assert_eq!(args.threads, 2);
+        assert_eq!(args.color, Some(ColorChoice::Always));
+
+        // Switches given either way on the command line are kept, and
+        // otherwise the first settings applied win.
+        let mut args = Args::from_argv(["TODO", "--ignore-vcs", "--no-hidden"]).unwrap();
+        Config::parse("hidden = true").unwrap().apply_to(&mut args);
+        config().apply_to(&mut args);
+        assert!(!args.no_ignore_vcs && !args.hidden);
+        let mut args = Args::from_argv(["TODO"]).unwrap();
+        Config::parse("no_ignore_vcs = false")
+            .unwrap()
+            .apply_to(&mut args);
+        config().apply_to(&mut args);
+        assert!(!args.no_ignore_vcs);
+
+        assert!(Config::parse("color = \"sometimes\"").is_err());
+    }
+
+    #[test]
+    fn test_from_vars() {
+        let vars = |name: &str| match name {
+            "RUSTY_SCOUT_THREADS" => Some("3".to_string()),
+            "RUSTY_SCOUT_COLORS" => Some("Never".to_string()),
+            "RUSTY_SCOUT_HIDDEN" => Some("1".to_string()),
+            _ => None,
+        };
+        let config = Config::from_vars(vars).unwrap();
+        assert_eq!(config.threads, Some(3));
+        assert_eq!(config.color, Some(ColorChoice::Never));
+        assert_eq!(config.hidden, Some(true));
+        assert!(config.extensions.is_empty());
+
+        let bad = |name: &str| (name == "RUSTY_SCOUT_THREADS").then(|| "many".to_string());
+        assert!(Config::from_vars(bad).is_err());
+    }
+
+    #[test]
+    fn test_save_profile() -> Result<(), Box<dyn Error>> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("rusty-scout/config.toml");
+        let flags = |flags: &[&str]| flags.iter().map(|f| f.to_string()).collect::<Vec<_>>();
+        Config::save_profile(&path, "audit", &flags(&["--rules", "audit.yml"]))?;
+        fs::write(
+            &path,
+            format!("# mine\nhidden = true\n{}", fs::read_to_string(&path)?),
+        )?;
+        Config::save_profile(&path, "audit", &flags(&["--rules", "audit.yml", "--json"]))?;
+        Config::save_profile(&path, "todo", &flags(&["-p", "TODO"]))?;
+
+        let contents = fs::read_to_string(&path)?;
+        assert!(
+            contents.starts_with("# mine\nhidden = true\n"),
+            "{}",
+            contents
+        );
+        let config = Config::parse(&contents)?;
+        assert_eq!(config.hidden, Some(true));
+        assert_eq!(config.profiles["audit"], ["--rules", "audit.yml", "--json"]);
+        assert_eq!(config.profiles["todo"], ["-p", "TODO"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_find_project() -> Result<(), Box<dyn Error>> {
+        let dir = tempfile::tempdir()?;
+        let nested = dir.path().join("src/deep");
+        fs::create_dir_all(&nested)?;
+        assert_eq!(Config::find_project(&nested), None);
+
+        let file = dir.path().join(PROJECT_FILE);
+        fs::write(
+            &file,
+            "exclude = [\"*.lock\"]\n[types]\nproto = [\"*.proto\"]\n",
+        )?;
+        assert_eq!(Config::find_project(&nested), Some(file.canonicalize()?));
+
+        let mut args = Args::from_argv(["TODO", "--exclude", "*.min.js"])?;
+        Config::load_from(&file)?.apply_to(&mut args);
+        assert_eq!(args.exclude, ["*.min.js", "*.lock"]);
+        assert_eq!(args.type_add[0].name, "proto");
+        Ok(())
+    }
+}
@@ -0,0 +1,672 @@
+//! `--daemon`: a long-running process that does searches for
+//! `rusty-scout --connect` over a Unix socket. It keeps the listing of each
+//! tree it has walked, and the searchers it has built, so running the same
+//! search again in a large tree skips both the walk and compiling its
+//! patterns.
+//!
+//! A client sends one line of JSON, `{"cwd": ..., "args": [...], "env":
+//! {...}}`, holding its flags, with those from `RUSTY_SCOUT_DEFAULT_FLAGS`
+//! and a `--profile` already in, and its `RUSTY_SCOUT_*` variables, which
+//! the daemon takes in place of its own. The daemon answers with a line per result,
+//! `{"result": ...}`, and then `{"done": {"found": N, "skipped": [...]}}`,
+//! or with `{"error": "..."}` if the search couldn't be done.
+//!
+//! A listing is used again for as long as none of its directories, nor any
+//! ignore file in one of them, has been modified. Files themselves are
+//! filtered and read afresh every time.
+
+use crate::cli::{self, Args};
+use crate::config;
+use crate::output::{print_found, print_skipped, FileCounts, Printer};
+use crate::result::SearchResult;
+use crate::searcher::Searcher;
+use crate::sink::{feed, JsonWriter, Sink};
+use crate::walker::IGNORE_FILENAME;
+use ignore::WalkState;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fs;
+use std::io::{self, BufRead, BufReader, Write};
+use std::os::unix::fs::{DirBuilderExt, FileTypeExt, MetadataExt, PermissionsExt};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::SystemTime;
+
+/// Searchers kept for reuse, each for one command line.
+const SEARCHERS: usize = 16;
+
+/// Tree listings kept for reuse. Each holds a path for every file in its
+/// tree, so there are fewer of them.
+const LISTINGS: usize = 4;
+
+/// Ignore files whose contents change what a walk lists.
+const IGNORE_FILES: [&str; 3] = [".gitignore", ".ignore", IGNORE_FILENAME];
+
+/// When a directory and each of its `IGNORE_FILES` were last modified.
+type Changed = [Option<SystemTime>; 1 + IGNORE_FILES.len()];
+
+#[derive(Debug, Deserialize, Serialize)]
+struct Query {
+    /// The client's working directory, which relative paths are from.
+    cwd: PathBuf,
+    /// The client's flags, without the program name.
+    args: Vec<String>,
+    /// The client's `RUSTY_SCOUT_*` variables.
+    #[serde(default)]
+    env: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum Reply<R> {
+    Result(R),
+    Done {
+        found: usize,
+        skipped: Vec<(PathBuf, String)>,
+        /// What couldn't be searched, for the exit status.
+        errors: usize,
+    },
+    Error(String),
+}
+
+/// The socket from `--socket`, or else one private to the user.
+pub(crate) fn socket_path(args: &Args) -> PathBuf {
+    if let Some(socket) = &args.socket {
+        return socket.clone();
+    }
+    socket_dir().join("rusty-scout.sock")
+}
+
+/// `$XDG_RUNTIME_DIR`, or else a directory for the user in the temporary
+/// directory, which is shared, for [`serve`] to make private.
+fn socket_dir() -> PathBuf {
+    match std::env::var_os("XDG_RUNTIME_DIR") {
+        Some(dir) => PathBuf::from(dir),
+        None => std::env::temp_dir().join(format!("rusty-scout-{}", uid())),
+    }
+}
+
+/// Creates `dir` for this user alone, or checks that it already is theirs
+/// alone, so no one else can put a socket in it.
+fn make_private(dir: &Path) -> io::Result<()> {
+    match fs::DirBuilder::new().mode(0o700).create(dir) {
+        Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {}
+        created => created?,
+    }
+    let metadata = fs::symlink_metadata(dir)?;
+    if !metadata.is_dir() || metadata.uid() != uid() || metadata.mode() & 0o077 != 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            format!("{} isn't a directory only this user can use", dir.display()),
+        ));
+    }
+    Ok(())
+}
+
+fn uid() -> u32 {
+    // SAFETY: geteuid always succeeds and touches no memory.
+    unsafe { libc::geteuid() }
+}
+
+/// The files a walk found, and when each directory it went through last
+/// changed.
+#[derive(Default)]
+struct Listing {
+    files: Vec<PathBuf>,
+    dirs: Vec<(PathBuf, Changed)>,
+    /// Ignore files the walk read outside the tree, and when each was last
+    /// modified.
+    outside: Vec<(PathBuf, Option<SystemTime>)>,
+    /// Whether the walk couldn't reach some entries, which a search from
+    /// the listing wouldn't report again.
+    incomplete: bool,
+}
+
+impl Listing {
+    fn walk(searcher: &Searcher, root: &Path) -> Result<Self, Box<dyn Error>> {
+        // Before the walk, so an edit during it shows as a change.
+        let outside = outside_ignore_files(root)
+            .into_iter()
+            .map(|path| {
+                let changed = modified(&path);
+                (path, changed)
+            })
+            .collect();
+        let listing = Mutex::new(Listing {
+            outside,
+            ..Listing::default()
+        });
+        searcher.walk_builder(root)?.build_parallel().run(|| {
+            Box::new(|entry| {
+                let entry = match entry {
+                    Ok(entry) => entry,
+                    Err(e) => {
+                        searcher.walk_error(&e);
+                        listing.lock().unwrap().incomplete = true;
+                        return WalkState::Continue;
+                    }
+                };
+                match entry.file_type() {
+                    Some(ft) if ft.is_dir() => {
+                        let changed = last_changed(entry.path());
+                        let dir = entry.into_path();
+                        listing.lock().unwrap().dirs.push((dir, changed));
+                    }
+                    Some(ft) if ft.is_file() && !searcher.is_hidden_entry(&entry) => {
+                        listing.lock().unwrap().files.push(entry.into_path());
+                    }
+                    _ => {}
+                }
+                WalkState::Continue
+            })
+        });
+        Ok(listing.into_inner().unwrap())
+    }
+
+    /// Whether walking the tree again would find the same files.
+    fn is_fresh(&self) -> bool {
+        !self.incomplete
+            && self
+                .outside
+                .iter()
+                .all(|(path, changed)| modified(path) == *changed)
+            && self
+                .dirs
+                .par_iter()
+                .all(|(dir, changed)| last_changed(dir) == *changed)
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+/// When `dir`, and each ignore file in it, was last modified. Editing an
+/// ignore file in place leaves its directory's time alone.
+fn last_changed(dir: &Path) -> Changed {
+    let mut changed = [modified(dir); 1 + IGNORE_FILES.len()];
+    for (time, name) in changed[1..].iter_mut().zip(IGNORE_FILES) {
+        *time = modified(&dir.join(name));
+    }
+    changed
+}
+
+/// Ignore files that a walk from `root` reads besides those in the tree:
+/// the ones in the directories above it, each `.git/info/exclude`, and the
+/// global gitignore with the git config files that can name it.
+fn outside_ignore_files(root: &Path) -> Vec<PathBuf> {
+    let root = fs::canonicalize(root).unwrap_or_else(|_| root.to_path_buf());
+    let mut files = Vec::new();
+    for (depth, dir) in root.ancestors().enumerate() {
+        files.push(dir.join(".git/info/exclude"));
+        if depth > 0 {
+            files.extend(IGNORE_FILES.iter().map(|name| dir.join(name)));
+        }
+    }
+    let home = std::env::var_os("HOME").map(PathBuf::from);
+    let git_config_dir = std::env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| home.as_ref().map(|home| home.join(".config")))
+        .map(|config| config.join("git"));
+    let configs: Vec<PathBuf> = git_config_dir
+        .iter()
+        .map(|dir| dir.join("config"))
+        .chain(home.iter().map(|home| home.join(".gitconfig")))
+        .collect();
+    let named = configs
+        .iter()
+        .filter_map(|config| fs::read_to_string(config).ok())
+        .find_map(|contents| excludes_file(&contents, home.as_deref()));
+    files.extend(named.or_else(|| git_config_dir.map(|dir| dir.join("ignore"))));
+    files.extend(configs);
+    files
+}
+
+/// The `core.excludesFile` a git config file names, read as git would.
+fn excludes_file(config: &str, home: Option<&Path>) -> Option<PathBuf> {
+    let value = config.lines().find_map(|line| {
+        let (name, value) = line.split_once('=')?;
+        name.trim()
+            .eq_ignore_ascii_case("excludesfile")
+            .then(|| value.trim().trim_matches('"').trim())
+    })?;
+    match (value.strip_prefix("~/"), home) {
+        (Some(rest), Some(home)) => Some(home.join(rest)),
+        _ => Some(PathBuf::from(value)),
+    }
+}
+
+/// The options that decide which files a walk lists.
+fn walk_key(searcher: &Searcher) -> String {
+    format!(
+        "{:?}",
+        (
+            searcher.hidden,
+            searcher.no_ignore,
+            searcher.no_ignore_vcs,
+            searcher.no_ignore_parent,
+            searcher.follow,
+            searcher.one_file_system,
+            searcher.max_depth,
+            searcher.types.definitions(),
+            &searcher.globs,
+            &searcher.exclude,
+            &searcher.exclude_dir,
+        )
+    )
+}
+
+/// Takes `key`'s entry out of `cache`, which is kept least recently used
+/// first.
+fn take<V>(cache: &mut Vec<(String, V)>, key: &str) -> Option<V> {
+    let i = cache.iter().position(|(k, _)| k == key)?;
+    Some(cache.remove(i).1)
+}
+
+/// Puts an entry back in `cache` as the most recently used, dropping the
+/// least recently used one if there are `capacity` already.
+fn keep<V>(cache: &mut Vec<(String, V)>, key: String, value: V, capacity: usize) {
+    if cache.len() >= capacity {
+        cache.remove(0);
+    }
+    cache.push((key, value));
+}
+
+/// What the daemon keeps between searches.
+#[derive(Default)]
+struct Warm {
+    searchers: Vec<(String, Searcher)>,
+    listings: Vec<(String, Listing)>,
+}
+
+impl Warm {
+    fn search(
+        &mut self,
+        query: &Query,
+        output: &mut (impl Write + Send),
+    ) -> Result<(), Box<dyn Error>> {
+        std::env::set_current_dir(&query.cwd)?;
+        let mut args = Args::from_argv(&query.args)?;
+        cli::apply_config_with(&mut args, |name| query.env.get(name).cloned())?;
+        // Relative times, as in `--newer-than 1h`, come out differently
+        // every time, so those searchers are never reused.
+        let key = format!("{:?}\0{:?}", query.cwd, args);
+        let mut searcher = match take(&mut self.searchers, &key) {
+            Some(mut searcher) => {
+                searcher.reset();
+                searcher
+            }
+            None => Searcher::new(&args)?,
+        };
+        let searched = self.run(&args, &mut searcher, output);
+        keep(&mut self.searchers, key, searcher, SEARCHERS);
+        searched
+    }
+
+    fn run(
+        &mut self,
+        args: &Args,
+        searcher: &mut Searcher,
+        output: &mut (impl Write + Send),
+    ) -> Result<(), Box<dyn Error>> {
+        let listings = &mut self.listings;
+        if args.fuzzy {
+            // Ranked results can only be sent once they're all in.
+            search_roots(listings, args, searcher)?;
+            for result in searcher.take_results() {
+                send(output, &Reply::Result(&result))?;
+            }
+        } else {
+            searcher.stream_into(&mut Remote(&mut *output), |searcher| {
+                search_roots(listings, args, searcher)
+            })?;
+        }
+        let skipped = std::mem::take(&mut *searcher.skipped.lock().unwrap());
+        send(
+            output,
+            &Reply::Done {
+                found: searcher.found(),
+                skipped,
+                errors: searcher.errors(),
+            },
+        )?;
+        Ok(())
+    }
+}
+
+/// Searches each of `args`' roots, from a listing of it if there's one
+/// still fresh.
+fn search_roots(
+    listings: &mut Vec<(String, Listing)>,
+    args: &Args,
+    searcher: &Searcher,
+) -> Result<(), Box<dyn Error>> {
+    let cwd = std::env::current_dir()?;
+    for root in args.search_roots() {
+        if !root.is_dir() {
+            searcher.search_dir(&root)?;
+            continue;
+        }
+        let key = format!("{:?}\0{:?}\0{}", cwd, root, walk_key(searcher));
+        let listing = match take(listings, &key) {
+            Some(listing) if listing.is_fresh() => listing,
+            _ => Listing::walk(searcher, &root)?,
+        };
+        searcher.search_listed(listing.files.clone());
+        keep(listings, key, listing, LISTINGS);
+    }
+    Ok(())
+}
+
+/// Writes one reply and its newline, all at once.
+fn send(output: &mut impl Write, reply: &Reply<&SearchResult>) -> io::Result<()> {
+    let mut line = serde_json::to_vec(reply)?;
+    line.push(b'\n');
+    output.write_all(&line)
+}
+
+/// Sends results to the client as they're found.
+struct Remote<'a, W>(&'a mut W);
+
+impl<W: Write> Sink for Remote<'_, W> {
+    fn on_match(&mut self, result: &SearchResult) -> io::Result<()> {
+        send(self.0, &Reply::Result(result))
+    }
+}
+
+/// Listens on `socket` until killed, replacing a socket left behind by a
+/// daemon that's no longer running.
+pub(crate) fn serve(socket: &Path) -> io::Result<()> {
+    let dir = socket_dir();
+    if socket.parent() == Some(&dir) {
+        make_private(&dir)?;
+    }
+    let listener = bind(socket)?;
+    eprintln!("Listening on {}", socket.display());
+    serve_on(listener, Arc::default())
+}
+
+fn bind(socket: &Path) -> io::Result<UnixListener> {
+    if UnixStream::connect(socket).is_ok() {
+        return Err(io::Error::new(
+            io::ErrorKind::AddrInUse,
+            format!("a daemon is already listening on {}", socket.display()),
+        ));
+    }
+    if fs::symlink_metadata(socket).is_ok_and(|m| m.file_type().is_socket()) {
+        fs::remove_file(socket)?;
+    }
+    let listener = UnixListener::bind(socket)?;
+    // Whoever can connect can read anything this user can.
+    fs::set_permissions(socket, fs::Permissions::from_mode(0o600))?;
+    Ok(listener)
+}
+
+fn serve_on(listener: UnixListener, warm: Arc<Mutex<Warm>>) -> io::Result<()> {
+    for stream in listener.incoming() {
+        let Ok(stream) = stream else {
+            continue;
+        };
+        let warm = Arc::clone(&warm);
+        // A client that hangs up early has nothing left to be told.
+        std::thread::spawn(move || answer(stream, &warm));
+    }
+    Ok(())
+}
+
+fn answer(stream: UnixStream, warm: &Mutex<Warm>) -> io::Result<()> {
+    let mut line = String::new();
+    BufReader::new(&stream).read_line(&mut line)?;
+    let mut output = &stream;
+    let answered = match serde_json::from_str::<Query>(&line) {
+        // One search at a time, since each runs in its client's directory.
+        Ok(query) => warm
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .search(&query, &mut output),
+        Err(e) => Err(e.into()),
+    };
+    match answered {
+        Err(e) => send(&mut output, &Reply::Error(e.to_string())),
+        Ok(()) => Ok(()),
+    }
+}
+
+/// Sends `query` to the daemon on `socket`, returning its replies.
+fn ask(
+    socket: &Path,
+    query: &Query,
+) -> io::Result<impl Iterator<Item = io::Result<Reply<SearchResult>>>> {
+    let mut stream = UnixStream::connect(socket)?;
+    let mut line = serde_json::to_vec(query)?;
+    line.push(b'\n');
+    stream.write_all(&line)?;
+    Ok(BufReader::new(stream)
+        .lines()
+        .map(|line| Ok(serde_json::from_str(&line?)?)))
+}
+
+/// Has the daemon do the search `args` were parsed for, printing what it
+/// finds as a search here would.
+pub(crate) fn connect(args: &Args) -> Result<ExitCode, Box<dyn Error>> {
+    let query = Query {
+        cwd: std::env::current_dir()?,
+        args: args.resolved.clone(),
+        // The default flags are in `args` already.
+        env: std::env::vars()
+            .filter(|(name, _)| name.starts_with("RUSTY_SCOUT_") && name != config::DEFAULT_FLAGS)
+            .collect(),
+    };
+    let mut sink: Box<dyn Sink> = if args.json {
+        Box::new(JsonWriter::new(io::stdout()))
+    } else if args.count {
+        Box::new(FileCounts::default())
+    } else {
+        Box::new(Printer::new(args))
+    };
+    let mut ranked = Vec::new();
+    let socket = socket_path(args);
+    // Another user's socket would be sent the search, and could forge its
+    // results.
+    if fs::metadata(&socket).is_ok_and(|m| m.uid() != uid()) {
+        return Err(format!("{} belongs to another user", socket.display()).into());
+    }
+    let replies = ask(&socket, &query).map_err(|e| {
+        format!(
+            "can't reach a daemon on {} ({}); is `rusty-scout --daemon` running?",
+            socket.display(),
+            e
+        )
+    })?;
+    for reply in replies {
+        match reply? {
+            Reply::Result(_) if args.quiet => {}
+            Reply::Result(result) if args.fuzzy => ranked.push(result),
+            Reply::Result(result) => {
+                if let Err(e) = sink.on_match(&result) {
+                    return stopped_reading(e);
+                }
+            }
+            Reply::Done {
+                found,
+                skipped,
+                errors,
+            } => {
+                if args.quiet {
+                    return Ok(cli::exit_status(args, found, errors));
+                }
+                if !args.results_only() {
+                    print_found(found, !args.fuzzy, args.limit);
+                }
+                if let Err(e) = feed(&mut sink, &ranked).and_then(|()| sink.on_finish(found)) {
+                    return stopped_reading(e);
+                }
+                print_skipped(&skipped, args.results_only());
+                return Ok(cli::exit_status(args, found, errors));
+            }
+            Reply::Error(message) => return Err(message.into()),
+        }
+    }
+    Err(io::Error::new(
+        io::ErrorKind::UnexpectedEof,
+        "the daemon hung up before the search was over",
+    )
+    .into())
+}
+
+/// Whatever was reading the output, like `head`, has all it wants.
+fn stopped_reading(e: io::Error) -> Result<ExitCode, Box<dyn Error>> {
+    match e.kind() {
+        io::ErrorKind::BrokenPipe => Ok(ExitCode::SUCCESS),
+        _ => Err(e.into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_make_private() -> io::Result<()> {
+        let dir = tempdir()?;
+        let private = dir.path().join("private");
+        make_private(&private)?;
+        assert_eq!(fs::metadata(&private)?.mode() & 0o777, 0o700);
+        make_private(&private)?;
+
+        fs::set_permissions(&private, fs::Permissions::from_mode(0o755))?;
+        assert!(make_private(&private).is_err());
+        fs::write(dir.path().join("file"), "")?;
+        assert!(make_private(&dir.path().join("file")).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_daemon() -> Result<(), Box<dyn Error>> {
+        let dir = tempdir()?;
+        let tree = dir.path().join("tree");
+        fs::create_dir(&tree)?;
+        fs::write(tree.join("a.txt"), "needle\n")?;
+        fs::write(tree.join(".ignore"), "")?;
+        // Changes the test makes must show in modification times, which
+        // can be coarser than the time between them.
+        let past = SystemTime::UNIX_EPOCH;
+        fs::File::options()
+            .write(true)
+            .open(tree.join(".ignore"))?
+            .set_modified(past)?;
+        fs::File::open(&tree)?.set_modified(past)?;
+
+        let socket = dir.path().join("scout.sock");
+        let warm = Arc::new(Mutex::new(Warm::default()));
+        let listener = bind(&socket)?;
+        std::thread::spawn({
+            let warm = Arc::clone(&warm);
+            move || serve_on(listener, warm)
+        });
+        assert!(bind(&socket).is_err());
+
+        let search_with = |args: &[&str], env: &[(&str, &str)]| -> io::Result<Vec<PathBuf>> {
+            let query = Query {
+                cwd: std::env::current_dir()?,
+                args: args.iter().map(|arg| arg.to_string()).collect(),
+                env: env
+                    .iter()
+                    .map(|(name, value)| (name.to_string(), value.to_string()))
+                    .collect(),
+            };
+            let mut paths = Vec::new();
+            for reply in ask(&socket, &query)? {
+                match reply? {
+                    Reply::Result(result) => paths.push(result.file_path),
+                    Reply::Done { found, .. } => {
+                        assert_eq!(found, paths.len());
+                        paths.sort();
+                        return Ok(paths);
+                    }
+                    Reply::Error(message) => return Err(io::Error::other(message)),
+                }
+            }
+            Err(io::ErrorKind::UnexpectedEof.into())
+        };
+        let search = |args: &[&str]| search_with(args, &[]);
+        let root = tree.to_str().unwrap();
+        assert_eq!(search(&["needle", root])?, [tree.join("a.txt")]);
+
+        // Files are read afresh, and new directories found, with the same
+        // searcher and a listing that's no longer fresh.
+        fs::create_dir(tree.join("sub"))?;
+        fs::write(tree.join("sub/b.txt"), "needle\n")?;
+        fs::write(tree.join("a.txt"), "hay\n")?;
+        assert_eq!(search(&["needle", root])?, [tree.join("sub/b.txt")]);
+        fs::write(tree.join(".ignore"), "sub/\n")?;
+        assert!(search(&["needle", root])?.is_empty());
+        {
+            let warm = warm.lock().unwrap();
+            assert_eq!(warm.searchers.len(), 1);
+            assert_eq!(warm.listings.len(), 1);
+        }
+
+        let error = search(&["(", "--regex", root]).unwrap_err();
+        assert!(error.to_string().contains("regex"), "{}", error);
+
+        // The client's variables are used, not the daemon's.
+        fs::write(tree.join(".hidden.txt"), "needle\n")?;
+        assert_eq!(
+            search_with(&["needle", root], &[("RUSTY_SCOUT_HIDDEN", "1")])?,
+            [tree.join(".hidden.txt")]
+        );
+        assert!(search(&["needle", root])?.is_empty());
+
+        // What the walk can't reach is an error, as it is searching here,
+        // each time the tree is searched.
+        std::os::unix::fs::symlink(dir.path().join("missing"), tree.join("broken"))?;
+        let errors = |args: &[&str]| -> io::Result<usize> {
+            let query = Query {
+                cwd: std::env::current_dir()?,
+                args: args.iter().map(|arg| arg.to_string()).collect(),
+                env: Default::default(),
+            };
+            for reply in ask(&socket, &query)? {
+                if let Reply::Done { errors, .. } = reply? {
+                    return Ok(errors);
+                }
+            }
+            Err(io::ErrorKind::UnexpectedEof.into())
+        };
+        assert_eq!(errors(&["needle", root])?, 0);
+        for _ in 0..2 {
+            assert_eq!(errors(&["needle", "--follow", root])?, 1);
+        }
+
+        // Ignore files above the tree change what a walk lists too.
+        fs::write(tree.join("a.txt"), "needle\n")?;
+        assert_eq!(search(&["needle", root])?, [tree.join("a.txt")]);
+        fs::write(dir.path().join(".ignore"), "a.txt\n")?;
+        assert!(search(&["needle", root])?.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_outside_ignore_files() {
+        let home = Path::new("/home/me");
+        let config = "[core]\n\tExcludesFile = \"~/.excludes\"\n";
+        assert_eq!(
+            excludes_file(config, Some(home)),
+            Some(home.join(".excludes"))
+        );
+        assert_eq!(excludes_file("[user]\n\tname = me\n", Some(home)), None);
+
+        let files = outside_ignore_files(Path::new("/a/b"));
+        assert!(files.contains(&PathBuf::from("/a/b/.git/info/exclude")));
+        assert!(files.contains(&PathBuf::from("/a/.gitignore")));
+        assert!(!files.contains(&PathBuf::from("/a/b/.gitignore")));
+    }
+}
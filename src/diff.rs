@@ -0,0 +1,237 @@
+use colored::*;
+use std::path::{Component, Path, PathBuf};
+
+/// Unchanged lines shown on either side of a change.
+pub const CONTEXT: usize = 3;
+
+/// A line `--replace` would change.
+pub struct Change<'a> {
+    pub line_number: usize,
+    /// How much of the old line was searched; the rest is kept as it is.
+    pub searched: usize,
+    pub replaced: &'a str,
+}
+
+/// A line of a hunk, with its line ending if it has one.
+pub enum Line<'a> {
+    Context(&'a str),
+    Removed(&'a str),
+    Added(String),
+}
+
+/// A run of changes close enough together to be shown as one, as in
+/// `diff -u`.
+pub struct Hunk<'a> {
+    /// First line shown, counting from 1.
+    pub start: usize,
+    /// Lines shown, which is the same before and after since replacements
+    /// never add or remove lines.
+    pub len: usize,
+    pub lines: Vec<Line<'a>>,
+}
+
+/// Groups `changes`, sorted by line, into hunks with `context` lines
+/// around each.
+pub fn hunks<'a>(contents: &'a str, changes: &[Change], context: usize) -> Vec<Hunk<'a>> {
+    let old: Vec<&str> = contents.split_inclusive('\n').collect();
+    let changes: Vec<_> = changes
+        .iter()
+        .filter(|change| change.line_number <= old.len())
+        .collect();
+
+    // Line ranges to show, with overlapping or touching ones merged.
+    let mut ranges: Vec<(usize, usize, &[&Change])> = Vec::new();
+    let mut first = 0;
+    for (i, change) in changes.iter().enumerate() {
+        let index = change.line_number - 1;
+        let (from, to) = (
+            index.saturating_sub(context),
+            (index + 1 + context).min(old.len()),
+        );
+        match ranges.last_mut() {
+            Some((_, end, group)) if from <= *end => {
+                *end = to;
+                *group = &changes[first..=i];
+            }
+            _ => {
+                first = i;
+                ranges.push((from, to, &changes[i..=i]));
+            }
+        }
+    }
+
+    ranges
+        .into_iter()
+        .map(|(from, to, group)| {
+            let mut group = group.iter().peekable();
+            let mut lines = Vec::new();
+            for (index, &line) in old.iter().enumerate().take(to).skip(from) {
+                match group.next_if(|change| change.line_number == index + 1) {
+                    Some(change) => {
+                        lines.push(Line::Removed(line));
+                        lines.push(Line::Added(format!(
+                            "{}{}",
+                            change.replaced,
+                            &line[change.searched..]
+                        )));
+                    }
+                    None => lines.push(Line::Context(line)),
+                }
+            }
+            Hunk {
+                start: from + 1,
+                len: to - from,
+                lines,
+            }
+        })
+        .collect()
+}
+
+/// Renders a file's hunks as a colored unified diff.
+pub fn render(path: &Path, hunks: &[Hunk]) -> String {
+    let path = path.display();
+    let mut out = format!(
+        "{}\n{}\n",
+        format!("--- {}", path).bold(),
+        format!("+++ {}", path).bold()
+    );
+    for hunk in hunks {
+        let range = format!("{},{}", hunk.start, hunk.len);
+        out.push_str(&format!(
+            "{}\n",
+            format!("@@ -{} +{} @@", range, range).cyan()
+        ));
+        for line in &hunk.lines {
+            let line = match line {
+                Line::Context(text) => format!(" {}", without_ending(text)),
+                Line::Removed(text) => format!("-{}", without_ending(text)).red().to_string(),
+                Line::Added(text) => format!("+{}", without_ending(text)).green().to_string(),
+            };
+            out.push_str(&line);
+            out.push('\n');
+        }
+    }
+    out
+}
+
+/// `path` relative to `dir`, as a patch applied from `dir` names it, or
+/// `None` if it's outside `dir`.
+pub fn relative_to(path: &Path, dir: &Path) -> Option<PathBuf> {
+    let within = |path: &Path| {
+        let mut normal = PathBuf::new();
+        for component in dir.join(path).components() {
+            match component {
+                Component::CurDir => {}
+                Component::ParentDir => {
+                    normal.pop();
+                }
+                component => normal.push(component),
+            }
+        }
+        normal.strip_prefix(dir).ok().map(Path::to_path_buf)
+    };
+    // The path may be through a symlink to `dir`, which has none.
+    within(path).or_else(|| within(&path.canonicalize().ok()?))
+}
+
+/// Writes a file's hunks as a patch that `git apply` or `patch -p1` can
+/// apply from the directory the search ran in, given `path` relative to it.
+pub fn patch(path: &Path, hunks: &[Hunk]) -> String {
+    let path = path.strip_prefix(".").unwrap_or(path).display();
+    let mut out = format!("--- a/{}\n+++ b/{}\n", path, path);
+    for hunk in hunks {
+        let range = format!("{},{}", hunk.start, hunk.len);
+        out.push_str(&format!("@@ -{} +{} @@\n", range, range));
+        for line in &hunk.lines {
+            let (prefix, text) = match line {
+                Line::Context(text) => (' ', *text),
+                Line::Removed(text) => ('-', *text),
+                Line::Added(text) => ('+', text.as_str()),
+            };
+            out.push(prefix);
+            out.push_str(text);
+            if !text.ends_with('\n') {
+                out.push_str("\n\\ No newline at end of file\n");
+            }
+        }
+    }
+    out
+}
+
+fn without_ending(line: &str) -> &str {
+    line.trim_end_matches('\n').trim_end_matches('\r')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn shown(hunk: &Hunk) -> Vec<String> {
+        hunk.lines
+            .iter()
+            .map(|line| match line {
+                Line::Context(text) => format!(" {}", without_ending(text)),
+                Line::Removed(text) => format!("-{}", without_ending(text)),
+                Line::Added(text) => format!("+{}", without_ending(text)),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_hunks() {
+        let contents: String = (1..=20).map(|n| format!("line {}\n", n)).collect();
+        let change = |line_number, replaced| Change {
+            line_number,
+            searched: 4,
+            replaced,
+        };
+        // Lines 2 and 6 share their context; line 18 is on its own.
+        let hunks = hunks(
+            &contents,
+            &[change(2, "LINE"), change(6, "LINE"), change(18, "LINE")],
+            2,
+        );
+        assert_eq!(hunks.len(), 2);
+        assert_eq!((hunks[0].start, hunks[0].len), (1, 8));
+        assert_eq!(
+            shown(&hunks[0]),
+            [
+                " line 1", "-line 2", "+LINE 2", " line 3", " line 4", " line 5", "-line 6",
+                "+LINE 6", " line 7", " line 8",
+            ]
+        );
+        assert_eq!((hunks[1].start, hunks[1].len), (16, 5));
+        assert_eq!(shown(&hunks[1])[3], "+LINE 18");
+    }
+
+    #[test]
+    fn test_patch() {
+        let contents = "keep\r\nold\r\nlast old";
+        let changes = [
+            Change {
+                line_number: 2,
+                searched: 3,
+                replaced: "new",
+            },
+            Change {
+                line_number: 3,
+                searched: 8,
+                replaced: "last new",
+            },
+        ];
+        let hunks = hunks(contents, &changes, CONTEXT);
+        assert_eq!(
+            patch(Path::new("./src/a.txt"), &hunks),
+            "--- a/src/a.txt\n+++ b/src/a.txt\n@@ -1,3 +1,3 @@\n keep\r\n-old\r\n+new\r\n\
+             -last old\n\\ No newline at end of file\n+last new\n\\ No newline at end of file\n"
+        );
+
+        let dir = Path::new("/work/repo");
+        let relative = |path| relative_to(Path::new(path), dir);
+        assert_eq!(relative("/work/repo/src/a.txt"), Some("src/a.txt".into()));
+        assert_eq!(relative("./src/../b.txt"), Some("b.txt".into()));
+        assert_eq!(relative("/work/other/a.txt"), None);
+        assert_eq!(relative("../repo/a.txt"), Some("a.txt".into()));
+        assert_eq!(relative("../other/a.txt"), None);
+    }
+}
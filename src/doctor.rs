@@ -0,0 +1,354 @@
+//! `--doctor`: the settings a search of the paths given would run with, and
+//! whether searching works at all, for working out why a file isn't found.
+
+use crate::cli::{Args, ColorChoice};
+use crate::config::Config;
+use crate::searcher::Searcher;
+use crate::walker::IGNORE_FILENAME;
+use colored::*;
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use ignore::Match;
+use std::error::Error;
+use std::fs;
+use std::io::{IsTerminal, Write};
+use std::path::{Path, PathBuf};
+
+/// An ignore file and the directory its rules are relative to.
+struct IgnoreFile {
+    path: PathBuf,
+    root: PathBuf,
+}
+
+impl IgnoreFile {
+    fn in_dir(dir: &Path, name: &str) -> Option<Self> {
+        let path = dir.join(name);
+        path.is_file().then(|| IgnoreFile {
+            path,
+            root: dir.to_path_buf(),
+        })
+    }
+
+    /// What the file's rules say about `path` itself, which is under its
+    /// root.
+    fn verdict(&self, path: &Path) -> Option<(bool, String)> {
+        let mut builder = GitignoreBuilder::new(&self.root);
+        builder.add(&self.path);
+        let rules = builder.build().ok()?;
+        let verdict = rules.matched(path, false);
+        describe(verdict, &self.path.display().to_string())
+    }
+}
+
+fn describe(verdict: Match<&ignore::gitignore::Glob>, file: &str) -> Option<(bool, String)> {
+    match verdict {
+        Match::None => None,
+        Match::Ignore(glob) => Some((true, format!("`{}` in {}", glob.original(), file))),
+        Match::Whitelist(glob) => Some((false, format!("`{}` in {}", glob.original(), file))),
+    }
+}
+
+/// The git repository `dir` is in.
+fn repo_root(dir: &Path) -> Option<&Path> {
+    dir.ancestors().find(|dir| dir.join(".git").exists())
+}
+
+/// The ignore files a walk of `dir` reads for what's directly inside it, in
+/// the order the walker consults them: its own files nearest first, then
+/// `.ignore` files, then git's.
+fn ignore_files(dir: &Path, args: &Args) -> Vec<IgnoreFile> {
+    if args.no_ignore {
+        return Vec::new();
+    }
+    let dirs: Vec<&Path> = if args.no_ignore_parent {
+        vec![dir]
+    } else {
+        dir.ancestors().collect()
+    };
+    let mut files: Vec<IgnoreFile> = [IGNORE_FILENAME, ".ignore"]
+        .iter()
+        .flat_map(|name| dirs.iter().filter_map(|dir| IgnoreFile::in_dir(dir, name)))
+        .collect();
+    if let Some(repo) = repo_root(dir).filter(|_| !args.no_ignore_vcs) {
+        // Git's own ignore files stop at the repository's root.
+        files.extend(
+            dirs.iter()
+                .take_while(|dir| dir.starts_with(repo))
+                .filter_map(|dir| IgnoreFile::in_dir(dir, ".gitignore")),
+        );
+        if dirs.contains(&repo) {
+            files.extend(
+                IgnoreFile::in_dir(&repo.join(".git/info"), "exclude").map(|exclude| IgnoreFile {
+                    root: repo.to_path_buf(),
+                    ..exclude
+                }),
+            );
+        }
+    }
+    files
+}
+
+/// Why a walk of the directory `file` is in would leave it out, if it would.
+fn skipped_by(file: &Path, args: &Args) -> Option<String> {
+    let dir = file.parent()?;
+    let hidden = file
+        .file_name()
+        .is_some_and(|name| name.to_string_lossy().starts_with('.'));
+    if hidden && !args.hidden {
+        return Some("it's hidden (--hidden searches it)".to_string());
+    }
+    let verdict = ignore_files(dir, args)
+        .iter()
+        .find_map(|ignore| ignore.verdict(file))
+        .or_else(|| {
+            let repo = repo_root(dir).filter(|_| !args.no_ignore && !args.no_ignore_vcs)?;
+            let (global, _) = Gitignore::global();
+            let relative = file.strip_prefix(repo).ok()?;
+            describe(global.matched(relative, false), "the global gitignore")
+        });
+    match verdict {
+        Some((true, rule)) => Some(format!("{} ignores it (--no-ignore searches it)", rule)),
+        _ => None,
+    }
+}
+
+/// Searches a small tree made for the purpose, returning what went wrong.
+fn self_test() -> Result<(), Box<dyn Error>> {
+    let dir = std::env::temp_dir().join(format!("rusty-scout-doctor-{}", std::process::id()));
+    fs::create_dir_all(&dir)?;
+    let searched = (|| {
+        fs::write(dir.join("a.txt"), "hay\nneedle\n")?;
+        fs::write(dir.join("b.txt"), "hay\n")?;
+        let args = Args::from_argv(["needle", "--no-ignore", "-j", "1"])?;
+        Searcher::new(&args)?.search(&dir)
+    })();
+    let _ = fs::remove_dir_all(&dir);
+    match &searched?[..] {
+        [found] if found.file_path.ends_with("a.txt") && found.line_number == 2 => Ok(()),
+        found => Err(format!(
+            "expected one match, on line 2 of a.txt, but found {:?}",
+            found
+        )
+        .into()),
+    }
+}
+
+fn features() -> Vec<&'static str> {
+    [
+        ("async", cfg!(feature = "async")),
+        ("clipboard", cfg!(feature = "clipboard")),
+        ("fancy-regex", cfg!(feature = "fancy-regex")),
+        ("io-uring", cfg!(feature = "io-uring")),
+        ("self-update", cfg!(feature = "self-update")),
+        ("syntax", cfg!(feature = "syntax")),
+    ]
+    .into_iter()
+    .filter_map(|(name, on)| on.then_some(name))
+    .collect()
+}
+
+fn var(name: &str) -> String {
+    match std::env::var(name) {
+        Ok(value) => format!("{}={}", name, value),
+        Err(_) => format!("{} unset", name),
+    }
+}
+
+/// Writes the report, returning whether the self-test search worked.
+pub(crate) fn report(args: &Args, out: &mut dyn Write) -> Result<bool, Box<dyn Error>> {
+    let features = features();
+    writeln!(
+        out,
+        "rusty-scout {} ({})",
+        env!("CARGO_PKG_VERSION"),
+        if features.is_empty() {
+            "no optional features".to_string()
+        } else {
+            format!("features: {}", features.join(", "))
+        }
+    )?;
+
+    writeln!(out, "\n{}", "Config files".bold())?;
+    match Config::path() {
+        Some(path) if path.is_file() => writeln!(out, "  user: {}", path.display())?,
+        Some(path) => writeln!(out, "  user: none ({} doesn't exist)", path.display())?,
+        None => writeln!(out, "  user: none (HOME and XDG_CONFIG_HOME are unset)")?,
+    }
+    for root in args.search_roots() {
+        match Config::find_project(&root) {
+            Some(path) => writeln!(out, "  project: {}", path.display())?,
+            None => writeln!(out, "  project: none in {} or above", root.display())?,
+        }
+    }
+    let mut vars: Vec<String> = std::env::vars()
+        .filter(|(name, _)| name.starts_with("RUSTY_SCOUT_"))
+        .map(|(name, value)| format!("{}={}", name, value))
+        .collect();
+    vars.sort();
+    if vars.is_empty() {
+        writeln!(out, "  environment: no RUSTY_SCOUT_ variables")?;
+    }
+    for var in vars {
+        writeln!(out, "  environment: {}", var)?;
+    }
+
+    for root in args.search_roots() {
+        let path = match root.canonicalize() {
+            Ok(path) => path,
+            Err(e) => {
+                writeln!(out, "\n{}: {}", root.display().to_string().bold(), e)?;
+                continue;
+            }
+        };
+        let (dir, file) = match path.parent() {
+            Some(dir) if path.is_file() => (dir, Some(&path)),
+            _ => (path.as_path(), None),
+        };
+        writeln!(
+            out,
+            "\n{}",
+            format!("Ignore files for {}", dir.display()).bold()
+        )?;
+        let files = ignore_files(dir, args);
+        if args.no_ignore {
+            writeln!(out, "  none, with --no-ignore")?;
+        } else if files.is_empty() {
+            writeln!(out, "  none, besides any in directories beneath it")?;
+        }
+        for ignore in &files {
+            writeln!(out, "  {}", ignore.path.display())?;
+        }
+        if repo_root(dir).is_some() && !args.no_ignore && !args.no_ignore_vcs {
+            let (global, _) = Gitignore::global();
+            if global.num_ignores() > 0 {
+                writeln!(
+                    out,
+                    "  the global gitignore ({} rules)",
+                    global.num_ignores()
+                )?;
+            }
+        }
+        if let Some(file) = file {
+            match skipped_by(file, args) {
+                Some(reason) => writeln!(
+                    out,
+                    "  a search of {} leaves out {}: {}",
+                    dir.display(),
+                    root.display(),
+                    reason
+                )?,
+                None => writeln!(
+                    out,
+                    "  a search of {} looks at {}, unless --glob, --type or other filters leave it out",
+                    dir.display(),
+                    root.display()
+                )?,
+            }
+        }
+    }
+
+    writeln!(out, "\n{}", "Colors".bold())?;
+    let terminal = std::io::stdout().is_terminal();
+    writeln!(
+        out,
+        "  stdout is {}",
+        if terminal {
+            "a terminal"
+        } else {
+            "not a terminal"
+        }
+    )?;
+    writeln!(
+        out,
+        "  {}, {}, {}, {}",
+        var("TERM"),
+        var("COLORTERM"),
+        var("NO_COLOR"),
+        var("CLICOLOR_FORCE")
+    )?;
+    let choice = match args.color.unwrap_or(ColorChoice::Auto) {
+        ColorChoice::Auto => "auto",
+        ColorChoice::Always => "always",
+        ColorChoice::Never => "never",
+    };
+    let colored = colored::control::SHOULD_COLORIZE.should_colorize();
+    writeln!(
+        out,
+        "  --color {}, so results {} colored",
+        choice,
+        if colored { "are" } else { "aren't" }
+    )?;
+
+    writeln!(out, "\n{}", "Threads".bold())?;
+    if args.threads == 0 {
+        let cores = std::thread::available_parallelism().map_or(1, |n| n.get());
+        writeln!(out, "  {}, one per core", cores)?;
+    } else {
+        writeln!(out, "  {}", args.threads)?;
+    }
+
+    writeln!(out, "\n{}", "Self-test".bold())?;
+    Ok(match self_test() {
+        Ok(()) => {
+            writeln!(
+                out,
+                "  {}: a search of a small tree found what it should",
+                "ok".green()
+            )?;
+            true
+        }
+        Err(e) => {
+            writeln!(out, "  {}: {}", "failed".red(), e)?;
+            false
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::parse_args;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_skipped_by() -> Result<(), Box<dyn Error>> {
+        let dir = tempdir()?;
+        let repo = dir.path().canonicalize()?.join("repo");
+        let sub = repo.join("sub");
+        fs::create_dir_all(repo.join(".git"))?;
+        fs::create_dir_all(&sub)?;
+        fs::write(repo.join(".gitignore"), "*.log\n")?;
+        fs::write(sub.join(".rscoutignore"), "*.snap\n")?;
+        for name in ["a.log", "b.rs", ".env", "c.snap"] {
+            fs::write(sub.join(name), "")?;
+        }
+
+        let args = parse_args(&["x"]);
+        let files: Vec<PathBuf> = ignore_files(&sub, &args)
+            .into_iter()
+            .map(|ignore| ignore.path)
+            .collect();
+        assert_eq!(files, [sub.join(".rscoutignore"), repo.join(".gitignore")]);
+
+        let reason = |name: &str, args: &Args| skipped_by(&sub.join(name), args);
+        assert!(reason("a.log", &args).unwrap().contains("`*.log`"));
+        assert!(reason("c.snap", &args).unwrap().contains("`*.snap`"));
+        assert!(reason(".env", &args).unwrap().contains("hidden"));
+        assert_eq!(reason("b.rs", &args), None);
+        assert_eq!(
+            reason("a.log", &parse_args(&["x", "--no-ignore-vcs"])),
+            None
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_report() -> Result<(), Box<dyn Error>> {
+        let mut out = Vec::new();
+        assert!(report(&parse_args(&["x", "-j", "3"]), &mut out)?);
+        let out = String::from_utf8(out)?;
+        assert!(out.contains("Threads"), "{}", out);
+        assert!(out.contains("  3\n"), "{}", out);
+        Ok(())
+    }
+}
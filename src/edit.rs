@@ -0,0 +1,197 @@
+//! `--edit`: opens the files a search matched in `$VISUAL` or `$EDITOR`,
+//! each at its first match.
+
+use crate::result::SearchResult;
+use crate::sink::Sink;
+use std::collections::HashSet;
+use std::error::Error;
+use std::ffi::OsString;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::{Arc, Mutex};
+
+/// Each file with a match and the line of its first one, 0 for results
+/// about a whole file, in the order they were found.
+pub(crate) type FirstMatches = Arc<Mutex<Vec<(PathBuf, usize)>>>;
+
+/// Passes results on to another sink, noting each file's first match.
+pub(crate) struct Noting {
+    inner: Box<dyn Sink + Send>,
+    found: FirstMatches,
+}
+
+impl Noting {
+    pub(crate) fn new(inner: Box<dyn Sink + Send>, found: FirstMatches) -> Self {
+        Noting { inner, found }
+    }
+}
+
+impl Sink for Noting {
+    fn on_file_start(&mut self, path: &Path) -> io::Result<()> {
+        self.inner.on_file_start(path)
+    }
+
+    fn on_match(&mut self, result: &SearchResult) -> io::Result<()> {
+        {
+            let mut found = self.found.lock().unwrap();
+            // A file's results arrive together.
+            if found
+                .last()
+                .is_none_or(|(path, _)| *path != result.file_path)
+            {
+                found.push((result.file_path.clone(), result.line_number));
+            }
+        }
+        self.inner.on_match(result)
+    }
+
+    fn on_file_error(&mut self, path: &Path, error: &str) -> io::Result<()> {
+        self.inner.on_file_error(path, error)
+    }
+
+    fn on_finish(&mut self, found: usize) -> io::Result<()> {
+        self.inner.on_finish(found)
+    }
+}
+
+/// The first match in each file among `results`, which may be in any order.
+pub(crate) fn first_matches(results: &[SearchResult]) -> Vec<(PathBuf, usize)> {
+    let mut seen = HashSet::new();
+    results
+        .iter()
+        .filter(|result| seen.insert(&result.file_path))
+        .map(|result| (result.file_path.clone(), result.line_number))
+        .collect()
+}
+
+/// How an editor is told which line to open a file at.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum LineStyle {
+    /// `+LINE FILE` before each file, as emacs, nano and most others take.
+    Plus,
+    /// `+LINE` before the first file only, since vim runs every `+`
+    /// command on the first file.
+    FirstPlus,
+    /// `--goto FILE:LINE`, as VS Code and its forks take.
+    Goto,
+}
+
+fn line_style(program: &str) -> LineStyle {
+    let name = Path::new(program)
+        .file_stem()
+        .map_or_else(String::new, |name| name.to_string_lossy().to_lowercase());
+    match name.as_str() {
+        "code" | "code-insiders" | "codium" | "cursor" => LineStyle::Goto,
+        "vi" | "vim" | "nvim" | "gvim" | "mvim" | "view" => LineStyle::FirstPlus,
+        _ => LineStyle::Plus,
+    }
+}
+
+/// The arguments, after the editor's own, that open `files` at their lines.
+fn file_args(style: LineStyle, files: &[(PathBuf, usize)]) -> Vec<OsString> {
+    let mut args = Vec::new();
+    for (i, (path, line)) in files.iter().enumerate() {
+        match style {
+            LineStyle::Goto if *line > 0 => {
+                let mut target = path.clone().into_os_string();
+                target.push(format!(":{}", line));
+                args.push("--goto".into());
+                args.push(target);
+                continue;
+            }
+            LineStyle::Plus if *line > 0 => args.push(format!("+{}", line).into()),
+            LineStyle::FirstPlus if *line > 0 && i == 0 => args.push(format!("+{}", line).into()),
+            _ => {}
+        }
+        args.push(path.clone().into_os_string());
+    }
+    args
+}
+
+/// `$VISUAL`, else `$EDITOR`, else `vi`, with any arguments given in it.
+fn editor() -> Result<Vec<String>, Box<dyn Error>> {
+    let command = ["VISUAL", "EDITOR"]
+        .iter()
+        .filter_map(|name| std::env::var(name).ok())
+        .find(|command| !command.trim().is_empty())
+        .unwrap_or_else(|| "vi".to_string());
+    match shlex::split(&command) {
+        Some(words) if !words.is_empty() => Ok(words),
+        _ => Err(format!("can't read the editor command {:?}", command).into()),
+    }
+}
+
+/// Opens `files` in one run of the editor, or with `each`, in one run per
+/// file, each after the last has exited.
+pub(crate) fn open(files: &[(PathBuf, usize)], each: bool) -> Result<(), Box<dyn Error>> {
+    let editor = editor()?;
+    let style = line_style(&editor[0]);
+    let runs: Vec<&[(PathBuf, usize)]> = if each {
+        files.chunks(1).collect()
+    } else {
+        vec![files]
+    };
+    for files in runs.into_iter().filter(|files| !files.is_empty()) {
+        let status = Command::new(&editor[0])
+            .args(&editor[1..])
+            .args(file_args(style, files))
+            .status()
+            .map_err(|e| format!("couldn't run {}: {}", editor[0], e))?;
+        if !status.success() {
+            return Err(format!("{} exited with {}", editor[0], status).into());
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sink::Counter;
+
+    #[test]
+    fn test_file_args() {
+        let files = [(PathBuf::from("a.rs"), 3), (PathBuf::from("b.rs"), 0)];
+        let args = |program: &str| -> Vec<String> {
+            file_args(line_style(program), &files)
+                .into_iter()
+                .map(|arg| arg.into_string().unwrap())
+                .collect()
+        };
+        assert_eq!(args("emacs"), ["+3", "a.rs", "b.rs"]);
+        assert_eq!(args("/usr/bin/nvim"), ["+3", "a.rs", "b.rs"]);
+        assert_eq!(args("code.exe"), ["--goto", "a.rs:3", "b.rs"]);
+
+        let files = [(PathBuf::from("a.rs"), 3), (PathBuf::from("b.rs"), 8)];
+        assert_eq!(
+            file_args(LineStyle::Plus, &files),
+            ["+3", "a.rs", "+8", "b.rs"].map(OsString::from)
+        );
+        assert_eq!(
+            file_args(LineStyle::FirstPlus, &files),
+            ["+3", "a.rs", "b.rs"].map(OsString::from)
+        );
+    }
+
+    #[test]
+    fn test_first_matches() -> io::Result<()> {
+        let result = |path: &str, line| SearchResult::new(Path::new(path), line, "x", Vec::new());
+        let results = [
+            result("a", 2),
+            result("a", 5),
+            result("b", 1),
+            result("a", 1),
+        ];
+        let expected = vec![(PathBuf::from("a"), 2), (PathBuf::from("b"), 1)];
+        assert_eq!(first_matches(&results), expected);
+
+        let found = FirstMatches::default();
+        let mut sink = Noting::new(Box::new(Counter::default()), Arc::clone(&found));
+        for result in &results[..3] {
+            sink.on_match(result)?;
+        }
+        assert_eq!(*found.lock().unwrap(), expected);
+        Ok(())
+    }
+}
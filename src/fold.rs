@@ -0,0 +1,166 @@
+use unicode_normalization::char::canonical_combining_class;
+use unicode_normalization::UnicodeNormalization;
+
+/// How lines and patterns are transformed before literal comparison.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Folder {
+    /// Compare case-insensitively.
+    pub ignore_case: bool,
+    /// Compare NFC-normalized text and, with `ignore_case`, full Unicode case
+    /// foldings (so `ß` matches `SS`) instead of per-character lowercasing.
+    pub unicode: bool,
+}
+
+/// A folded copy of a line that remembers where each piece came from, so
+/// matches found in the folded text can be reported against the original.
+pub struct FoldedLine {
+    pub text: String,
+    /// (folded offset, original offset) of the start of each segment.
+    segments: Vec<(usize, usize)>,
+    original_len: usize,
+}
+
+impl FoldedLine {
+    /// Maps a byte range in the folded text back onto the original line,
+    /// widening it to whole segments (e.g. a match on the first `s` of a
+    /// folded `ß` covers the `ß`).
+    pub fn original_span(&self, start: usize, end: usize) -> (usize, usize) {
+        let segment_of = |offset: usize| {
+            self.segments
+                .partition_point(|&(folded, _)| folded <= offset)
+                .saturating_sub(1)
+        };
+        let original_start = self.segments[segment_of(start)].1;
+        let original_end = if end <= start {
+            original_start
+        } else {
+            self.segments
+                .get(segment_of(end - 1) + 1)
+                .map_or(self.original_len, |&(_, original)| original)
+        };
+        (original_start, original_end)
+    }
+}
+
+impl Folder {
+    pub fn is_identity(&self) -> bool {
+        !self.ignore_case && !self.unicode
+    }
+
+    /// Folds a pattern the same way lines are folded.
+    pub fn fold_str(&self, text: &str) -> String {
+        self.fold_line(text).text
+    }
+
+    pub fn fold_line(&self, line: &str) -> FoldedLine {
+        let mut folded = FoldedLine {
+            text: String::with_capacity(line.len()),
+            segments: Vec::new(),
+            original_len: line.len(),
+        };
+
+        let mut segment_start = 0;
+        let mut chars = line.char_indices().peekable();
+        while let Some((offset, c)) = chars.next() {
+            let mut segment_end = offset + c.len_utf8();
+            if self.unicode {
+                // Keep combining marks with their base character so that
+                // normalization can compose them.
+                while let Some(&(next_offset, next)) = chars.peek() {
+                    if canonical_combining_class(next) == 0 {
+                        break;
+                    }
+                    segment_end = next_offset + next.len_utf8();
+                    chars.next();
+                }
+            }
+            folded.segments.push((folded.text.len(), segment_start));
+            self.fold_segment(&line[segment_start..segment_end], &mut folded.text);
+            segment_start = segment_end;
+        }
+
+        folded
+    }
+
+    fn fold_segment(&self, segment: &str, out: &mut String) {
+        match (self.unicode, self.ignore_case) {
+            (false, false) => out.push_str(segment),
+            (false, true) => out.extend(segment.chars().flat_map(char::to_lowercase)),
+            (true, false) => out.extend(segment.nfc()),
+            (true, true) => {
+                let normalized: String = segment.nfc().collect();
+                // Dotted capital I folds to a plain `i` so that `İstanbul`
+                // matches `istanbul` rather than `i` plus a combining dot.
+                let normalized = normalized.replace('\u{130}', "i");
+                out.extend(caseless::default_case_fold_str(&normalized).nfc());
+            }
+        }
+    }
+}
+
+/// Gives `replacement` the case of `matched` when it's all capitals, all
+/// lowercase or capitalized, for `--preserve-case`. Any other mix, or no
+/// letters at all, leaves it as written.
+pub fn match_case(matched: &str, replacement: &str) -> String {
+    let mut letters = matched.chars().filter(|c| c.is_alphabetic());
+    let Some(first) = letters.next() else {
+        return replacement.to_string();
+    };
+    let rest: Vec<char> = letters.collect();
+    if first.is_uppercase() && !rest.is_empty() && rest.iter().all(|c| c.is_uppercase()) {
+        replacement.to_uppercase()
+    } else if first.is_lowercase() && rest.iter().all(|c| c.is_lowercase()) {
+        replacement.to_lowercase()
+    } else if first.is_uppercase() && rest.iter().all(|c| c.is_lowercase()) {
+        let mut chars = replacement.chars();
+        chars
+            .next()
+            .map(|c| c.to_uppercase().chain(chars).collect())
+            .unwrap_or_default()
+    } else {
+        replacement.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const UNICODE_FOLD: Folder = Folder {
+        ignore_case: true,
+        unicode: true,
+    };
+
+    #[test]
+    fn test_fold_sharp_s_maps_back() {
+        let folded = UNICODE_FOLD.fold_line("Straße");
+        assert_eq!(folded.text, "strasse");
+        // "ss" covers the two-byte `ß` in the original.
+        assert_eq!(folded.original_span(4, 6), (4, 6));
+        assert_eq!(folded.original_span(5, 7), (4, 7));
+    }
+
+    #[test]
+    fn test_fold_normalizes_decomposed_accents() {
+        let decomposed = "cafe\u{301}";
+        let folded = UNICODE_FOLD.fold_line(decomposed);
+        assert_eq!(folded.text, UNICODE_FOLD.fold_str("CAFÉ"));
+        assert_eq!(folded.original_span(3, folded.text.len()), (3, 6));
+    }
+
+    #[test]
+    fn test_fold_dotted_capital_i() {
+        assert_eq!(UNICODE_FOLD.fold_str("İstanbul"), "istanbul");
+    }
+
+    #[test]
+    fn test_match_case() {
+        assert_eq!(match_case("color", "colour"), "colour");
+        assert_eq!(match_case("Color", "colour"), "Colour");
+        assert_eq!(match_case("COLOR", "colour"), "COLOUR");
+        assert_eq!(match_case("C", "colour"), "Colour");
+        assert_eq!(match_case("coLor", "colour"), "colour");
+        assert_eq!(match_case("cOLOR", "colour"), "colour");
+        assert_eq!(match_case("42", "colour"), "colour");
+    }
+}
@@ -0,0 +1,104 @@
+//! `--history` and `--repeat`: the command lines of recent searches, one
+//! per line, quoted as in a shell, in `$XDG_STATE_HOME/rusty-scout/history`.
+
+use std::error::Error;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Searches kept; older ones are dropped as new ones are recorded.
+pub(crate) const HISTORY_SIZE: usize = 100;
+
+/// `$XDG_STATE_HOME/rusty-scout/history`, falling back to `~/.local/state`.
+pub(crate) fn path() -> Option<PathBuf> {
+    let base = std::env::var_os("XDG_STATE_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| {
+            std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".local").join("state"))
+        })?;
+    Some(base.join("rusty-scout").join("history"))
+}
+
+fn read_lines(path: &Path) -> io::Result<Vec<String>> {
+    match fs::read_to_string(path) {
+        Ok(contents) => Ok(contents.lines().map(str::to_string).collect()),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(e) => Err(e),
+    }
+}
+
+/// The recorded command lines, oldest first.
+pub(crate) fn load(path: &Path) -> io::Result<Vec<Vec<String>>> {
+    Ok(read_lines(path)?
+        .iter()
+        .filter_map(|line| shlex::split(line))
+        .collect())
+}
+
+/// Adds `args` to the history, unless they're what was searched last.
+pub(crate) fn record(path: &Path, args: &[String]) -> io::Result<()> {
+    let Ok(line) = shlex::try_join(args.iter().map(String::as_str)) else {
+        // Arguments with NUL bytes can't be quoted, or run again.
+        return Ok(());
+    };
+    let mut lines = read_lines(path)?;
+    if lines.last() == Some(&line) {
+        return Ok(());
+    }
+    lines.push(line);
+    let start = lines.len().saturating_sub(HISTORY_SIZE);
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let mut contents = lines[start..].join("\n");
+    contents.push('\n');
+    fs::write(path, contents)
+}
+
+/// The command line `back` searches ago, counting the last one as 1.
+pub(crate) fn entry(path: &Path, back: usize) -> Result<Vec<String>, Box<dyn Error>> {
+    let mut entries = load(path)?;
+    match back
+        .checked_sub(1)
+        .and_then(|i| entries.len().checked_sub(i + 1))
+    {
+        Some(index) => Ok(entries.swap_remove(index)),
+        None => Err(format!(
+            "there's no search {} back; --history lists the {} there are",
+            back,
+            entries.len()
+        )
+        .into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_history() -> Result<(), Box<dyn Error>> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("rusty-scout/history");
+        let args = |args: &[&str]| args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>();
+        assert!(load(&path)?.is_empty());
+
+        record(&path, &args(&["-E", r"fn \w+", "src"]))?;
+        record(&path, &args(&["TODO"]))?;
+        record(&path, &args(&["TODO"]))?;
+        assert_eq!(load(&path)?.len(), 2);
+        assert_eq!(entry(&path, 1)?, ["TODO"]);
+        assert_eq!(entry(&path, 2)?, ["-E", r"fn \w+", "src"]);
+        assert!(entry(&path, 3).is_err());
+        assert!(entry(&path, 0).is_err());
+
+        for i in 0..HISTORY_SIZE {
+            record(&path, &[i.to_string()])?;
+        }
+        let entries = load(&path)?;
+        assert_eq!(entries.len(), HISTORY_SIZE);
+        assert_eq!(entries[0], ["0"]);
+        Ok(())
+    }
+}
@@ -0,0 +1,367 @@
+//! `--serve`: a small HTTP API over one directory, for dashboards and people
+//! without the binary.
+//!
+//! `GET /search?q=TODO&path=src` responds with
+//! `{"results": [...], "truncated": false}`, each result as `--json` prints
+//! it, with its path relative to the served directory. With
+//! `Accept: text/event-stream`, or `stream=1`, results are instead sent as
+//! server-sent events as they're found: a `match` event for each, then a
+//! `done` event with how many there were, or an `error` event if the
+//! search fails partway.
+//!
+//! Other parameters, which may be repeated where it makes sense, are
+//! `regex`, `ignore_case`, `fuzzy`, `hidden`, `glob`, `exclude`, `type`,
+//! `max_count` and `limit`.
+
+use crate::options::{SearchOptions, SearcherBuilder};
+use crate::sandbox::Sandbox;
+use crate::searcher::Searcher;
+use serde_json::{json, Value};
+use std::error::Error;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::Duration;
+
+/// Results a search stops at when `limit` isn't given.
+const DEFAULT_LIMIT: usize = 1000;
+
+/// Most bytes read of a request, which never needs a body.
+const MAX_REQUEST: u64 = 16 * 1024;
+
+/// How long a client can leave the server waiting to read or write.
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Connections answered at once; more wait their turn.
+const MAX_CONNECTIONS: usize = 64;
+
+/// What a `/search` request asks for.
+struct SearchRequest {
+    path: Option<String>,
+    options: SearchOptions,
+    stream: bool,
+}
+
+fn flag(value: &str) -> Result<bool, String> {
+    match value {
+        "" | "1" | "true" => Ok(true),
+        "0" | "false" => Ok(false),
+        _ => Err(format!("{:?} isn't true or false", value)),
+    }
+}
+
+fn count(name: &str, value: &str) -> Result<usize, String> {
+    value
+        .parse()
+        .map_err(|_| format!("{} must be a number, not {:?}", name, value))
+}
+
+/// Reads a `/search` query string.
+fn parse_query(query: &str) -> Result<SearchRequest, String> {
+    let mut request = SearchRequest {
+        path: None,
+        options: SearchOptions {
+            limit: Some(DEFAULT_LIMIT),
+            ..SearchOptions::default()
+        },
+        stream: false,
+    };
+    let options = &mut request.options;
+    for (name, value) in form_urlencoded::parse(query.as_bytes()) {
+        let value = value.into_owned();
+        match &*name {
+            "q" => options.patterns.push(value),
+            "path" => request.path = Some(value),
+            "regex" => options.regex = flag(&value)?,
+            "ignore_case" => options.ignore_case = flag(&value)?,
+            "fuzzy" => options.fuzzy = flag(&value)?,
+            "hidden" => options.hidden = flag(&value)?,
+            "glob" => options.globs.push(value),
+            "exclude" => options.exclude.push(value),
+            "type" => options.types.push(value),
+            "max_count" => options.max_count = Some(count(&name, &value)?),
+            "limit" => options.limit = Some(count(&name, &value)?),
+            "stream" => request.stream = flag(&value)?,
+            _ => return Err(format!("unknown parameter {:?}", name)),
+        }
+    }
+    if options.patterns.is_empty() {
+        return Err("q is required".to_string());
+    }
+    Ok(request)
+}
+
+/// The request line's method and target, and whether the client asked for
+/// server-sent events.
+fn read_request(stream: &TcpStream) -> io::Result<(String, String, bool)> {
+    let mut reader = BufReader::new(stream.take(MAX_REQUEST));
+    let mut line = String::new();
+    reader.read_line(&mut line)?;
+    let mut parts = line.split_whitespace();
+    let (method, target) = match (parts.next(), parts.next()) {
+        (Some(method), Some(target)) => (method.to_string(), target.to_string()),
+        _ => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "malformed request line",
+            ))
+        }
+    };
+    let mut events = false;
+    loop {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 || line.trim().is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            events |=
+                name.trim().eq_ignore_ascii_case("accept") && value.contains("text/event-stream");
+        }
+    }
+    Ok((method, target, events))
+}
+
+fn respond(stream: &mut TcpStream, status: &str, body: &Value) -> io::Result<()> {
+    let body = body.to_string();
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    )
+}
+
+fn answer(stream: &mut TcpStream, sandbox: &Sandbox) -> io::Result<()> {
+    let (method, target, events) = read_request(stream)?;
+    let (route, query) = target.split_once('?').unwrap_or((&target, ""));
+    if route != "/search" {
+        return respond(stream, "404 Not Found", &json!({"error": "not found"}));
+    }
+    if method != "GET" {
+        return respond(
+            stream,
+            "405 Method Not Allowed",
+            &json!({"error": "only GET is allowed"}),
+        );
+    }
+    let request = match parse_query(query) {
+        Ok(request) => request,
+        Err(e) => return respond(stream, "400 Bad Request", &json!({"error": e})),
+    };
+    let limit = request.options.limit.unwrap_or(usize::MAX);
+    let (searcher, path) = match start(sandbox, &request) {
+        Ok(started) => started,
+        Err(e) => return bad_request(stream, e),
+    };
+    if events || request.stream {
+        return send_events(stream, sandbox, searcher, path, limit);
+    }
+    match searcher.search(&path) {
+        Ok(mut results) => {
+            let truncated = results.len() > limit;
+            results.truncate(limit);
+            let body = json!({
+                "results": results.iter().map(|r| sandbox.to_json(r)).collect::<Vec<_>>(),
+                "truncated": truncated,
+            });
+            respond(stream, "200 OK", &body)
+        }
+        Err(e) => bad_request(stream, e),
+    }
+}
+
+/// The searcher for `request` and the path it searches.
+fn start(
+    sandbox: &Sandbox,
+    request: &SearchRequest,
+) -> Result<(Searcher, PathBuf), Box<dyn Error>> {
+    let path = sandbox.resolve(request.path.as_deref())?;
+    let searcher = SearcherBuilder::from_options(SearchOptions {
+        // One more than is sent, to tell whether there were more.
+        limit: request.options.limit.map(|limit| limit.saturating_add(1)),
+        ..request.options.clone()
+    })
+    .build()?;
+    Ok((searcher, path))
+}
+
+fn bad_request(stream: &mut TcpStream, error: Box<dyn Error>) -> io::Result<()> {
+    respond(
+        stream,
+        "400 Bad Request",
+        &json!({"error": error.to_string()}),
+    )
+}
+
+/// Sends each result as a server-sent event once the headers are out,
+/// after which an error can only be an event of its own.
+fn send_events(
+    stream: &mut TcpStream,
+    sandbox: &Sandbox,
+    searcher: Searcher,
+    path: PathBuf,
+    limit: usize,
+) -> io::Result<()> {
+    write!(
+        stream,
+        "HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nCache-Control: no-cache\r\nConnection: close\r\n\r\n"
+    )?;
+    let mut found = 0;
+    let mut truncated = false;
+    // Dropping the iterator, as when the client goes away, stops the search.
+    for result in searcher.search_iter(path) {
+        if found == limit {
+            truncated = true;
+            break;
+        }
+        let data = match result {
+            Ok(result) => sandbox.to_json(&result),
+            Err(e) => {
+                return write!(
+                    stream,
+                    "event: error\ndata: {}\n\n",
+                    json!({"error": e.to_string()})
+                );
+            }
+        };
+        write!(stream, "event: match\ndata: {}\n\n", data)?;
+        found += 1;
+    }
+    write!(
+        stream,
+        "event: done\ndata: {}\n\n",
+        json!({"matches": found, "truncated": truncated})
+    )
+}
+
+/// Serves `root` on `address`, such as `127.0.0.1:7878`, until killed.
+pub(crate) fn serve(address: &str, root: &Path) -> io::Result<()> {
+    let sandbox = Sandbox::new(root)?;
+    let listener = TcpListener::bind(address)?;
+    eprintln!("Listening on http://{}", listener.local_addr()?);
+    serve_on(listener, Arc::new(sandbox), MAX_CONNECTIONS)
+}
+
+fn serve_on(
+    listener: TcpListener,
+    sandbox: Arc<Sandbox>,
+    max_connections: usize,
+) -> io::Result<()> {
+    let open = Arc::new((Mutex::new(0), Condvar::new()));
+    for stream in listener.incoming() {
+        let Ok(mut stream) = stream else {
+            continue;
+        };
+        // A client that stops reading or writing is let go rather than
+        // holding its thread.
+        if stream.set_read_timeout(Some(CLIENT_TIMEOUT)).is_err()
+            || stream.set_write_timeout(Some(CLIENT_TIMEOUT)).is_err()
+        {
+            continue;
+        }
+        // Past the limit, new connections wait to be accepted until one
+        // finishes.
+        {
+            let (count, finished) = &*open;
+            let mut count = finished
+                .wait_while(count.lock().unwrap(), |count| *count >= max_connections)
+                .unwrap();
+            *count += 1;
+        }
+        let sandbox = Arc::clone(&sandbox);
+        let open = Arc::clone(&open);
+        std::thread::spawn(move || {
+            // A client that hangs up early has nothing left to be told.
+            let _ = answer(&mut stream, &sandbox);
+            // Before the client sees the connection close, so it can make
+            // another straight away.
+            let (count, finished) = &*open;
+            *count.lock().unwrap() -= 1;
+            finished.notify_one();
+            drop(stream);
+        });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::tempdir;
+
+    fn get(address: &str, target: &str) -> io::Result<(String, String)> {
+        let mut stream = TcpStream::connect(address)?;
+        write!(stream, "GET {} HTTP/1.1\r\nHost: test\r\n\r\n", target)?;
+        let mut response = String::new();
+        stream.read_to_string(&mut response)?;
+        let (head, body) = response.split_once("\r\n\r\n").unwrap();
+        Ok((head.lines().next().unwrap().to_string(), body.to_string()))
+    }
+
+    #[test]
+    fn test_serve() -> Result<(), Box<dyn Error>> {
+        let dir = tempdir()?;
+        let root = dir.path().join("tree");
+        fs::create_dir_all(root.join("src"))?;
+        fs::write(root.join("src/a.rs"), "// TODO: one\n// todo: two\n")?;
+        fs::write(dir.path().join("outside.rs"), "// TODO\n")?;
+
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let address = listener.local_addr()?.to_string();
+        let sandbox = Arc::new(Sandbox::new(&root).unwrap());
+        std::thread::spawn(move || serve_on(listener, sandbox, 1));
+
+        let (status, body) = get(&address, "/search?q=todo%3A&ignore_case=1&path=src")?;
+        assert_eq!(status, "HTTP/1.1 200 OK");
+        let body: Value = serde_json::from_str(&body)?;
+        assert_eq!(body["results"].as_array().unwrap().len(), 2);
+        assert_eq!(body["results"][0]["path"], "src/a.rs");
+        assert_eq!(body["truncated"], false);
+
+        let (status, body) = get(&address, "/search?q=TODO&stream=1")?;
+        assert_eq!(status, "HTTP/1.1 200 OK");
+        assert!(body.starts_with("event: match\ndata: {"), "{}", body);
+        assert!(body.ends_with("event: done\ndata: {\"matches\":1,\"truncated\":false}\n\n"));
+
+        for (limit, truncated) in [(2, false), (1, true)] {
+            let query = format!("/search?q=todo&ignore_case=1&limit={}", limit);
+            let body: Value = serde_json::from_str(&get(&address, &query)?.1)?;
+            assert_eq!(body["results"].as_array().unwrap().len(), limit);
+            assert_eq!(body["truncated"], truncated);
+            let (_, body) = get(&address, &format!("{}&stream=1", query))?;
+            let done = format!(
+                "event: done\ndata: {{\"matches\":{},\"truncated\":{}}}\n\n",
+                limit, truncated
+            );
+            assert!(body.ends_with(&done), "{}", body);
+        }
+
+        for target in [
+            "/search?q=TODO&path=..",
+            "/search?path=src",
+            "/search?q=x&colour=1",
+        ] {
+            assert_eq!(
+                get(&address, target)?.0,
+                "HTTP/1.1 400 Bad Request",
+                "{}",
+                target
+            );
+        }
+        assert_eq!(get(&address, "/")?.0, "HTTP/1.1 404 Not Found");
+
+        // While one client holds the only connection, others wait for it.
+        let idle = TcpStream::connect(&address)?;
+        let (sender, receiver) = std::sync::mpsc::channel();
+        std::thread::spawn(move || sender.send(get(&address, "/").unwrap().0));
+        let wait = Duration::from_millis(200);
+        assert!(receiver.recv_timeout(wait).is_err());
+        drop(idle);
+        assert_eq!(receiver.recv_timeout(wait * 10)?, "HTTP/1.1 404 Not Found");
+
+        Ok(())
+    }
+}
@@ -0,0 +1,143 @@
+use crate::budget;
+use crate::result::SearchResult;
+use crate::searcher::{Event, ResultBatch, Searcher, RESULT_CHANNEL_CAPACITY};
+use crate::SearchError;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+
+/// Results of a search running on its own thread, handed over as they're
+/// found. Dropping it stops the search.
+pub struct SearchIter {
+    events: Receiver<Event>,
+    current: std::vec::IntoIter<SearchResult>,
+    /// The current batch's `--max-memory` room, held until it's handed out.
+    _reservation: Option<budget::Reservation>,
+    cancelled: Arc<AtomicBool>,
+    search: Option<JoinHandle<Result<(), SearchError>>>,
+}
+
+/// Stops a search, which then finishes with the results found so far.
+#[derive(Clone, Debug)]
+pub struct Cancel(Arc<AtomicBool>);
+
+impl Cancel {
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+}
+
+impl Iterator for SearchIter {
+    type Item = Result<SearchResult, SearchError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(result) = self.current.next() {
+                return Some(Ok(result));
+            }
+            match self.events.recv() {
+                Ok(Event::Results(ResultBatch {
+                    results,
+                    _reservation,
+                })) => {
+                    self.current = results.into_iter();
+                    self._reservation = _reservation;
+                }
+                // Files that can't be read are left out, as in `search`.
+                Ok(Event::FileError(..)) => {}
+                // The search is over once it drops its end of the channel.
+                Err(_) => {
+                    return match self.search.take()?.join() {
+                        Ok(Ok(())) => None,
+                        Ok(Err(e)) => Some(Err(e)),
+                        Err(panic) => std::panic::resume_unwind(panic),
+                    };
+                }
+            }
+        }
+    }
+}
+
+impl SearchIter {
+    /// A handle that stops the search from another thread.
+    pub fn cancel_handle(&self) -> Cancel {
+        Cancel(Arc::clone(&self.cancelled))
+    }
+}
+
+impl Drop for SearchIter {
+    fn drop(&mut self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+}
+
+impl Searcher {
+    /// Searches on another thread, handing results over as they're found
+    /// instead of all at once. Searching waits while a few hundred files'
+    /// results are unread, so memory stays bounded however many there are.
+    /// Ranked results come in the order they're found, not by score.
+    pub fn search_iter(mut self, path: impl Into<PathBuf>) -> SearchIter {
+        let path = path.into();
+        // Before the thread starts, so a cancel straight away isn't undone.
+        self.clear();
+        let (sender, events) = mpsc::sync_channel(RESULT_CHANNEL_CAPACITY);
+        self.stream_to(sender);
+        let cancelled = Arc::clone(&self.cancelled);
+        let search = std::thread::spawn(move || {
+            self.search_dir(&path)
+                .map_err(|e| match e.downcast::<SearchError>() {
+                    Ok(e) => *e,
+                    Err(e) => SearchError::new("SearchFailed", e.to_string()),
+                })
+        });
+        SearchIter {
+            events,
+            current: Vec::new().into_iter(),
+            _reservation: None,
+            cancelled,
+            search: Some(search),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::error::Error;
+    use std::fs;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_search_iter() -> Result<(), Box<dyn Error>> {
+        let dir = tempdir()?;
+        for i in 0..50 {
+            fs::write(dir.path().join(format!("{}.txt", i)), "needle\nneedle\n")?;
+        }
+
+        let searcher = Searcher::builder().pattern("needle").build()?;
+        let results: Vec<_> = searcher.search_iter(dir.path()).collect::<Result<_, _>>()?;
+        assert_eq!(results.len(), 100);
+
+        // Cancelling lets the results already sent through, then ends.
+        let searcher = Searcher::builder().pattern("needle").threads(1).build()?;
+        let results = searcher.search_iter(dir.path());
+        results.cancel_handle().cancel();
+        assert!(results.count() < 100);
+
+        // Stopping early drops the rest without waiting for them.
+        let searcher = Searcher::builder().pattern("needle").threads(1).build()?;
+        assert_eq!(searcher.search_iter(dir.path()).take(3).count(), 3);
+
+        let searcher = Searcher::builder().pattern("needle").glob("[").build()?;
+        let error = searcher
+            .search_iter(dir.path())
+            .next()
+            .unwrap()
+            .unwrap_err();
+        assert!(error.to_string().contains("SearchFailed"));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,99 @@
+//! Rusty Scout: fast, parallel search of files for text, regexes, byte
+//! sequences and code patterns.
+//!
+//! The `rusty-scout` binary is a thin wrapper around [`cli::run`]. Other
+//! programs can search without shelling out by building a [`Searcher`] and
+//! reading back [`SearchResult`]s:
+//!
+//! ```no_run
+//! use rusty_scout::{cli::Args, Searcher};
+//! use std::path::Path;
+//!
+//! let args = Args::from_argv(["TODO", "--ignore-case"])?;
+//! let searcher = Searcher::new(&args)?;
+//! for result in searcher.search(Path::new("src"))? {
+//!     println!("{}:{}: {}", result.file_path.display(), result.line_number, result.line);
+//! }
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+
+mod budget;
+mod buffer;
+pub mod cli;
+mod clipboard;
+mod clock;
+mod config;
+#[cfg(unix)]
+mod daemon;
+mod diff;
+mod doctor;
+mod edit;
+mod fold;
+mod history;
+mod http;
+mod iter;
+mod logging;
+mod matcher;
+mod mcp;
+mod mime;
+mod options;
+mod output;
+mod perms;
+mod query;
+mod replace;
+mod result;
+mod rpc;
+mod rules;
+mod sandbox;
+mod searcher;
+mod sink;
+mod stats;
+#[cfg(feature = "async")]
+mod stream;
+#[cfg(feature = "syntax")]
+mod syntax;
+mod types;
+mod units;
+#[cfg(feature = "self-update")]
+mod update;
+#[cfg(all(feature = "io-uring", target_os = "linux"))]
+mod uring;
+mod vfs;
+mod walker;
+
+pub use iter::{Cancel, SearchIter};
+pub use options::{SearchOptions, SearcherBuilder};
+pub use result::{RelatedLine, Replacement, SearchResult, JSON_SCHEMA, JSON_SCHEMA_VERSION};
+pub use rules::{Rule, Severity};
+pub use searcher::Searcher;
+pub use sink::{Counter, JsonWriter, Sink};
+#[cfg(feature = "async")]
+pub use stream::SearchStream;
+pub use vfs::{FileSystem, MemoryTree};
+
+use std::error::Error;
+
+/// An error from setting up or running a search, such as an invalid pattern
+/// or a file that took too long.
+#[derive(Debug)]
+pub struct SearchError {
+    kind: String,
+    message: String,
+}
+
+impl std::fmt::Display for SearchError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}: {}", self.kind, self.message)
+    }
+}
+
+impl Error for SearchError {}
+
+impl SearchError {
+    pub(crate) fn new(kind: &str, message: impl Into<String>) -> Self {
+        SearchError {
+            kind: kind.to_string(),
+            message: message.into(),
+        }
+    }
+}
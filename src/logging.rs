@@ -0,0 +1,69 @@
+//! `--verbose` and `--debug`: why files were left out of a search, and how long
+//! each step took, written to stderr through the `log` crate.
+
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use std::time::Instant;
+
+struct Logger {
+    started: Instant,
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        // Dependencies' messages are left out.
+        metadata.level() <= log::max_level() && metadata.target().starts_with("rusty_scout")
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let level = match record.level() {
+            Level::Error => "error",
+            Level::Warn => "warning",
+            Level::Info => "info",
+            Level::Debug | Level::Trace => "debug",
+        };
+        eprintln!(
+            "[{:7.3}s {}] {}",
+            self.started.elapsed().as_secs_f64(),
+            level,
+            record.args()
+        );
+    }
+
+    fn flush(&self) {}
+}
+
+/// Sends warnings and info to stderr with `verbose`, and debug messages
+/// too with `debug`. Only the first call in a process has any effect.
+pub(crate) fn init(verbose: bool, debug: bool) {
+    let level = match (verbose, debug) {
+        (_, true) => LevelFilter::Debug,
+        (true, false) => LevelFilter::Info,
+        (false, false) => return,
+    };
+    let logger = Logger {
+        started: Instant::now(),
+    };
+    if log::set_boxed_logger(Box::new(logger)).is_ok() {
+        log::set_max_level(level);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_enabled() {
+        log::set_max_level(LevelFilter::Info);
+        let logger = Logger {
+            started: Instant::now(),
+        };
+        let metadata = |level, target| Metadata::builder().level(level).target(target).build();
+        assert!(logger.enabled(&metadata(Level::Warn, "rusty_scout::walker")));
+        assert!(!logger.enabled(&metadata(Level::Debug, "rusty_scout::walker")));
+        assert!(!logger.enabled(&metadata(Level::Info, "ignore::walk")));
+    }
+}
@@ -0,0 +1,1034 @@
+use crate::fold::Folder;
+use crate::SearchError;
+use aho_corasick::{AhoCorasick, AhoCorasickBuilder, MatchKind};
+use regex::{Regex, RegexBuilder};
+use regex_syntax::hir::literal::Extractor;
+use std::error::Error;
+
+/// A compiled pattern that reports the byte ranges it matches in a line.
+///
+/// [`Searcher`](crate::Searcher) only talks to this trait, so new engines
+/// can be added without touching the searching or display code.
+pub trait Matcher: Send + Sync {
+    /// Returns the (start, end) byte positions of every match in `line`.
+    fn find_matches(&self, line: &str) -> Vec<(usize, usize)>;
+
+    /// Like `find_matches`, but fails when the engine gives up on `line`
+    /// instead of leaving out what it couldn't check.
+    fn try_find_matches(&self, line: &str) -> Result<Vec<(usize, usize)>, SearchError> {
+        Ok(self.find_matches(line))
+    }
+
+    /// Relevance of the hit in `line`, for matchers that rank their results.
+    fn score(&self, _line: &str) -> Option<i64> {
+        None
+    }
+
+    /// Whether a file with these contents should have its lines searched at
+    /// all, for matchers with file-level conditions.
+    fn matches_file(&self, _contents: &str) -> bool {
+        true
+    }
+
+    /// Whether `matches_file` needs the whole file, so lines can't be
+    /// searched as they're read.
+    fn needs_whole_file(&self) -> bool {
+        false
+    }
+
+    /// Fewest edits among the hits in `line`, for matchers that allow
+    /// approximate matches.
+    fn edit_distance(&self, _line: &str) -> Option<usize> {
+        None
+    }
+
+    /// `replacement` with `$1` and `${name}` filled in from the match
+    /// starting at `start` in `line`, for matchers with capture groups.
+    fn expand(&self, _line: &str, _start: usize, _replacement: &str) -> Option<String> {
+        None
+    }
+}
+
+/// Plain substring search for a single literal pattern.
+pub struct LiteralMatcher {
+    pattern: String,
+    folder: Folder,
+    /// For a case-insensitive ASCII pattern, finds it in ASCII lines without
+    /// folding them; those fold the same way either way.
+    ascii_finder: Option<AhoCorasick>,
+}
+
+impl LiteralMatcher {
+    pub fn new(pattern: &str, folder: Folder) -> Self {
+        let pattern = folder.fold_str(pattern);
+        let ascii_finder = (folder.ignore_case && !folder.unicode && pattern.is_ascii())
+            .then(|| {
+                AhoCorasickBuilder::new()
+                    .ascii_case_insensitive(true)
+                    .build([&pattern])
+                    .ok()
+            })
+            .flatten();
+        LiteralMatcher {
+            pattern,
+            folder,
+            ascii_finder,
+        }
+    }
+
+    fn find_in(&self, text: &str) -> Vec<(usize, usize)> {
+        let mut matches = Vec::new();
+        let mut start = 0;
+        while let Some(pos) = text[start..].find(&self.pattern) {
+            let abs_pos = start + pos;
+            matches.push((abs_pos, abs_pos + self.pattern.len()));
+            // Step one character forward so overlapping matches are found.
+            match text[abs_pos..].chars().next() {
+                Some(c) => start = abs_pos + c.len_utf8(),
+                None => break,
+            }
+        }
+        matches
+    }
+}
+
+impl Matcher for LiteralMatcher {
+    fn find_matches(&self, line: &str) -> Vec<(usize, usize)> {
+        if self.folder.is_identity() {
+            return self.find_in(line);
+        }
+        if !self.folder.unicode && line.is_ascii() {
+            // A lowercased ASCII line can't contain a non-ASCII pattern.
+            return match &self.ascii_finder {
+                Some(finder) => finder
+                    .find_overlapping_iter(line)
+                    .map(|m| (m.start(), m.end()))
+                    .collect(),
+                None => Vec::new(),
+            };
+        }
+        let folded = self.folder.fold_line(line);
+        self.find_in(&folded.text)
+            .into_iter()
+            .map(|(start, end)| folded.original_span(start, end))
+            .collect()
+    }
+
+    fn matches_file(&self, contents: &str) -> bool {
+        // A folded pattern can match text that differs from it byte for byte.
+        !self.folder.is_identity()
+            || memchr::memmem::find(contents.as_bytes(), self.pattern.as_bytes()).is_some()
+    }
+}
+
+/// Matches many literals in a single automaton pass per line.
+pub struct MultiLiteralMatcher {
+    automaton: AhoCorasick,
+    folder: Option<Folder>,
+}
+
+impl MultiLiteralMatcher {
+    pub fn new(patterns: &[String], folder: Folder) -> Result<Self, Box<dyn Error>> {
+        // Without --unicode the automaton's own ASCII case folding is enough
+        // and lines don't need to be copied.
+        if !folder.unicode {
+            let automaton = AhoCorasickBuilder::new()
+                .ascii_case_insensitive(folder.ignore_case)
+                .match_kind(MatchKind::LeftmostLongest)
+                .build(patterns)?;
+            return Ok(MultiLiteralMatcher {
+                automaton,
+                folder: None,
+            });
+        }
+
+        let folded: Vec<String> = patterns.iter().map(|p| folder.fold_str(p)).collect();
+        let automaton = AhoCorasickBuilder::new()
+            .match_kind(MatchKind::LeftmostLongest)
+            .build(&folded)?;
+        Ok(MultiLiteralMatcher {
+            automaton,
+            folder: Some(folder),
+        })
+    }
+}
+
+impl Matcher for MultiLiteralMatcher {
+    fn find_matches(&self, line: &str) -> Vec<(usize, usize)> {
+        match &self.folder {
+            None => self
+                .automaton
+                .find_iter(line)
+                .map(|m| (m.start(), m.end()))
+                .collect(),
+            Some(folder) => {
+                let folded = folder.fold_line(line);
+                self.automaton
+                    .find_iter(&folded.text)
+                    .map(|m| folded.original_span(m.start(), m.end()))
+                    .collect()
+            }
+        }
+    }
+}
+
+/// Settings applied when compiling regex-based matchers.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct RegexOptions {
+    /// Match letters regardless of case.
+    pub case_insensitive: bool,
+    /// Upper bound on the compiled program size, in bytes.
+    pub size_limit: Option<usize>,
+    /// Upper bound on the lazy DFA cache, in bytes.
+    pub dfa_size_limit: Option<usize>,
+}
+
+/// Literals that every match of a regex starts with one of, so a file
+/// containing none of them can be skipped without running the regex.
+pub struct Prefilter {
+    automaton: AhoCorasick,
+}
+
+impl Prefilter {
+    /// Returns `None` when the regex has no usable literals, e.g. `\w+`.
+    pub fn new(pattern: &str, case_insensitive: bool) -> Option<Self> {
+        let hir = regex_syntax::ParserBuilder::new()
+            .case_insensitive(case_insensitive)
+            .build()
+            .parse(pattern)
+            .ok()?;
+        let mut prefixes = Extractor::new().extract(&hir);
+        prefixes.optimize_for_prefix_by_preference();
+        let literals = prefixes.literals()?;
+        if literals.is_empty() || literals.iter().any(|literal| literal.is_empty()) {
+            return None;
+        }
+        let automaton = AhoCorasick::new(literals.iter().map(|literal| literal.as_bytes())).ok()?;
+        Some(Prefilter { automaton })
+    }
+
+    pub fn is_match(&self, haystack: &[u8]) -> bool {
+        self.automaton.is_match(haystack)
+    }
+}
+
+/// Regular expressions using the `regex` crate.
+pub struct RegexMatcher {
+    regex: Regex,
+    prefilter: Option<Prefilter>,
+}
+
+impl RegexMatcher {
+    pub fn new(pattern: &str, options: &RegexOptions) -> Result<Self, Box<dyn Error>> {
+        let mut builder = RegexBuilder::new(pattern);
+        builder.case_insensitive(options.case_insensitive);
+        if let Some(limit) = options.size_limit {
+            builder.size_limit(limit);
+        }
+        if let Some(limit) = options.dfa_size_limit {
+            builder.dfa_size_limit(limit);
+        }
+        Ok(RegexMatcher {
+            regex: builder.build()?,
+            prefilter: Prefilter::new(pattern, options.case_insensitive),
+        })
+    }
+}
+
+impl Matcher for RegexMatcher {
+    fn find_matches(&self, line: &str) -> Vec<(usize, usize)> {
+        self.regex
+            .find_iter(line)
+            .map(|m| (m.start(), m.end()))
+            .collect()
+    }
+
+    fn matches_file(&self, contents: &str) -> bool {
+        self.prefilter
+            .as_ref()
+            .is_none_or(|prefilter| prefilter.is_match(contents.as_bytes()))
+    }
+
+    fn expand(&self, line: &str, start: usize, replacement: &str) -> Option<String> {
+        let captures = self.regex.captures_at(line, start)?;
+        let mut expanded = String::new();
+        captures.expand(replacement, &mut expanded);
+        Some(expanded)
+    }
+}
+
+/// Regular expressions with lookaround and backreferences via `fancy-regex`.
+#[cfg(feature = "fancy-regex")]
+pub struct FancyRegexMatcher {
+    regex: fancy_regex::Regex,
+}
+
+#[cfg(feature = "fancy-regex")]
+impl FancyRegexMatcher {
+    pub fn new(pattern: &str, options: &RegexOptions) -> Result<Self, Box<dyn Error>> {
+        let mut builder = fancy_regex::RegexBuilder::new(pattern);
+        builder.case_insensitive(options.case_insensitive);
+        if let Some(limit) = options.size_limit {
+            builder.delegate_size_limit(limit);
+        }
+        if let Some(limit) = options.dfa_size_limit {
+            builder.delegate_dfa_size_limit(limit);
+        }
+        Ok(FancyRegexMatcher {
+            regex: builder.build()?,
+        })
+    }
+}
+
+#[cfg(feature = "fancy-regex")]
+impl Matcher for FancyRegexMatcher {
+    fn find_matches(&self, line: &str) -> Vec<(usize, usize)> {
+        // A match that exceeds the backtracking limit ends the scan of this
+        // line; everything found up to that point is still reported.
+        self.regex
+            .find_iter(line)
+            .map_while(Result::ok)
+            .map(|m| (m.start(), m.end()))
+            .collect()
+    }
+
+    fn try_find_matches(&self, line: &str) -> Result<Vec<(usize, usize)>, SearchError> {
+        self.regex
+            .find_iter(line)
+            .map(|found| {
+                let m = found.map_err(|e| {
+                    SearchError::new("BacktrackLimit", format!("backtrack limit exceeded: {}", e))
+                })?;
+                Ok((m.start(), m.end()))
+            })
+            .collect()
+    }
+
+    fn expand(&self, line: &str, start: usize, replacement: &str) -> Option<String> {
+        let captures = self.regex.captures_from_pos(line, start).ok()??;
+        let mut expanded = String::new();
+        captures.expand(replacement, &mut expanded);
+        Some(expanded)
+    }
+}
+
+/// Whether `c` can be part of an identifier in most languages.
+fn is_identifier_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+/// Matches a name only where it forms a complete identifier token.
+///
+/// Underscores and letters both continue a token, so `id` matches `id`,
+/// `self.id` and `Foo::id` but not `valid`, `user_id` or `userId`. A
+/// qualified name such as `Vec::new` matches with either `::` or `.` between
+/// its segments.
+pub struct IdentifierMatcher {
+    regex: Regex,
+}
+
+impl IdentifierMatcher {
+    pub fn new(name: &str, options: &RegexOptions) -> Result<Self, Box<dyn Error>> {
+        let segments: Vec<&str> = name
+            .split("::")
+            .flat_map(|segment| segment.split('.'))
+            .map(str::trim)
+            .collect();
+        if segments
+            .iter()
+            .any(|segment| segment.is_empty() || !segment.chars().all(is_identifier_char))
+        {
+            return Err(Box::new(SearchError::new(
+                "InvalidPattern",
+                format!("{:?} is not an identifier", name),
+            )));
+        }
+
+        let pattern = segments
+            .iter()
+            .map(|segment| regex::escape(segment))
+            .collect::<Vec<_>>()
+            .join(r"\s*(?:::|\.)\s*");
+        Ok(IdentifierMatcher {
+            regex: RegexMatcher::new(&pattern, options)?.regex,
+        })
+    }
+}
+
+impl Matcher for IdentifierMatcher {
+    fn find_matches(&self, line: &str) -> Vec<(usize, usize)> {
+        let mut matches = Vec::new();
+        let mut position = 0;
+        while let Some(m) = self.regex.find_at(line, position) {
+            let before = line[..m.start()].chars().next_back();
+            let after = line[m.end()..].chars().next();
+            if before.is_some_and(is_identifier_char) || after.is_some_and(is_identifier_char) {
+                // Retry from the next character, which may start a token.
+                position = m.start() + line[m.start()..].chars().next().map_or(1, char::len_utf8);
+            } else {
+                matches.push((m.start(), m.end()));
+                position = m.end();
+            }
+            if position > line.len() {
+                break;
+            }
+        }
+        matches
+    }
+}
+
+/// Flags tokens whose characters are spread evenly enough to look random,
+/// as API keys and other secrets tend to be.
+///
+/// A token is a run of letters, digits and `+/=_-`; it is reported when it
+/// is at least `min_length` characters long and its Shannon entropy is at
+/// least `threshold` bits per character.
+pub struct EntropyMatcher {
+    threshold: f64,
+    min_length: usize,
+}
+
+impl EntropyMatcher {
+    pub fn new(threshold: f64, min_length: usize) -> Self {
+        EntropyMatcher {
+            threshold,
+            min_length,
+        }
+    }
+}
+
+fn is_token_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || matches!(c, '+' | '/' | '=' | '_' | '-')
+}
+
+/// Shannon entropy of `token` in bits per character.
+pub fn shannon_entropy(token: &str) -> f64 {
+    let mut counts = [0usize; 256];
+    for byte in token.bytes() {
+        counts[byte as usize] += 1;
+    }
+    let length = token.len() as f64;
+    counts
+        .iter()
+        .filter(|&&count| count > 0)
+        .map(|&count| {
+            let p = count as f64 / length;
+            -p * p.log2()
+        })
+        .sum()
+}
+
+impl Matcher for EntropyMatcher {
+    fn find_matches(&self, line: &str) -> Vec<(usize, usize)> {
+        let mut matches = Vec::new();
+        let mut start = None;
+        for (offset, c) in line
+            .char_indices()
+            .chain(std::iter::once((line.len(), ' ')))
+        {
+            match (start, is_token_char(c)) {
+                (None, true) => start = Some(offset),
+                (Some(token_start), false) => {
+                    let token = &line[token_start..offset];
+                    if token.len() >= self.min_length && shannon_entropy(token) >= self.threshold {
+                        matches.push((token_start, offset));
+                    }
+                    start = None;
+                }
+                _ => {}
+            }
+        }
+        matches
+    }
+}
+
+/// File-level conditions: every `contains` pattern must occur somewhere in a
+/// file and no `not_contains` pattern may. All of them are matched together
+/// in a single pass over the contents.
+pub struct FilePredicates {
+    set: regex::bytes::RegexSet,
+    /// The first `required` patterns in the set are the `contains` ones.
+    required: usize,
+}
+
+impl FilePredicates {
+    /// Patterns are taken literally unless `regex` is set.
+    pub fn new(
+        contains: &[String],
+        not_contains: &[String],
+        regex: bool,
+        options: &RegexOptions,
+    ) -> Result<Self, Box<dyn Error>> {
+        let patterns = contains.iter().chain(not_contains).map(|pattern| {
+            if regex {
+                pattern.clone()
+            } else {
+                regex::escape(pattern)
+            }
+        });
+        let mut builder = regex::bytes::RegexSetBuilder::new(patterns);
+        builder.case_insensitive(options.case_insensitive);
+        if let Some(limit) = options.size_limit {
+            builder.size_limit(limit);
+        }
+        if let Some(limit) = options.dfa_size_limit {
+            builder.dfa_size_limit(limit);
+        }
+        Ok(FilePredicates {
+            set: builder.build()?,
+            required: contains.len(),
+        })
+    }
+
+    pub fn matches(&self, contents: &[u8]) -> bool {
+        let found = self.set.matches(contents);
+        (0..self.set.len()).all(|index| found.matched(index) == (index < self.required))
+    }
+}
+
+/// Restricts another matcher to hits that cover the whole line.
+pub struct WholeLineMatcher {
+    inner: Box<dyn Matcher>,
+}
+
+impl WholeLineMatcher {
+    pub fn new(inner: Box<dyn Matcher>) -> Self {
+        WholeLineMatcher { inner }
+    }
+}
+
+impl Matcher for WholeLineMatcher {
+    fn find_matches(&self, line: &str) -> Vec<(usize, usize)> {
+        self.inner
+            .find_matches(line)
+            .into_iter()
+            .filter(|&(start, end)| start == 0 && end == line.len())
+            .take(1)
+            .collect()
+    }
+}
+
+/// Approximate matching: the pattern's characters must appear in order in
+/// the line, but not necessarily next to each other.
+///
+/// Each candidate alignment is scored, favouring consecutive characters and
+/// characters at word boundaries while penalizing gaps, and the best one is
+/// reported.
+pub struct FuzzyMatcher {
+    pattern: Vec<char>,
+    ignore_case: bool,
+}
+
+const FUZZY_MATCH_SCORE: i64 = 16;
+const FUZZY_CONSECUTIVE_BONUS: i64 = 8;
+const FUZZY_BOUNDARY_BONUS: i64 = 8;
+const FUZZY_GAP_PENALTY: i64 = 1;
+
+impl FuzzyMatcher {
+    pub fn new(pattern: &str, ignore_case: bool) -> Self {
+        FuzzyMatcher {
+            pattern: pattern.chars().map(|c| fold_char(c, ignore_case)).collect(),
+            ignore_case,
+        }
+    }
+
+    /// Finds the best scoring alignment, returning its score and the byte
+    /// ranges of the matched characters.
+    fn best_match(&self, line: &str) -> Option<(i64, Vec<(usize, usize)>)> {
+        if self.pattern.is_empty() {
+            return None;
+        }
+        let chars: Vec<(usize, char)> = line.char_indices().collect();
+        let mut best: Option<(i64, Vec<usize>)> = None;
+
+        for start in 0..chars.len() {
+            if fold_char(chars[start].1, self.ignore_case) != self.pattern[0] {
+                continue;
+            }
+            let mut positions = vec![start];
+            let mut next = start + 1;
+            for &wanted in &self.pattern[1..] {
+                match (next..chars.len())
+                    .find(|&i| fold_char(chars[i].1, self.ignore_case) == wanted)
+                {
+                    Some(found) => {
+                        positions.push(found);
+                        next = found + 1;
+                    }
+                    None => break,
+                }
+            }
+            if positions.len() < self.pattern.len() {
+                // No later start can complete the pattern either.
+                break;
+            }
+            let score = score_positions(&chars, &positions);
+            if best
+                .as_ref()
+                .is_none_or(|(best_score, _)| score > *best_score)
+            {
+                best = Some((score, positions));
+            }
+        }
+
+        best.map(|(score, positions)| {
+            let mut spans: Vec<(usize, usize)> = Vec::new();
+            for i in positions {
+                let (offset, c) = chars[i];
+                let end = offset + c.len_utf8();
+                match spans.last_mut() {
+                    Some(last) if last.1 == offset => last.1 = end,
+                    _ => spans.push((offset, end)),
+                }
+            }
+            (score, spans)
+        })
+    }
+}
+
+/// Finds substrings within `max_errors` character insertions, deletions or
+/// substitutions of the pattern (Sellers' algorithm).
+///
+/// Overlapping candidates are collapsed to the one needing the fewest edits,
+/// so an exact occurrence isn't also reported one character short or long.
+pub struct ApproximateMatcher {
+    pattern: Vec<char>,
+    max_errors: usize,
+    ignore_case: bool,
+}
+
+impl ApproximateMatcher {
+    pub fn new(
+        pattern: &str,
+        max_errors: usize,
+        ignore_case: bool,
+    ) -> Result<Self, Box<dyn Error>> {
+        let pattern: Vec<char> = pattern.chars().map(|c| fold_char(c, ignore_case)).collect();
+        if max_errors >= pattern.len() {
+            return Err(Box::new(SearchError::new(
+                "InvalidPattern",
+                format!(
+                    "--max-errors must be less than the pattern length ({})",
+                    pattern.len()
+                ),
+            )));
+        }
+        Ok(ApproximateMatcher {
+            pattern,
+            max_errors,
+            ignore_case,
+        })
+    }
+
+    /// Returns the (start, end, edits) of each non-overlapping match.
+    fn find_with_distances(&self, line: &str) -> Vec<(usize, usize, usize)> {
+        // previous[i] is the (edits, start offset) of the cheapest alignment
+        // of the first `i` pattern characters ending at the current offset.
+        let mut previous: Vec<(usize, usize)> = (0..=self.pattern.len()).map(|i| (i, 0)).collect();
+        let mut current = previous.clone();
+        let mut found: Vec<(usize, usize, usize)> = Vec::new();
+
+        for (offset, c) in line.char_indices() {
+            let end = offset + c.len_utf8();
+            let c = fold_char(c, self.ignore_case);
+            current[0] = (0, end);
+            for i in 1..=self.pattern.len() {
+                let substitute = (
+                    previous[i - 1].0 + usize::from(self.pattern[i - 1] != c),
+                    previous[i - 1].1,
+                );
+                let insert = (previous[i].0 + 1, previous[i].1);
+                let delete = (current[i - 1].0 + 1, current[i - 1].1);
+                // Fewest edits first, then the shortest match.
+                current[i] = [substitute, insert, delete]
+                    .into_iter()
+                    .min_by_key(|&(edits, start)| (edits, std::cmp::Reverse(start)))
+                    .unwrap();
+            }
+
+            let (edits, start) = current[self.pattern.len()];
+            if edits <= self.max_errors {
+                match found.last_mut() {
+                    Some(last) if start < last.1 => {
+                        if edits < last.2 {
+                            *last = (start, end, edits);
+                        }
+                    }
+                    _ => found.push((start, end, edits)),
+                }
+            }
+            std::mem::swap(&mut previous, &mut current);
+        }
+        found
+    }
+}
+
+impl Matcher for ApproximateMatcher {
+    fn find_matches(&self, line: &str) -> Vec<(usize, usize)> {
+        self.find_with_distances(line)
+            .into_iter()
+            .map(|(start, end, _)| (start, end))
+            .collect()
+    }
+
+    fn edit_distance(&self, line: &str) -> Option<usize> {
+        self.find_with_distances(line)
+            .into_iter()
+            .map(|(_, _, edits)| edits)
+            .min()
+    }
+}
+
+fn fold_char(c: char, ignore_case: bool) -> char {
+    if ignore_case {
+        c.to_lowercase().next().unwrap_or(c)
+    } else {
+        c
+    }
+}
+
+fn score_positions(chars: &[(usize, char)], positions: &[usize]) -> i64 {
+    let mut score = 0;
+    for (n, &i) in positions.iter().enumerate() {
+        score += FUZZY_MATCH_SCORE;
+        if n > 0 {
+            let gap = (i - positions[n - 1] - 1) as i64;
+            if gap == 0 {
+                score += FUZZY_CONSECUTIVE_BONUS;
+            } else {
+                score -= gap * FUZZY_GAP_PENALTY;
+            }
+        }
+        let at_boundary = match i.checked_sub(1).map(|prev| chars[prev].1) {
+            None => true,
+            Some(prev) => {
+                !prev.is_alphanumeric() || (prev.is_lowercase() && chars[i].1.is_uppercase())
+            }
+        };
+        if at_boundary {
+            score += FUZZY_BOUNDARY_BONUS;
+        }
+    }
+    score
+}
+
+impl Matcher for FuzzyMatcher {
+    fn find_matches(&self, line: &str) -> Vec<(usize, usize)> {
+        self.best_match(line)
+            .map(|(_, spans)| spans)
+            .unwrap_or_default()
+    }
+
+    fn score(&self, line: &str) -> Option<i64> {
+        self.best_match(line).map(|(score, _)| score)
+    }
+}
+
+/// Joins several regex patterns into a single alternation.
+pub fn combine_patterns(patterns: &[String]) -> String {
+    if patterns.len() == 1 {
+        return patterns[0].clone();
+    }
+    patterns
+        .iter()
+        .map(|p| format!("(?:{})", p))
+        .collect::<Vec<_>>()
+        .join("|")
+}
+
+/// Translates a shell-style wildcard pattern into an equivalent regex.
+///
+/// `*` matches any run of characters and `?` matches a single character;
+/// everything else, including regex metacharacters, is matched literally.
+/// A backslash escapes the following character.
+pub fn wildcard_to_regex(pattern: &str) -> String {
+    let mut translated = String::with_capacity(pattern.len() * 2);
+    let mut chars = pattern.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '*' => translated.push_str(".*"),
+            '?' => translated.push('.'),
+            '\\' => {
+                if let Some(escaped) = chars.next() {
+                    translated.push_str(&regex::escape(&escaped.to_string()));
+                } else {
+                    translated.push_str(r"\\");
+                }
+            }
+            _ => translated.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    translated
+}
+
+/// Parses a `--bytes` pattern: either hex digit pairs with optional
+/// whitespace (`DE AD BE EF`), or text containing `\xHH` escapes.
+pub fn parse_byte_pattern(pattern: &str) -> Result<Vec<u8>, Box<dyn Error>> {
+    let invalid = |message: String| -> Box<dyn Error> {
+        Box::new(SearchError::new("InvalidPattern", message))
+    };
+    let hex_pair = |digits: &str| {
+        // `from_str_radix` alone would take a sign, as in `+1`.
+        if digits.len() != 2 || !digits.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return Err(invalid(format!("invalid hex byte {:?}", digits)));
+        }
+        Ok(u8::from_str_radix(digits, 16)?)
+    };
+
+    let mut bytes = Vec::new();
+    if pattern.contains("\\x") {
+        let mut rest = pattern;
+        while let Some(pos) = rest.find("\\x") {
+            bytes.extend_from_slice(&rest.as_bytes()[..pos]);
+            let digits = rest.get(pos + 2..pos + 4).unwrap_or(&rest[pos..]);
+            bytes.push(hex_pair(digits)?);
+            rest = &rest[pos + 4..];
+        }
+        bytes.extend_from_slice(rest.as_bytes());
+    } else {
+        let digits: String = pattern.chars().filter(|c| !c.is_whitespace()).collect();
+        if !digits.len().is_multiple_of(2) {
+            return Err(invalid(format!(
+                "odd number of hex digits in {:?}",
+                pattern
+            )));
+        }
+        for i in (0..digits.len()).step_by(2) {
+            bytes.push(hex_pair(digits.get(i..i + 2).unwrap_or(&digits[i..]))?);
+        }
+    }
+
+    if bytes.is_empty() {
+        return Err(invalid("empty byte pattern".to_string()));
+    }
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_combine_patterns() {
+        let patterns = vec!["a+".to_string(), "b|c".to_string()];
+        assert_eq!(combine_patterns(&patterns), "(?:a+)|(?:b|c)");
+    }
+
+    #[test]
+    fn test_identifier_matcher() -> Result<(), Box<dyn Error>> {
+        let matcher = IdentifierMatcher::new("id", &RegexOptions::default())?;
+        assert_eq!(
+            matcher.find_matches("valid(video, user_id, userId, self.id, Foo::id)"),
+            vec![(35, 37), (44, 46)]
+        );
+
+        let matcher = IdentifierMatcher::new("Vec::new", &RegexOptions::default())?;
+        assert_eq!(matcher.find_matches("std::vec::Vec::new()"), vec![(10, 18)]);
+        assert_eq!(matcher.find_matches("Vec.new MyVec::new"), vec![(0, 7)]);
+
+        assert!(IdentifierMatcher::new("foo bar", &RegexOptions::default()).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_approximate_matcher() -> Result<(), Box<dyn Error>> {
+        let matcher = ApproximateMatcher::new("receive", 1, false)?;
+        let line = "receve receive recv";
+        assert_eq!(matcher.find_matches(line), vec![(0, 6), (7, 14)]);
+        assert_eq!(matcher.edit_distance(line), Some(0));
+        assert_eq!(matcher.edit_distance("recieve"), None);
+        assert_eq!(matcher.edit_distance("receeve"), Some(1));
+        assert_eq!(matcher.edit_distance("recv"), None);
+
+        assert!(ApproximateMatcher::new("ab", 2, false).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_file_predicates() -> Result<(), Box<dyn Error>> {
+        let predicates = FilePredicates::new(
+            &["Mutex".to_string()],
+            &["lock()".to_string()],
+            false,
+            &RegexOptions::default(),
+        )?;
+        assert!(predicates.matches(b"use std::sync::Mutex;\n"));
+        assert!(!predicates.matches(b"use std::sync::Mutex;\nm.lock().unwrap();\n"));
+        assert!(!predicates.matches(b"fn main() {}\n"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_entropy_matcher() {
+        assert_eq!(shannon_entropy("aaaa"), 0.0);
+        assert_eq!(shannon_entropy("abcd"), 2.0);
+
+        let matcher = EntropyMatcher::new(4.0, 20);
+        let line = "key = \"wJalrXUtnFEMI/K7MDENG/bPxRfiCYEXAMPLEKEY\" # aaaaaaaaaaaaaaaaaaaaaaaa";
+        assert_eq!(matcher.find_matches(line), vec![(7, 47)]);
+        assert!(matcher.find_matches("short_but_random_Xq9").is_empty());
+    }
+
+    #[test]
+    fn test_literal_matcher_ignore_case() {
+        let folder = Folder {
+            ignore_case: true,
+            unicode: false,
+        };
+        let matcher = LiteralMatcher::new("AnA", folder);
+        assert_eq!(
+            matcher.find_matches("banana BANANA"),
+            vec![(1, 4), (3, 6), (8, 11), (10, 13)]
+        );
+        // Non-ASCII lines still go through per-character lowercasing.
+        assert_eq!(matcher.find_matches("Ünana"), vec![(3, 6)]);
+
+        let matcher = LiteralMatcher::new("ÉTÉ", folder);
+        assert_eq!(matcher.find_matches("un été"), vec![(3, 8)]);
+        assert!(matcher.find_matches("plain ascii ete").is_empty());
+    }
+
+    #[test]
+    fn test_literal_matcher_multibyte() {
+        let folder = Folder {
+            ignore_case: true,
+            unicode: false,
+        };
+        let matcher = LiteralMatcher::new("é", folder);
+        assert_eq!(matcher.find_matches("ÉÉ"), vec![(0, 2), (2, 4)]);
+    }
+
+    #[test]
+    fn test_unicode_multi_literal() -> Result<(), Box<dyn Error>> {
+        let folder = Folder {
+            ignore_case: true,
+            unicode: true,
+        };
+        let patterns = vec!["strasse".to_string(), "istanbul".to_string()];
+        let matcher = MultiLiteralMatcher::new(&patterns, folder)?;
+        assert_eq!(
+            matcher.find_matches("STRAẞE, İstanbul"),
+            vec![(0, 8), (10, 19)]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_byte_pattern() -> Result<(), Box<dyn Error>> {
+        assert_eq!(
+            parse_byte_pattern("DE AD be ef")?,
+            vec![0xde, 0xad, 0xbe, 0xef]
+        );
+        assert_eq!(parse_byte_pattern(r"PK\x03\x04")?, b"PK\x03\x04".to_vec());
+        assert!(parse_byte_pattern("ABC").is_err());
+        assert!(parse_byte_pattern("zz").is_err());
+        assert!(parse_byte_pattern(r"\x4").is_err());
+        assert!(parse_byte_pattern(r"\x+1").is_err());
+        assert!(parse_byte_pattern("+1+2").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_regex_prefilter() -> Result<(), Box<dyn Error>> {
+        let prefilter = Prefilter::new(r"(?:foo|bar)\d+", false).unwrap();
+        assert!(prefilter.is_match(b"x = bar42"));
+        assert!(!prefilter.is_match(b"nothing to see"));
+        assert!(Prefilter::new(r"\w+\d", false).is_none());
+
+        let matcher = RegexMatcher::new(r"err(or)?: \w+", &RegexOptions::default())?;
+        assert!(matcher.matches_file("ok\nerror: disk full\n"));
+        assert!(!matcher.matches_file("all good\n"));
+
+        let options = RegexOptions {
+            case_insensitive: true,
+            ..Default::default()
+        };
+        let matcher = RegexMatcher::new(r"todo", &options)?;
+        assert!(matcher.matches_file("// TODO: later"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_regex_size_limit() {
+        let options = RegexOptions {
+            size_limit: Some(64 * 1024),
+            ..RegexOptions::default()
+        };
+        assert!(RegexMatcher::new(r"\w{100}", &options).is_err());
+        assert!(RegexMatcher::new(r"[a-z]+", &options).is_ok());
+    }
+
+    #[test]
+    fn test_wildcard_to_regex() -> Result<(), Box<dyn Error>> {
+        assert_eq!(wildcard_to_regex("foo*.rs"), r"foo.*\.rs");
+        assert_eq!(wildcard_to_regex(r"a?b\*"), r"a.b\*");
+
+        let matcher = RegexMatcher::new(&wildcard_to_regex("get_*(?)"), &RegexOptions::default())?;
+        assert_eq!(matcher.find_matches("x = get_value(1);"), vec![(4, 16)]);
+        assert!(matcher.find_matches("get_value()").is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_fuzzy_matcher() {
+        let matcher = FuzzyMatcher::new("fsrch", true);
+        assert_eq!(
+            matcher.find_matches("fn FileSearch()"),
+            vec![(3, 4), (7, 8), (10, 13)]
+        );
+        assert!(matcher.find_matches("search").is_empty());
+
+        let tight = matcher.score("file_search").unwrap();
+        let loose = matcher.score("for some rich people").unwrap();
+        assert!(tight > loose);
+    }
+
+    #[test]
+    fn test_regex_case_insensitive() -> Result<(), Box<dyn Error>> {
+        let options = RegexOptions {
+            case_insensitive: true,
+            ..RegexOptions::default()
+        };
+        let matcher = RegexMatcher::new(r"err(or)?\b", &options)?;
+        assert_eq!(
+            matcher.find_matches("ERROR and Err"),
+            vec![(0, 5), (10, 13)]
+        );
+        Ok(())
+    }
+
+    #[cfg(feature = "fancy-regex")]
+    #[test]
+    fn test_fancy_regex_case_insensitive() -> Result<(), Box<dyn Error>> {
+        let options = RegexOptions {
+            case_insensitive: true,
+            ..RegexOptions::default()
+        };
+        let matcher = FancyRegexMatcher::new(r"(?<!//)todo", &options)?;
+        assert_eq!(matcher.find_matches("TODO //Todo"), vec![(0, 4)]);
+        Ok(())
+    }
+
+    #[cfg(feature = "fancy-regex")]
+    #[test]
+    fn test_fancy_regex_backtrack_limit() -> Result<(), Box<dyn Error>> {
+        // The lookahead keeps the nested repetition in fancy-regex's
+        // backtracking VM rather than handing it to `regex`.
+        let matcher = FancyRegexMatcher::new(r"((?=a)a+)+b", &RegexOptions::default())?;
+        let line = "a".repeat(30);
+        assert!(matcher.find_matches(&line).is_empty());
+        assert!(matcher.try_find_matches(&line).is_err());
+        assert_eq!(matcher.try_find_matches("aab")?, vec![(0, 3)]);
+        Ok(())
+    }
+
+    #[cfg(feature = "fancy-regex")]
+    #[test]
+    fn test_fancy_regex_lookbehind() -> Result<(), Box<dyn Error>> {
+        let matcher = FancyRegexMatcher::new(r"(?<!//)\bTODO\b", &RegexOptions::default())?;
+        assert_eq!(matcher.find_matches("TODO: fix"), vec![(0, 4)]);
+        assert!(matcher.find_matches("//TODO: fix").is_empty());
+        Ok(())
+    }
+}
@@ -0,0 +1,470 @@
+//! `--mcp`: a Model Context Protocol server over stdio, so coding agents can
+//! search a project through tools instead of running the binary:
+//!
+//! - `search_content` finds lines matching a pattern.
+//! - `list_files` lists the files a search would look at.
+//! - `search_and_replace_preview` shows what a replacement would change,
+//!   as a patch, without writing anything.
+//!
+//! Every path a tool is given is taken from the root directory, and must
+//! stay inside it; see [`Sandbox`].
+
+use crate::diff;
+use crate::options::{SearchOptions, SearcherBuilder};
+use crate::result::SearchResult;
+use crate::rpc::{error, parse_request, response, send, INVALID_PARAMS, METHOD_NOT_FOUND};
+use crate::sandbox::Sandbox;
+use crate::searcher::Searcher;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fs;
+use std::io::{self, BufRead, Write};
+use std::path::Path;
+use std::sync::Mutex;
+
+/// Protocol versions this server speaks, newest last.
+const PROTOCOL_VERSIONS: [&str; 3] = ["2024-11-05", "2025-03-26", "2025-06-18"];
+
+/// Results a tool returns when `max_results` isn't given.
+const DEFAULT_MAX_RESULTS: usize = 200;
+
+/// The arguments of every tool; each uses only some of them.
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct Arguments {
+    pattern: Option<String>,
+    replacement: Option<String>,
+    path: Option<String>,
+    regex: bool,
+    ignore_case: bool,
+    hidden: bool,
+    globs: Vec<String>,
+    types: Vec<String>,
+    max_results: Option<usize>,
+}
+
+impl Arguments {
+    fn max_results(&self) -> usize {
+        self.max_results.unwrap_or(DEFAULT_MAX_RESULTS)
+    }
+
+    fn options(&self) -> SearchOptions {
+        SearchOptions {
+            patterns: self.pattern.iter().cloned().collect(),
+            regex: self.regex,
+            ignore_case: self.ignore_case,
+            hidden: self.hidden,
+            globs: self.globs.clone(),
+            types: self.types.clone(),
+            // No limit: the walk is parallel, so which results a limit kept
+            // would change from call to call. They're cut down once sorted.
+            replace: self.replacement.clone(),
+            ..SearchOptions::default()
+        }
+    }
+}
+
+fn tools() -> Value {
+    let path = json!({
+        "type": "string",
+        "description": "File or directory to search, relative to the project root (default: the root)",
+    });
+    let pattern = json!({
+        "type": "string",
+        "description": "Text to find, or a regex with regex: true",
+    });
+    let flag = |description: &str| json!({"type": "boolean", "description": description});
+    let globs = json!({
+        "type": "array",
+        "items": {"type": "string"},
+        "description": "Only search files matching these globs, e.g. \"*.rs\"; a leading ! excludes",
+    });
+    let types = json!({
+        "type": "array",
+        "items": {"type": "string"},
+        "description": "Only search files of these types, e.g. \"rust\" or \"py\"",
+    });
+    let max_results = json!({
+        "type": "integer",
+        "minimum": 1,
+        "description": format!(
+            "Return at most this many results (default {}), the first by path and line",
+            DEFAULT_MAX_RESULTS
+        ),
+    });
+    json!([
+        {
+            "name": "search_content",
+            "description": "Search file contents for a pattern, respecting .gitignore. Returns each matching line with its path, line number and match positions.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "pattern": pattern,
+                    "path": path,
+                    "regex": flag("Treat the pattern as a regex"),
+                    "ignore_case": flag("Match case-insensitively"),
+                    "hidden": flag("Search hidden files and directories"),
+                    "globs": globs,
+                    "types": types,
+                    "max_results": max_results,
+                },
+                "required": ["pattern"],
+            },
+        },
+        {
+            "name": "list_files",
+            "description": "List the files below a directory that a search would look at, respecting .gitignore.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "path": path,
+                    "hidden": flag("Include hidden files and directories"),
+                    "globs": globs,
+                    "types": types,
+                    "max_results": max_results,
+                },
+            },
+        },
+        {
+            "name": "search_and_replace_preview",
+            "description": "Show how replacing a pattern would change each file, as a unified diff, without changing anything. Regex replacements can use $1 or ${name}.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "pattern": pattern,
+                    "replacement": {"type": "string", "description": "Text to put in place of each match"},
+                    "path": path,
+                    "regex": flag("Treat the pattern as a regex"),
+                    "ignore_case": flag("Match case-insensitively"),
+                    "hidden": flag("Search hidden files and directories"),
+                    "globs": globs,
+                    "types": types,
+                    "max_results": max_results,
+                },
+                "required": ["pattern", "replacement"],
+            },
+        },
+    ])
+}
+
+impl Sandbox {
+    fn call(&self, tool: &str, arguments: Arguments) -> Result<(String, Value), Box<dyn Error>> {
+        let path = self.resolve(arguments.path.as_deref())?;
+        if tool != "list_files" && arguments.pattern.is_none() {
+            return Err("pattern is required".into());
+        }
+        match tool {
+            "search_content" => {
+                let (results, truncated) = self.search(&arguments, &path)?;
+                let text = results
+                    .iter()
+                    .map(|r| {
+                        format!(
+                            "{}:{}: {}\n",
+                            self.relative(&r.file_path),
+                            r.line_number,
+                            r.line
+                        )
+                    })
+                    .collect();
+                Ok((
+                    text,
+                    json!({
+                        "matches": results.iter().map(|r| self.to_json(r)).collect::<Vec<_>>(),
+                        "truncated": truncated,
+                    }),
+                ))
+            }
+            "list_files" => {
+                let mut args = arguments.options().to_args();
+                args.files = true;
+                let mut files: Vec<String> = Searcher::new(&args)?
+                    .search(&path)?
+                    .iter()
+                    .map(|r| self.relative(&r.file_path))
+                    .collect();
+                files.sort();
+                let truncated = files.len() > arguments.max_results();
+                files.truncate(arguments.max_results());
+                let text = files.iter().map(|file| format!("{}\n", file)).collect();
+                Ok((
+                    text,
+                    json!({
+                        "files": files,
+                        "truncated": truncated,
+                    }),
+                ))
+            }
+            "search_and_replace_preview" => {
+                if arguments.replacement.is_none() {
+                    return Err("replacement is required".into());
+                }
+                let (results, truncated) = self.search(&arguments, &path)?;
+                let patch = self.patch(&results)?;
+                Ok((
+                    patch.clone(),
+                    json!({
+                        "changes": results.iter().map(|r| self.to_json(r)).collect::<Vec<_>>(),
+                        "patch": patch,
+                        "truncated": truncated,
+                    }),
+                ))
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    /// Searches `path` with `arguments`, in file and line order, and says
+    /// whether there were more results than `max_results`.
+    fn search(
+        &self,
+        arguments: &Arguments,
+        path: &Path,
+    ) -> Result<(Vec<SearchResult>, bool), Box<dyn Error>> {
+        let searcher = SearcherBuilder::from_options(arguments.options()).build()?;
+        let mut results = searcher.search(path)?;
+        results.sort_by(|a, b| (&a.file_path, a.line_number).cmp(&(&b.file_path, b.line_number)));
+        let truncated = results.len() > arguments.max_results();
+        results.truncate(arguments.max_results());
+        Ok((results, truncated))
+    }
+
+    /// A patch making the replacements in `results`, which `git apply` can
+    /// apply from the root.
+    fn patch(&self, results: &[SearchResult]) -> io::Result<String> {
+        let mut files: BTreeMap<&Path, Vec<&SearchResult>> = BTreeMap::new();
+        for result in results {
+            files.entry(&result.file_path).or_default().push(result);
+        }
+        let mut patch = String::new();
+        for (file, results) in files {
+            let contents = String::from_utf8_lossy(&fs::read(file)?).into_owned();
+            let changes: Vec<diff::Change> = results
+                .iter()
+                .filter_map(|result| {
+                    Some(diff::Change {
+                        line_number: result.line_number,
+                        searched: result.line.len(),
+                        replaced: &result.replacement.as_ref()?.line,
+                    })
+                })
+                .collect();
+            let hunks = diff::hunks(&contents, &changes, diff::CONTEXT);
+            if !hunks.is_empty() {
+                patch.push_str(&diff::patch(Path::new(&self.relative(file)), &hunks));
+            }
+        }
+        Ok(patch)
+    }
+}
+
+fn initialize(params: &Value) -> Value {
+    let requested = params["protocolVersion"].as_str();
+    let version = PROTOCOL_VERSIONS
+        .into_iter()
+        .find(|&version| Some(version) == requested)
+        .unwrap_or(PROTOCOL_VERSIONS[PROTOCOL_VERSIONS.len() - 1]);
+    json!({
+        "protocolVersion": version,
+        "capabilities": {"tools": {}},
+        "serverInfo": {"name": "rusty-scout", "version": env!("CARGO_PKG_VERSION")},
+    })
+}
+
+/// Serves requests read from `input` until it ends, with tools confined to
+/// the directory `root`.
+pub(crate) fn serve<R: BufRead, W: Write>(input: R, output: W, root: &Path) -> io::Result<()> {
+    let sandbox = Sandbox::new(root)?;
+    let output = Mutex::new(output);
+    for line in input.lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let request = match parse_request(&line) {
+            Ok(request) => request,
+            Err(message) => {
+                send(&output, &message)?;
+                continue;
+            }
+        };
+        // Notifications, such as `notifications/initialized`, need nothing
+        // back.
+        let Some(id) = request.id else {
+            continue;
+        };
+        let message = match request.method.as_str() {
+            "initialize" => response(&id, initialize(&request.params)),
+            "ping" => response(&id, json!({})),
+            "tools/list" => response(&id, json!({"tools": tools()})),
+            "tools/call" => {
+                let tool = request.params["name"].as_str().unwrap_or_default();
+                let arguments = match request.params.get("arguments") {
+                    None | Some(Value::Null) => Ok(Arguments::default()),
+                    Some(arguments) => Arguments::deserialize(arguments),
+                };
+                match arguments {
+                    _ if !["search_content", "list_files", "search_and_replace_preview"]
+                        .contains(&tool) =>
+                    {
+                        error(&id, INVALID_PARAMS, format!("unknown tool {:?}", tool))
+                    }
+                    Err(e) => error(&id, INVALID_PARAMS, e),
+                    // A tool that fails is reported as its result, so the
+                    // agent sees why.
+                    Ok(arguments) => response(
+                        &id,
+                        match sandbox.call(tool, arguments) {
+                            Ok((text, structured)) => json!({
+                                "content": [{"type": "text", "text": text}],
+                                "structuredContent": structured,
+                                "isError": false,
+                            }),
+                            Err(e) => json!({
+                                "content": [{"type": "text", "text": e.to_string()}],
+                                "isError": true,
+                            }),
+                        },
+                    ),
+                }
+            }
+            method => error(
+                &id,
+                METHOD_NOT_FOUND,
+                format!("unknown method {:?}", method),
+            ),
+        };
+        send(&output, &message)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn call(root: &Path, tool: &str, arguments: Value) -> io::Result<Value> {
+        let requests = [
+            json!({"jsonrpc": "2.0", "id": 0, "method": "initialize", "params": {"protocolVersion": "2025-03-26"}}),
+            json!({"jsonrpc": "2.0", "method": "notifications/initialized"}),
+            json!({"jsonrpc": "2.0", "id": 1, "method": "tools/call", "params": {"name": tool, "arguments": arguments}}),
+        ];
+        let input: String = requests.iter().map(|r| format!("{}\n", r)).collect();
+        let mut output = Vec::new();
+        serve(input.as_bytes(), &mut output, root)?;
+        let replies: Vec<Value> = output
+            .split(|&b| b == b'\n')
+            .filter(|line| !line.is_empty())
+            .map(|line| serde_json::from_slice(line).unwrap())
+            .collect();
+        assert_eq!(replies.len(), 2);
+        assert_eq!(replies[0]["result"]["protocolVersion"], "2025-03-26");
+        Ok(replies[1].clone())
+    }
+
+    #[test]
+    fn test_tools() -> Result<(), Box<dyn Error>> {
+        let dir = tempdir()?;
+        let root = dir.path().join("project");
+        fs::create_dir_all(root.join("src"))?;
+        fs::write(root.join("src/a.rs"), "let old = 1;\nold += 1;\n")?;
+        fs::write(root.join("notes.txt"), "old notes\n")?;
+        fs::write(dir.path().join("secret.txt"), "old secret\n")?;
+
+        let reply = call(
+            &root,
+            "search_content",
+            json!({"pattern": "old", "types": ["rust"]}),
+        )?;
+        let result = &reply["result"];
+        assert_eq!(result["isError"], false);
+        assert_eq!(
+            result["structuredContent"]["matches"][0]["path"],
+            "src/a.rs"
+        );
+        assert_eq!(result["structuredContent"]["matches"][1]["line_number"], 2);
+        assert_eq!(
+            result["content"][0]["text"],
+            "src/a.rs:1: let old = 1;\nsrc/a.rs:2: old += 1;\n"
+        );
+
+        let reply = call(&root, "list_files", json!({"path": "."}))?;
+        assert_eq!(
+            reply["result"]["structuredContent"]["files"],
+            json!(["notes.txt", "src/a.rs"])
+        );
+        let reply = call(&root, "list_files", json!({"max_results": 2}))?;
+        assert_eq!(reply["result"]["structuredContent"]["truncated"], false);
+        let reply = call(&root, "list_files", json!({"max_results": 1}))?;
+        let result = &reply["result"]["structuredContent"];
+        assert_eq!(result["files"], json!(["notes.txt"]));
+        assert_eq!(result["truncated"], true);
+        // Cut-down results are the first by path, every time.
+        for _ in 0..5 {
+            let reply = call(
+                &root,
+                "search_content",
+                json!({"pattern": "old", "max_results": 2}),
+            )?;
+            let result = &reply["result"]["structuredContent"];
+            assert_eq!(result["truncated"], true);
+            assert_eq!(result["matches"][0]["path"], "notes.txt");
+            assert_eq!(result["matches"][1]["path"], "src/a.rs");
+            assert_eq!(result["matches"][1]["line_number"], 1);
+        }
+
+        let reply = call(
+            &root,
+            "search_and_replace_preview",
+            json!({
+                "pattern": "old", "replacement": "new", "path": "src", "max_results": 1,
+            }),
+        )?;
+        let result = &reply["result"]["structuredContent"];
+        assert_eq!(result["truncated"], true);
+        assert_eq!(result["changes"].as_array().unwrap().len(), 1);
+        assert_eq!(result["changes"][0]["replacement"], "let new = 1;");
+        assert!(result["patch"]
+            .as_str()
+            .unwrap()
+            .starts_with("--- a/src/a.rs\n+++ b/src/a.rs\n"));
+        assert_eq!(
+            fs::read_to_string(root.join("src/a.rs"))?,
+            "let old = 1;\nold += 1;\n"
+        );
+
+        // Nothing outside the root, even through a symlink.
+        #[cfg(unix)]
+        std::os::unix::fs::symlink(dir.path().join("secret.txt"), root.join("link.txt"))?;
+        for path in ["..", "../secret.txt", "/", "link.txt"] {
+            let reply = call(
+                &root,
+                "search_content",
+                json!({"pattern": "old", "path": path}),
+            )?;
+            assert_eq!(reply["result"]["isError"], true, "{}", path);
+        }
+        let reply = call(&root, "search_content", json!({"pattern": "old"}))?;
+        assert_eq!(
+            reply["result"]["structuredContent"]["matches"]
+                .as_array()
+                .unwrap()
+                .len(),
+            3
+        );
+
+        let reply = call(&root, "write_files", json!({}))?;
+        assert_eq!(reply["error"]["code"], INVALID_PARAMS);
+        let reply = call(
+            &root,
+            "search_content",
+            json!({"pattern": "old", "colour": true}),
+        )?;
+        assert_eq!(reply["error"]["code"], INVALID_PARAMS);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,128 @@
+/// How many leading bytes of a file are looked at to guess its type.
+pub const SNIFF_LEN: usize = 8192;
+
+/// Signatures of common binary formats, checked in order.
+const MAGIC: &[(&[u8], &str)] = &[
+    (b"\x89PNG\r\n\x1a\n", "image/png"),
+    (b"\xff\xd8\xff", "image/jpeg"),
+    (b"GIF87a", "image/gif"),
+    (b"GIF89a", "image/gif"),
+    (b"%PDF-", "application/pdf"),
+    (b"PK\x03\x04", "application/zip"),
+    (b"\x1f\x8b", "application/gzip"),
+    (b"BZh", "application/x-bzip2"),
+    (b"\xfd7zXZ\x00", "application/x-xz"),
+    (b"\x28\xb5\x2f\xfd", "application/zstd"),
+    (b"\x7fELF", "application/x-executable"),
+    (b"\0asm", "application/wasm"),
+    (b"SQLite format 3\0", "application/vnd.sqlite3"),
+];
+
+/// Interpreters named in a `#!` line, and the type of script they run.
+const INTERPRETERS: &[(&str, &str)] = &[
+    ("bash", "text/x-shellscript"),
+    ("node", "text/javascript"),
+    ("perl", "text/x-perl"),
+    ("python", "text/x-python"),
+    ("ruby", "text/x-ruby"),
+    ("sh", "text/x-shellscript"),
+    ("zsh", "text/x-shellscript"),
+];
+
+/// Guesses a MIME type from the start of a file's contents, without looking
+/// at its name.
+pub fn sniff(bytes: &[u8]) -> &'static str {
+    let head = &bytes[..bytes.len().min(SNIFF_LEN)];
+    if let Some((_, mime)) = MAGIC.iter().find(|(magic, _)| head.starts_with(magic)) {
+        return mime;
+    }
+    if memchr::memchr(0, head).is_some() {
+        return "application/octet-stream";
+    }
+    // A cut-off multi-byte character at the end of the sample is still text.
+    let text = match std::str::from_utf8(head) {
+        Ok(text) => text,
+        Err(e) if e.error_len().is_none() => std::str::from_utf8(&head[..e.valid_up_to()]).unwrap(),
+        Err(_) => return "application/octet-stream",
+    };
+
+    if let Some(shebang) = text.strip_prefix("#!") {
+        let line = shebang.lines().next().unwrap_or("");
+        let mut words = line.split_whitespace();
+        let mut program = words.next().unwrap_or("").rsplit('/').next().unwrap_or("");
+        if program == "env" {
+            program = words.find(|word| !word.starts_with('-')).unwrap_or("");
+        }
+        let program = program.trim_end_matches(|c: char| c.is_ascii_digit() || c == '.');
+        return INTERPRETERS
+            .iter()
+            .find(|(name, _)| *name == program)
+            .map_or("text/plain", |(_, mime)| mime);
+    }
+
+    let start = text.trim_start_matches('\u{feff}').trim_start();
+    let lower: String = start
+        .chars()
+        .take(64)
+        .collect::<String>()
+        .to_ascii_lowercase();
+    if lower.starts_with("<!doctype html") || lower.starts_with("<html") {
+        "text/html"
+    } else if lower.starts_with("<?xml") {
+        if start.contains("<svg") {
+            "image/svg+xml"
+        } else {
+            "text/xml"
+        }
+    } else if lower.starts_with("<svg") {
+        "image/svg+xml"
+    } else {
+        "text/plain"
+    }
+}
+
+/// Whether `mime` matches a pattern such as `text/plain`, `text/*` or `*/*`.
+pub fn matches(pattern: &str, mime: &str) -> bool {
+    let (kind, subtype) = mime.split_once('/').unwrap_or((mime, ""));
+    match pattern.split_once('/') {
+        Some((pattern_kind, pattern_subtype)) => {
+            (pattern_kind == "*" || pattern_kind.eq_ignore_ascii_case(kind))
+                && (pattern_subtype == "*" || pattern_subtype.eq_ignore_ascii_case(subtype))
+        }
+        None => pattern == "*" || pattern.eq_ignore_ascii_case(kind),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sniff() {
+        assert_eq!(sniff(b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR"), "image/png");
+        assert_eq!(sniff(b"\x7fELF\x02\x01\x01\0"), "application/x-executable");
+        assert_eq!(sniff(b"plain\0data"), "application/octet-stream");
+        assert_eq!(sniff(b"just some notes\n"), "text/plain");
+        assert_eq!(
+            sniff(b"#!/usr/bin/env python3\nprint(1)\n"),
+            "text/x-python"
+        );
+        assert_eq!(sniff(b"#!/bin/bash\necho hi\n"), "text/x-shellscript");
+        assert_eq!(sniff(b"  <!DOCTYPE html>\n<html>"), "text/html");
+        assert_eq!(sniff(b"<?xml version=\"1.0\"?>\n<svg>"), "image/svg+xml");
+        assert_eq!(sniff("\u{e9}".repeat(40).as_bytes()), "text/plain");
+        assert_eq!(sniff("caf\u{e9}".as_bytes()), "text/plain");
+        assert_eq!(sniff(&"\u{e9}".as_bytes()[..1]), "text/plain");
+        assert_eq!(sniff(b"\xff\xfe\xfa"), "application/octet-stream");
+    }
+
+    #[test]
+    fn test_matches() {
+        assert!(matches("text/*", "text/x-python"));
+        assert!(matches("TEXT/Plain", "text/plain"));
+        assert!(matches("image", "image/png"));
+        assert!(matches("*/*", "application/pdf"));
+        assert!(!matches("text/*", "application/octet-stream"));
+        assert!(!matches("text/html", "text/plain"));
+    }
+}
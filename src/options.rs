@@ -0,0 +1,323 @@
+use crate::cli::Args;
+use crate::searcher::Searcher;
+use crate::SearchError;
+use serde::Deserialize;
+use std::error::Error;
+
+/// What to search for and where, set in code rather than parsed from a
+/// command line. Anything not set here keeps the command line's default.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SearchOptions {
+    /// Patterns to search for; a line matches if any of them does.
+    pub patterns: Vec<String>,
+    /// Treat the patterns as regexes rather than literal text.
+    pub regex: bool,
+    pub ignore_case: bool,
+    /// Only report lines that a pattern matches in their entirety.
+    pub line_regexp: bool,
+    /// Match approximately and rank results by score.
+    pub fuzzy: bool,
+    /// Allow up to this many character edits per match.
+    pub max_errors: Option<usize>,
+    /// Search hidden files and directories.
+    pub hidden: bool,
+    /// Don't respect ignore files such as .gitignore.
+    pub no_ignore: bool,
+    pub follow_links: bool,
+    pub one_file_system: bool,
+    /// Directories below the search root to descend into.
+    pub max_depth: Option<usize>,
+    /// Only search files matching these globs, or skip those starting
+    /// with `!`.
+    pub globs: Vec<String>,
+    /// Skip files and directories matching these globs.
+    pub exclude: Vec<String>,
+    /// Don't descend into directories with these names.
+    pub exclude_dirs: Vec<String>,
+    /// Only search files of these types, e.g. `rust`.
+    pub types: Vec<String>,
+    /// Skip files larger than this many bytes.
+    pub max_filesize: Option<usize>,
+    /// Stop searching a file after this many matching lines.
+    pub max_count: Option<usize>,
+    /// Stop the whole search after this many results.
+    pub limit: Option<usize>,
+    /// Threads to search with, or 0 for one per core.
+    pub threads: usize,
+    /// Search every file as text, even ones that look binary.
+    pub text: bool,
+    /// Search binary files, reporting only whether each one matched.
+    pub binary: bool,
+    /// Report each matching line with its matches replaced by this text.
+    pub replace: Option<String>,
+}
+
+impl SearchOptions {
+    /// Checks for options that can't be used together.
+    pub fn validate(&self) -> Result<(), SearchError> {
+        let conflicts = [
+            (self.fuzzy && self.regex, "fuzzy", "regex"),
+            (
+                self.max_errors.is_some() && self.regex,
+                "max_errors",
+                "regex",
+            ),
+            (
+                self.max_errors.is_some() && self.fuzzy,
+                "max_errors",
+                "fuzzy",
+            ),
+            (self.line_regexp && self.fuzzy, "line_regexp", "fuzzy"),
+            (self.binary && self.text, "binary", "text"),
+        ];
+        if let Some((_, a, b)) = conflicts.iter().find(|(conflict, _, _)| *conflict) {
+            return Err(SearchError::new(
+                "InvalidOptions",
+                format!("{} can't be combined with {}", a, b),
+            ));
+        }
+        if self.patterns.is_empty() {
+            return Err(SearchError::new("InvalidPattern", "no patterns given"));
+        }
+        Ok(())
+    }
+
+    pub(crate) fn to_args(&self) -> Args {
+        let mut args = Args::defaults();
+        args.pattern = self.patterns.clone();
+        args.regex = self.regex;
+        args.ignore_case = self.ignore_case;
+        args.line_regexp = self.line_regexp;
+        args.fuzzy = self.fuzzy;
+        args.max_errors = self.max_errors;
+        args.hidden = self.hidden;
+        args.no_ignore = self.no_ignore;
+        args.follow = self.follow_links;
+        args.one_file_system = self.one_file_system;
+        args.max_depth = self.max_depth;
+        args.globs = self.globs.clone();
+        args.exclude = self.exclude.clone();
+        args.exclude_dir = self.exclude_dirs.clone();
+        args.types = self.types.clone();
+        args.max_filesize = self.max_filesize;
+        args.max_count = self.max_count;
+        args.limit = self.limit;
+        args.threads = self.threads;
+        args.text = self.text;
+        args.binary = self.binary;
+        args.replace = self.replace.clone();
+        args
+    }
+}
+
+/// Builds a [`Searcher`] one option at a time:
+///
+/// ```no_run
+/// use rusty_scout::Searcher;
+/// use std::path::Path;
+///
+/// let searcher = Searcher::builder()
+///     .pattern(r"fn \w+")
+///     .regex(true)
+///     .file_type("rust")
+///     .build()?;
+/// let results = searcher.search(Path::new("src"))?;
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct SearcherBuilder {
+    options: SearchOptions,
+}
+
+impl SearcherBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn from_options(options: SearchOptions) -> Self {
+        SearcherBuilder { options }
+    }
+
+    /// Adds a pattern to search for.
+    pub fn pattern(mut self, pattern: impl Into<String>) -> Self {
+        self.options.patterns.push(pattern.into());
+        self
+    }
+
+    pub fn regex(mut self, yes: bool) -> Self {
+        self.options.regex = yes;
+        self
+    }
+
+    pub fn ignore_case(mut self, yes: bool) -> Self {
+        self.options.ignore_case = yes;
+        self
+    }
+
+    pub fn line_regexp(mut self, yes: bool) -> Self {
+        self.options.line_regexp = yes;
+        self
+    }
+
+    pub fn fuzzy(mut self, yes: bool) -> Self {
+        self.options.fuzzy = yes;
+        self
+    }
+
+    pub fn max_errors(mut self, errors: usize) -> Self {
+        self.options.max_errors = Some(errors);
+        self
+    }
+
+    pub fn hidden(mut self, yes: bool) -> Self {
+        self.options.hidden = yes;
+        self
+    }
+
+    pub fn no_ignore(mut self, yes: bool) -> Self {
+        self.options.no_ignore = yes;
+        self
+    }
+
+    pub fn follow_links(mut self, yes: bool) -> Self {
+        self.options.follow_links = yes;
+        self
+    }
+
+    pub fn one_file_system(mut self, yes: bool) -> Self {
+        self.options.one_file_system = yes;
+        self
+    }
+
+    pub fn max_depth(mut self, depth: usize) -> Self {
+        self.options.max_depth = Some(depth);
+        self
+    }
+
+    /// Adds a glob files must match, or must not if it starts with `!`.
+    pub fn glob(mut self, glob: impl Into<String>) -> Self {
+        self.options.globs.push(glob.into());
+        self
+    }
+
+    pub fn exclude(mut self, glob: impl Into<String>) -> Self {
+        self.options.exclude.push(glob.into());
+        self
+    }
+
+    pub fn exclude_dir(mut self, name: impl Into<String>) -> Self {
+        self.options.exclude_dirs.push(name.into());
+        self
+    }
+
+    /// Adds a file type to search, e.g. `rust` or `py`.
+    pub fn file_type(mut self, name: impl Into<String>) -> Self {
+        self.options.types.push(name.into());
+        self
+    }
+
+    pub fn max_filesize(mut self, bytes: usize) -> Self {
+        self.options.max_filesize = Some(bytes);
+        self
+    }
+
+    pub fn max_count(mut self, count: usize) -> Self {
+        self.options.max_count = Some(count);
+        self
+    }
+
+    pub fn limit(mut self, limit: usize) -> Self {
+        self.options.limit = Some(limit);
+        self
+    }
+
+    pub fn threads(mut self, threads: usize) -> Self {
+        self.options.threads = threads;
+        self
+    }
+
+    pub fn text(mut self, yes: bool) -> Self {
+        self.options.text = yes;
+        self
+    }
+
+    pub fn binary(mut self, yes: bool) -> Self {
+        self.options.binary = yes;
+        self
+    }
+
+    pub fn replace(mut self, replacement: impl Into<String>) -> Self {
+        self.options.replace = Some(replacement.into());
+        self
+    }
+
+    /// Checks the options and builds the searcher, failing if they conflict
+    /// or a pattern, glob or type is invalid.
+    pub fn build(self) -> Result<Searcher, Box<dyn Error>> {
+        self.options.validate()?;
+        Searcher::new(&self.options.to_args())
+    }
+}
+
+impl Searcher {
+    pub fn builder() -> SearcherBuilder {
+        SearcherBuilder::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_builder() -> Result<(), Box<dyn Error>> {
+        let dir = tempdir()?;
+        fs::write(dir.path().join("a.rs"), "fn main() {}\nFN OTHER\n")?;
+        fs::write(dir.path().join("b.txt"), "fn main\n")?;
+
+        let searcher = Searcher::builder()
+            .pattern("fn")
+            .ignore_case(true)
+            .file_type("rust")
+            .build()?;
+        let results = searcher.search(dir.path())?;
+        assert_eq!(results.len(), 2);
+        assert!(results.iter().all(|r| r.file_path.ends_with("a.rs")));
+
+        // Options left unset keep the command line's defaults.
+        let args = SearchOptions::default().to_args();
+        assert_eq!(args.mmap_threshold, 4 << 20);
+        assert_eq!(args.extensions, "*");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_validate() {
+        let error = |builder: SearcherBuilder| builder.build().err().unwrap().to_string();
+        assert_eq!(
+            error(Searcher::builder().pattern("a").fuzzy(true).regex(true)),
+            "InvalidOptions: fuzzy can't be combined with regex"
+        );
+        assert_eq!(
+            error(SearcherBuilder::from_options(SearchOptions {
+                binary: true,
+                text: true,
+                ..SearchOptions::default()
+            })),
+            "InvalidOptions: binary can't be combined with text"
+        );
+        assert_eq!(
+            error(Searcher::builder()),
+            "InvalidPattern: no patterns given"
+        );
+        assert!(Searcher::builder()
+            .pattern("(")
+            .regex(true)
+            .build()
+            .is_err());
+    }
+}
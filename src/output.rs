@@ -0,0 +1,285 @@
+use crate::cli::Args;
+use crate::result::SearchResult;
+use crate::rules::Severity;
+use crate::searcher::Searcher;
+use crate::sink::{feed, JsonWriter, Sink};
+use colored::*;
+use std::io;
+use std::path::PathBuf;
+use std::sync::atomic::Ordering;
+
+/// How results are printed as text; copied to the thread that prints them
+/// as they arrive.
+#[derive(Clone, Copy)]
+pub(crate) struct Printer {
+    /// Print only the (highlighted) path, for path matching and listings.
+    pub(crate) paths_only: bool,
+    /// Print `--replace` previews as a diff of each file.
+    pub(crate) diff: bool,
+}
+
+impl Printer {
+    pub(crate) fn new(args: &Args) -> Self {
+        Printer {
+            paths_only: args.match_paths || args.files_with_matches || args.files,
+            diff: args.replace.is_some() && !args.write && !args.json,
+        }
+    }
+
+    /// The lines printed for `result`, each ending in a newline.
+    pub(crate) fn render(&self, result: &SearchResult) -> String {
+        if self.paths_only {
+            return format!("{}\n", highlight(&result.line, &result.matches));
+        }
+        if self.diff && !result.binary {
+            // The rest of the file's results are part of its diff.
+            return result
+                .diff
+                .as_ref()
+                .map(|diff| format!("{}\n", diff))
+                .unwrap_or_default();
+        }
+        if result.binary {
+            return format!(
+                "{}: {}\n",
+                result.file_path.display().to_string().blue(),
+                "binary file matches".dimmed()
+            );
+        }
+        let location = match result.byte_offset {
+            Some(offset) => format!("{:#x}", offset).yellow(),
+            None => result.line_number.to_string().yellow(),
+        };
+        let annotation = if let Some(rule) = &result.rule {
+            let severity = match rule.severity {
+                Severity::Error => rule.severity.to_string().red(),
+                Severity::Warning => rule.severity.to_string().yellow(),
+                Severity::Info => rule.severity.to_string().blue(),
+            };
+            let description = rule
+                .description
+                .as_ref()
+                .map(|d| format!(" - {}", d))
+                .unwrap_or_default();
+            format!(" [{}] {}{}", severity, rule.name.bold(), description)
+        } else if let Some(score) = result.score {
+            format!(" {}", format!("[score {}]", score).dimmed())
+        } else if let Some(distance) = result.edit_distance {
+            format!(" {}", format!("[{} edits]", distance).dimmed())
+        } else if !result.metavariables.is_empty() {
+            let bindings = result
+                .metavariables
+                .iter()
+                .map(|(name, text)| format!("${}={}", name, text))
+                .collect::<Vec<_>>()
+                .join(" ");
+            format!(" {}", bindings.dimmed())
+        } else if let Some(kind) = &result.syntax_node {
+            format!(" {}", format!("({})", kind).dimmed())
+        } else {
+            String::new()
+        };
+        let mut text = format!(
+            "{}:{}:{}\n",
+            result.file_path.display().to_string().blue(),
+            location,
+            annotation
+        );
+
+        let (line, matches) = match &result.replacement {
+            Some(replacement) => (&replacement.line, &replacement.matches),
+            None => (&result.line, &result.matches),
+        };
+        text += &format!("    {}\n", highlight(line, matches));
+        if let Some(related) = &result.related {
+            text += &format!(
+                "  {} {}: {}\n",
+                "~".dimmed(),
+                related.line_number.to_string().yellow(),
+                highlight(&related.line, &related.matches)
+            );
+        }
+        text
+    }
+
+    pub(crate) fn print(&self, result: &SearchResult) {
+        print!("{}", self.render(result));
+    }
+}
+
+impl Sink for Printer {
+    fn on_match(&mut self, result: &SearchResult) -> io::Result<()> {
+        self.print(result);
+        Ok(())
+    }
+}
+
+/// Prints how many results each file had, as `--count` does, once the next
+/// file's results start or the search is over.
+#[derive(Default)]
+pub(crate) struct FileCounts {
+    file: Option<(PathBuf, usize)>,
+}
+
+impl FileCounts {
+    fn print(&mut self) {
+        if let Some((path, count)) = self.file.take() {
+            println!("{}:{}", path.display().to_string().blue(), count);
+        }
+    }
+}
+
+impl Sink for FileCounts {
+    fn on_match(&mut self, result: &SearchResult) -> io::Result<()> {
+        match &mut self.file {
+            Some((path, count)) if *path == result.file_path => *count += 1,
+            _ => {
+                self.print();
+                self.file = Some((result.file_path.clone(), 1));
+            }
+        }
+        Ok(())
+    }
+
+    fn on_finish(&mut self, _found: usize) -> io::Result<()> {
+        self.print();
+        Ok(())
+    }
+}
+
+/// Wraps each match in `line` in terminal color codes, when output is
+/// colored.
+pub(crate) fn highlight(line: &str, matches: &[(usize, usize)]) -> String {
+    let mut line = line.to_string();
+    if !colored::control::SHOULD_COLORIZE.should_colorize() {
+        return line;
+    }
+    // Highlight matches in reverse order to not invalidate positions
+    for (start, end) in matches.iter().rev() {
+        line.insert_str(*end, "\x1b[0m");
+        line.insert_str(*start, "\x1b[31m");
+    }
+    line
+}
+
+impl Searcher {
+    /// Where results are printed: as JSON with `--json`, as counts with
+    /// `--count`, or else as text.
+    pub(crate) fn sink(&self) -> Box<dyn Sink + Send> {
+        if self.json {
+            Box::new(JsonWriter::new(io::stdout()))
+        } else if self.count {
+            Box::new(FileCounts::default())
+        } else {
+            Box::new(self.printer)
+        }
+    }
+
+    pub(crate) fn display_results(&self) -> io::Result<()> {
+        if !self.results_only {
+            self.display_summary(self.found());
+        }
+
+        // Streamed results were printed as they arrived.
+        if !self.streamed {
+            let mut results = self.results.lock().unwrap();
+            if results.iter().any(|r| r.score.is_some()) {
+                results.sort_by_key(|r| std::cmp::Reverse(r.score));
+            }
+            let mut sink = self.sink();
+            feed(&mut sink, &results)?;
+            sink.on_finish(results.len())?;
+        }
+        Ok(())
+    }
+
+    pub(crate) fn display_summary(&self, count: usize) {
+        print_found(count, self.streamed, self.limit);
+        if count == 0 {
+            return;
+        }
+        if self.write {
+            println!(
+                "{} {} files rewritten\n",
+                "✓".green(),
+                self.rewritten.load(Ordering::Relaxed).to_string().green()
+            );
+        }
+        if let Some(patch) = &self.diff_output {
+            println!(
+                "{} Changes to {} files written to {}\n",
+                "✓".green(),
+                self.patches.lock().unwrap().len().to_string().green(),
+                patch.display()
+            );
+        }
+    }
+
+    pub(crate) fn display_interrupted(&self) {
+        if !self.was_interrupted() {
+            return;
+        }
+        let message = "Search interrupted; these are the results found so far.";
+        // Keep JSON, counts and file lists parseable.
+        if self.results_only {
+            eprintln!("{}", message);
+        } else {
+            println!("{}", message.yellow());
+        }
+    }
+
+    pub(crate) fn display_skipped(&self) {
+        print_skipped(&self.skipped.lock().unwrap(), self.results_only);
+    }
+}
+
+/// Says how many results there were, and whether `limit` cut the search
+/// short. `streamed` results were printed before this rather than after.
+pub(crate) fn print_found(count: usize, streamed: bool, limit: Option<usize>) {
+    if count == 0 {
+        println!("{}", "No matches found.".yellow());
+        return;
+    }
+
+    if streamed {
+        println!(
+            "\n{} {} matches found",
+            "✓".green(),
+            count.to_string().green()
+        );
+    } else {
+        println!(
+            "\n{} {} matches found:\n",
+            "✓".green(),
+            count.to_string().green()
+        );
+    }
+    if limit.is_some_and(|limit| count >= limit) {
+        println!(
+            "{}\n",
+            "Result limit reached; search stopped early.".yellow()
+        );
+    }
+}
+
+pub(crate) fn print_skipped(skipped: &[(PathBuf, String)], results_only: bool) {
+    if skipped.is_empty() {
+        return;
+    }
+    if results_only {
+        // Keep stdout parseable.
+        for (path, reason) in skipped {
+            eprintln!("skipped {}: {}", path.display(), reason);
+        }
+        return;
+    }
+
+    println!(
+        "\n{} {} files skipped:",
+        "!".yellow(),
+        skipped.len().to_string().yellow()
+    );
+    for (path, reason) in skipped {
+        println!("    {} ({})", path.display().to_string().blue(), reason);
+    }
+}
@@ -0,0 +1,107 @@
+use std::str::FromStr;
+
+/// A user given to `--owner`, by name or numeric ID.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Owner(pub u32);
+
+impl FromStr for Owner {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Ok(uid) = s.parse() {
+            return Ok(Owner(uid));
+        }
+        let passwd = std::fs::read_to_string("/etc/passwd")
+            .map_err(|e| format!("can't look up user {:?}: {}", s, e))?;
+        uid_from_passwd(&passwd, s)
+            .map(Owner)
+            .ok_or_else(|| format!("unknown user {:?}", s))
+    }
+}
+
+/// Finds a user's ID in the contents of an `/etc/passwd` file.
+fn uid_from_passwd(passwd: &str, name: &str) -> Option<u32> {
+    passwd.lines().find_map(|line| {
+        let mut fields = line.split(':');
+        if fields.next()? != name {
+            return None;
+        }
+        fields.nth(1)?.parse().ok()
+    })
+}
+
+/// A permission test for `--perm`, written as with `find -perm`: `644`
+/// matches that mode exactly, `-022` files with all those bits set, and
+/// `/111` files with any of them set.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PermFilter {
+    Exact(u32),
+    All(u32),
+    Any(u32),
+}
+
+impl PermFilter {
+    pub fn matches(&self, mode: u32) -> bool {
+        let mode = mode & 0o7777;
+        match *self {
+            PermFilter::Exact(bits) => mode == bits,
+            PermFilter::All(bits) => mode & bits == bits,
+            PermFilter::Any(bits) => bits == 0 || mode & bits != 0,
+        }
+    }
+}
+
+impl FromStr for PermFilter {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (make, digits): (fn(u32) -> PermFilter, &str) =
+            if let Some(digits) = s.strip_prefix('-') {
+                (PermFilter::All, digits)
+            } else if let Some(digits) = s.strip_prefix('/') {
+                (PermFilter::Any, digits)
+            } else {
+                (PermFilter::Exact, s)
+            };
+        match u32::from_str_radix(digits, 8) {
+            Ok(bits) if bits <= 0o7777 && !digits.starts_with('+') => Ok(make(bits)),
+            _ => Err(format!(
+                "expected an octal mode such as 644, -002 or /111, got {:?}",
+                s
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_owner() {
+        let passwd = "root:x:0:0:root:/root:/bin/bash\ndeploy:x:1001:1001::/home/deploy:/bin/sh\n";
+        assert_eq!(uid_from_passwd(passwd, "deploy"), Some(1001));
+        assert_eq!(uid_from_passwd(passwd, "root"), Some(0));
+        assert_eq!(uid_from_passwd(passwd, "nobody"), None);
+        assert_eq!("1001".parse::<Owner>(), Ok(Owner(1001)));
+    }
+
+    #[test]
+    fn test_perm_filter() {
+        assert_eq!("644".parse(), Ok(PermFilter::Exact(0o644)));
+        assert!(PermFilter::Exact(0o644).matches(0o100644));
+        assert!(!PermFilter::Exact(0o644).matches(0o100664));
+
+        let world_writable: PermFilter = "-002".parse().unwrap();
+        assert!(world_writable.matches(0o777));
+        assert!(!world_writable.matches(0o755));
+
+        let executable: PermFilter = "/111".parse().unwrap();
+        assert!(executable.matches(0o744));
+        assert!(!executable.matches(0o644));
+
+        assert!("rwx".parse::<PermFilter>().is_err());
+        assert!("99".parse::<PermFilter>().is_err());
+        assert!("17777".parse::<PermFilter>().is_err());
+    }
+}
@@ -0,0 +1,336 @@
+use crate::matcher::Matcher;
+use crate::SearchError;
+use clap::ValueEnum;
+use std::error::Error;
+
+/// Where a boolean query is evaluated.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum QueryScope {
+    /// Every reported line must satisfy the query on its own
+    Line,
+    /// The whole file must satisfy the query; lines containing its terms are reported
+    File,
+}
+
+/// Predicate tree produced by parsing a query such as `unsafe AND NOT test`.
+///
+/// Terms are stored by index into `QueryMatcher::terms`.
+#[derive(Debug, PartialEq)]
+enum Query {
+    Term(usize),
+    Not(Box<Query>),
+    And(Box<Query>, Box<Query>),
+    Or(Box<Query>, Box<Query>),
+}
+
+#[derive(Debug, PartialEq)]
+enum Token {
+    Word(String),
+    And,
+    Or,
+    Not,
+    Open,
+    Close,
+}
+
+fn invalid_query(message: impl Into<String>) -> Box<dyn Error> {
+    Box::new(SearchError::new("InvalidQuery", message))
+}
+
+fn tokenize(input: &str) -> Result<Vec<Token>, Box<dyn Error>> {
+    let mut tokens = Vec::new();
+    let mut chars = input.chars().peekable();
+
+    while let Some(&c) = chars.peek() {
+        match c {
+            c if c.is_whitespace() => {
+                chars.next();
+            }
+            '(' => {
+                chars.next();
+                tokens.push(Token::Open);
+            }
+            ')' => {
+                chars.next();
+                tokens.push(Token::Close);
+            }
+            '"' => {
+                chars.next();
+                let mut word = String::new();
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(escaped) => word.push(escaped),
+                            None => return Err(invalid_query("unterminated quoted term")),
+                        },
+                        Some(c) => word.push(c),
+                        None => return Err(invalid_query("unterminated quoted term")),
+                    }
+                }
+                tokens.push(Token::Word(word));
+            }
+            _ => {
+                let mut word = String::new();
+                while let Some(&c) = chars.peek() {
+                    if c.is_whitespace() || c == '(' || c == ')' {
+                        break;
+                    }
+                    word.push(c);
+                    chars.next();
+                }
+                tokens.push(match word.as_str() {
+                    "AND" => Token::And,
+                    "OR" => Token::Or,
+                    "NOT" => Token::Not,
+                    _ => Token::Word(word),
+                });
+            }
+        }
+    }
+
+    Ok(tokens)
+}
+
+/// Recursive descent parser. `NOT` binds tighter than `AND`, which binds
+/// tighter than `OR`; adjacent terms are implicitly joined with `AND`.
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+    terms: Vec<String>,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn parse_or(&mut self) -> Result<Query, Box<dyn Error>> {
+        let mut left = self.parse_and()?;
+        while self.peek() == Some(&Token::Or) {
+            self.pos += 1;
+            let right = self.parse_and()?;
+            left = Query::Or(Box::new(left), Box::new(right));
+        }
+        Ok(left)
+    }
+
+    fn parse_and(&mut self) -> Result<Query, Box<dyn Error>> {
+        let mut left = self.parse_unary()?;
+        loop {
+            match self.peek() {
+                Some(Token::And) => self.pos += 1,
+                Some(Token::Word(_)) | Some(Token::Not) | Some(Token::Open) => {}
+                _ => break,
+            }
+            let right = self.parse_unary()?;
+            left = Query::And(Box::new(left), Box::new(right));
+        }
+        Ok(left)
+    }
+
+    fn parse_unary(&mut self) -> Result<Query, Box<dyn Error>> {
+        if self.peek() == Some(&Token::Not) {
+            self.pos += 1;
+            return Ok(Query::Not(Box::new(self.parse_unary()?)));
+        }
+        self.parse_primary()
+    }
+
+    fn parse_primary(&mut self) -> Result<Query, Box<dyn Error>> {
+        match self.tokens.get(self.pos) {
+            Some(Token::Word(word)) => {
+                self.terms.push(word.clone());
+                self.pos += 1;
+                Ok(Query::Term(self.terms.len() - 1))
+            }
+            Some(Token::Open) => {
+                self.pos += 1;
+                let inner = self.parse_or()?;
+                if self.peek() != Some(&Token::Close) {
+                    return Err(invalid_query("missing closing parenthesis"));
+                }
+                self.pos += 1;
+                Ok(inner)
+            }
+            Some(token) => Err(invalid_query(format!("unexpected {:?}", token))),
+            None => Err(invalid_query("unexpected end of query")),
+        }
+    }
+}
+
+fn parse(input: &str) -> Result<(Query, Vec<String>), Box<dyn Error>> {
+    let mut parser = Parser {
+        tokens: tokenize(input)?,
+        pos: 0,
+        terms: Vec::new(),
+    };
+    let query = parser.parse_or()?;
+    if parser.pos != parser.tokens.len() {
+        return Err(invalid_query(format!(
+            "unexpected {:?}",
+            parser.tokens[parser.pos]
+        )));
+    }
+    Ok((query, parser.terms))
+}
+
+/// Collects the terms that appear under an even number of `NOT`s; these are
+/// the ones worth highlighting in a matching line.
+fn collect_positive(query: &Query, negated: bool, positive: &mut Vec<usize>) {
+    match query {
+        Query::Term(index) => {
+            if !negated {
+                positive.push(*index);
+            }
+        }
+        Query::Not(inner) => collect_positive(inner, !negated, positive),
+        Query::And(left, right) | Query::Or(left, right) => {
+            collect_positive(left, negated, positive);
+            collect_positive(right, negated, positive);
+        }
+    }
+}
+
+/// Evaluates a boolean query, with each term matched by its own `Matcher`.
+pub struct QueryMatcher {
+    query: Query,
+    terms: Vec<Box<dyn Matcher>>,
+    positive: Vec<usize>,
+    scope: QueryScope,
+}
+
+impl QueryMatcher {
+    /// Parses `input` and compiles each term with `build_term`, so terms obey
+    /// the same literal/regex/case options as ordinary patterns.
+    pub fn new<F>(input: &str, scope: QueryScope, build_term: F) -> Result<Self, Box<dyn Error>>
+    where
+        F: Fn(&str) -> Result<Box<dyn Matcher>, Box<dyn Error>>,
+    {
+        let (query, words) = parse(input)?;
+        let terms = words
+            .iter()
+            .map(|word| build_term(word))
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut positive = Vec::new();
+        collect_positive(&query, false, &mut positive);
+        Ok(QueryMatcher {
+            query,
+            terms,
+            positive,
+            scope,
+        })
+    }
+
+    fn evaluate(&self, query: &Query, text: &str) -> bool {
+        match query {
+            Query::Term(index) => !self.terms[*index].find_matches(text).is_empty(),
+            Query::Not(inner) => !self.evaluate(inner, text),
+            Query::And(left, right) => self.evaluate(left, text) && self.evaluate(right, text),
+            Query::Or(left, right) => self.evaluate(left, text) || self.evaluate(right, text),
+        }
+    }
+
+    fn positive_matches(&self, line: &str) -> Vec<(usize, usize)> {
+        let mut matches: Vec<(usize, usize)> = self
+            .positive
+            .iter()
+            .flat_map(|&index| self.terms[index].find_matches(line))
+            .collect();
+        matches.sort_unstable();
+        matches.dedup();
+        matches
+    }
+}
+
+impl Matcher for QueryMatcher {
+    fn find_matches(&self, line: &str) -> Vec<(usize, usize)> {
+        match self.scope {
+            QueryScope::File => self.positive_matches(line),
+            QueryScope::Line => {
+                if !self.evaluate(&self.query, line) {
+                    return Vec::new();
+                }
+                let matches = self.positive_matches(line);
+                if matches.is_empty() {
+                    // The line satisfies a purely negative query such as
+                    // `NOT test`; report it with an empty span.
+                    vec![(0, 0)]
+                } else {
+                    matches
+                }
+            }
+        }
+    }
+
+    fn matches_file(&self, contents: &str) -> bool {
+        match self.scope {
+            QueryScope::Line => true,
+            QueryScope::File => self.evaluate(&self.query, contents),
+        }
+    }
+
+    fn needs_whole_file(&self) -> bool {
+        self.scope == QueryScope::File
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fold::Folder;
+    use crate::matcher::LiteralMatcher;
+
+    fn literal(term: &str) -> Result<Box<dyn Matcher>, Box<dyn Error>> {
+        let folder = Folder {
+            ignore_case: false,
+            unicode: false,
+        };
+        Ok(Box::new(LiteralMatcher::new(term, folder)))
+    }
+
+    #[test]
+    fn test_parse_precedence() -> Result<(), Box<dyn Error>> {
+        let (query, terms) = parse("a OR b AND NOT c")?;
+        assert_eq!(terms, vec!["a", "b", "c"]);
+        assert_eq!(
+            query,
+            Query::Or(
+                Box::new(Query::Term(0)),
+                Box::new(Query::And(
+                    Box::new(Query::Term(1)),
+                    Box::new(Query::Not(Box::new(Query::Term(2))))
+                ))
+            )
+        );
+
+        assert!(parse("(a OR b").is_err());
+        assert!(parse("a AND").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_line_query() -> Result<(), Box<dyn Error>> {
+        let matcher = QueryMatcher::new(r#"unsafe AND NOT test"#, QueryScope::Line, literal)?;
+        assert_eq!(matcher.find_matches("unsafe { ptr.read() }"), vec![(0, 6)]);
+        assert!(matcher.find_matches("unsafe in a test").is_empty());
+        assert!(matcher.find_matches("safe code").is_empty());
+
+        let quoted = QueryMatcher::new(r#""fn main" OR (use NOT std)"#, QueryScope::Line, literal)?;
+        assert_eq!(quoted.find_matches("fn main() {}"), vec![(0, 7)]);
+        assert!(quoted.find_matches("use std::fs;").is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_file_query() -> Result<(), Box<dyn Error>> {
+        let matcher = QueryMatcher::new("Mutex NOT lock", QueryScope::File, literal)?;
+        assert!(matcher.matches_file("use std::sync::Mutex;\nlet m = Mutex::new(0);"));
+        assert!(!matcher.matches_file("let m = Mutex::new(0);\nm.lock();"));
+        assert_eq!(
+            matcher.find_matches("let m = Mutex::new(0);"),
+            vec![(8, 13)]
+        );
+        Ok(())
+    }
+}
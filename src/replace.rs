@@ -0,0 +1,534 @@
+use crate::cli::Args;
+use crate::fold;
+use crate::matcher::Matcher;
+use crate::result::{Replacement, SearchResult};
+use crate::searcher::Searcher;
+use crate::SearchError;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::Ordering;
+
+/// Where `--write` keeps a copy of each file before changing it.
+pub(crate) struct Backup {
+    pub(crate) suffix: String,
+    pub(crate) dir: Option<PathBuf>,
+}
+
+impl Backup {
+    pub(crate) fn from_args(args: &Args) -> Result<Option<Self>, SearchError> {
+        if !args.backup && args.backup_suffix.is_none() && args.backup_dir.is_none() {
+            return Ok(None);
+        }
+        if args.backup_dir.is_none() && args.backup_suffix.as_deref() == Some("") {
+            return Err(SearchError::new(
+                "InvalidOptions",
+                "an empty --backup-suffix needs --backup-dir, or the backup would be the file itself",
+            ));
+        }
+        let default_suffix = if args.backup_dir.is_some() {
+            ""
+        } else {
+            ".bak"
+        };
+        Ok(Some(Backup {
+            suffix: args
+                .backup_suffix
+                .clone()
+                .unwrap_or_else(|| default_suffix.to_string()),
+            dir: args.backup_dir.clone(),
+        }))
+    }
+
+    /// Where the copy of `path` goes. Under a backup directory only the
+    /// path's plain components are kept, so `..` can't lead out of it.
+    pub(crate) fn path_for(&self, path: &Path) -> std::io::Result<PathBuf> {
+        let mut backup = match &self.dir {
+            Some(dir) => dir.join(
+                path.components()
+                    .filter(|c| matches!(c, std::path::Component::Normal(_)))
+                    .collect::<PathBuf>(),
+            ),
+            None => path.to_path_buf(),
+        }
+        .into_os_string();
+        backup.push(&self.suffix);
+        let backup = PathBuf::from(backup);
+        // Such as with `--backup-dir /` and an absolute path, where copying
+        // the file onto itself would empty it.
+        let same = |a: &Path, b: &Path| {
+            a.canonicalize()
+                .ok()
+                .zip(b.canonicalize().ok())
+                .is_some_and(|(a, b)| a == b)
+        };
+        if backup == path || same(&backup, path) {
+            return Err(std::io::Error::other(format!(
+                "the backup of {} would be the file itself",
+                path.display()
+            )));
+        }
+        Ok(backup)
+    }
+}
+
+/// Replaces a file's contents by writing them beside it and renaming them
+/// over it, so a crash leaves either the old file or the new one, never a
+/// truncated one. The permissions, the owner where that's allowed and, with
+/// `keep_mtime`, the modification time carry over. A symlink is followed
+/// rather than replaced.
+pub(crate) fn write_atomically(
+    path: &Path,
+    contents: &[u8],
+    keep_mtime: bool,
+) -> std::io::Result<()> {
+    let target = fs::canonicalize(path)?;
+    let metadata = fs::metadata(&target)?;
+    let mut temp_name = std::ffi::OsString::from(".");
+    temp_name.push(target.file_name().unwrap_or_default());
+    temp_name.push(format!(".rscout-{}.tmp", std::process::id()));
+    let temp = target.with_file_name(temp_name);
+
+    let written = (|| {
+        let mut file = fs::File::options()
+            .write(true)
+            .create_new(true)
+            .open(&temp)?;
+        std::io::Write::write_all(&mut file, contents)?;
+        // Before the mode, since changing the owner can clear setuid bits.
+        set_owner(&file, &metadata);
+        file.set_permissions(metadata.permissions())?;
+        if keep_mtime {
+            file.set_modified(metadata.modified()?)?;
+        }
+        file.sync_all()?;
+        fs::rename(&temp, &target)
+    })();
+    if written.is_err() {
+        let _ = fs::remove_file(&temp);
+    }
+    written
+}
+
+/// Gives a rewritten file the original's owner and group, or just its group
+/// when only root may change owners; failing both, it stays the writer's.
+#[cfg(unix)]
+pub(crate) fn set_owner(file: &fs::File, metadata: &fs::Metadata) {
+    use std::os::unix::fs::{fchown, MetadataExt};
+    let _ = fchown(file, Some(metadata.uid()), Some(metadata.gid()))
+        .or_else(|_| fchown(file, None, Some(metadata.gid())));
+}
+
+#[cfg(not(unix))]
+pub(crate) fn set_owner(_file: &fs::File, _metadata: &fs::Metadata) {}
+
+/// Replaces each of `matches` in `line` with the text `replacement` gives
+/// for that match's start and end, or leaves it if that's `None`. Overlapping
+/// matches are replaced once, by the first of them.
+pub(crate) fn replace_matches(
+    line: &str,
+    matches: &[(usize, usize)],
+    mut replacement: impl FnMut(usize, usize) -> Option<String>,
+) -> Replacement {
+    let mut replaced = String::with_capacity(line.len());
+    let mut spans = Vec::with_capacity(matches.len());
+    let mut last = 0;
+    for &(start, end) in matches {
+        if start < last {
+            continue;
+        }
+        let Some(text) = replacement(start, end) else {
+            continue;
+        };
+        replaced.push_str(&line[last..start]);
+        spans.push((replaced.len(), replaced.len() + text.len()));
+        replaced.push_str(&text);
+        last = end;
+    }
+    replaced.push_str(&line[last..]);
+    Replacement {
+        line: replaced,
+        matches: spans,
+    }
+}
+
+impl Searcher {
+    /// Fills in what each of a file's result lines becomes with `--replace`,
+    /// counting occurrences through the file for `--replace-nth` and
+    /// through the whole run for `--replace-max`. Lines left with nothing to
+    /// replace get no replacement.
+    pub(crate) fn replace_in(
+        &self,
+        results: &mut [SearchResult],
+        text: &str,
+        matcher: &dyn Matcher,
+    ) {
+        let mut occurrence = 0;
+        for result in results {
+            let line = &result.line;
+            let replaced = replace_matches(line, &result.matches, |start, end| {
+                occurrence += 1;
+                if self.replace_nth.is_some_and(|nth| nth != occurrence) {
+                    return None;
+                }
+                if let Some(max) = self.replace_max {
+                    self.replaced
+                        .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |n| {
+                            (n < max).then_some(n + 1)
+                        })
+                        .ok()?;
+                }
+                let expanded = matcher
+                    .expand(line, start, text)
+                    .unwrap_or_else(|| text.to_string());
+                Some(if self.preserve_case {
+                    fold::match_case(&line[start..end], &expanded)
+                } else {
+                    expanded
+                })
+            });
+            result.replacement = (!replaced.matches.is_empty()).then_some(replaced);
+        }
+    }
+
+    /// Rewrites a file with every line in `results` replaced, keeping the
+    /// rest of it, line endings included, byte for byte.
+    pub(crate) fn rewrite(
+        &self,
+        path: &Path,
+        contents: &str,
+        results: &[SearchResult],
+    ) -> std::io::Result<()> {
+        let mut replaced = results
+            .iter()
+            .filter(|result| result.replacement.is_some())
+            .peekable();
+        let mut rewritten = String::with_capacity(contents.len());
+        for (index, line) in contents.split_inclusive('\n').enumerate() {
+            match replaced.next_if(|result| result.line_number == index + 1) {
+                Some(result) => {
+                    rewritten.push_str(&result.replacement.as_ref().unwrap().line);
+                    // Past the end of what was searched, as with
+                    // --max-line-length, or just the line ending.
+                    rewritten.push_str(&line[result.line.len()..]);
+                }
+                None => rewritten.push_str(line),
+            }
+        }
+
+        if let Some(backup) = &self.backup {
+            let backup = backup.path_for(path)?;
+            if let Some(parent) = backup.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::copy(path, backup)?;
+        }
+        write_atomically(path, rewritten.as_bytes(), self.preserve_mtime)
+    }
+
+    /// Writes the `--diff-output` patch, with files in path order.
+    pub(crate) fn write_patch(&self) -> std::io::Result<()> {
+        let Some(output) = &self.diff_output else {
+            return Ok(());
+        };
+        let mut patches = self.patches.lock().unwrap();
+        patches.sort_by(|a, b| a.0.cmp(&b.0));
+        let patch: String = patches.iter().map(|(_, patch)| patch.as_str()).collect();
+        fs::write(output, patch)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::parse_args;
+    use clap::Parser;
+    use std::error::Error;
+    use std::fs::File;
+    use std::time::{Duration, SystemTime};
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_replace() -> Result<(), Box<dyn Error>> {
+        let replaced =
+            replace_matches("foo bar foo", &[(0, 3), (8, 11)], |_, _| Some("baz".into()));
+        assert_eq!(replaced.line, "baz bar baz");
+        assert_eq!(replaced.matches, [(0, 3), (8, 11)]);
+
+        let dir = tempdir()?;
+        let path = dir.path().join("a.txt");
+        fs::write(&path, "let foo = 1;\r\nkeep\nfoo(foo)\n")?;
+
+        let args = parse_args(&["foo", "--replace", "bar", dir.path().to_str().unwrap()]);
+        let searcher = Searcher::new(&args)?;
+        let results = searcher.search_file(&path)?;
+        assert_eq!(results[1].replacement.as_ref().unwrap().line, "bar(bar)");
+        // Without --write, nothing changes.
+        assert_eq!(
+            fs::read_to_string(&path)?,
+            "let foo = 1;\r\nkeep\nfoo(foo)\n"
+        );
+
+        let args = parse_args(&[
+            "foo",
+            "--replace",
+            "bar",
+            "--write",
+            dir.path().to_str().unwrap(),
+        ]);
+        let searcher = Searcher::new(&args)?;
+        searcher.search_file(&path)?;
+        assert_eq!(
+            fs::read_to_string(&path)?,
+            "let bar = 1;\r\nkeep\nbar(bar)\n"
+        );
+        assert_eq!(searcher.rewritten.load(Ordering::Relaxed), 1);
+        assert_eq!(fs::read_dir(dir.path())?.count(), 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_replace_diff() -> Result<(), Box<dyn Error>> {
+        let dir = tempdir()?;
+        let path = dir.path().join("a.txt");
+        fs::write(&path, "one\nfoo\nthree\n")?;
+
+        let args = parse_args(&["foo", "--replace", "bar", dir.path().to_str().unwrap()]);
+        let searcher = Searcher::new(&args)?;
+        let results = searcher.search_file(&path)?;
+        let diff = results[0].diff.as_deref().unwrap();
+        for line in ["@@ -1,3 +1,3 @@", " one", "-foo", "+bar", " three"] {
+            assert!(diff.contains(line), "{:?} not in {:?}", line, diff);
+        }
+
+        // Rewriting shows the new lines instead.
+        let args = parse_args(&[
+            "foo",
+            "--replace",
+            "bar",
+            "--write",
+            dir.path().to_str().unwrap(),
+        ]);
+        assert!(Searcher::new(&args)?.search_file(&path)?[0].diff.is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_replace_captures() -> Result<(), Box<dyn Error>> {
+        let dir = tempdir()?;
+        let path = dir.path().join("a.rs");
+        fs::write(&path, "assert_eq!(got, want); assert_eq!(a, b);\n")?;
+
+        let args = parse_args(&[
+            "-E",
+            r"assert_eq!\((?P<left>\w+), (\w+)\)",
+            "--replace",
+            "assert_eq!($2, ${left})",
+            dir.path().to_str().unwrap(),
+        ]);
+        let results = Searcher::new(&args)?.search_file(&path)?;
+        assert_eq!(
+            results[0].replacement.as_ref().unwrap().line,
+            "assert_eq!(want, got); assert_eq!(b, a);"
+        );
+
+        // Literal patterns have no groups, so `$1` is kept as it is.
+        let args = parse_args(&["got", "--replace", "$1", dir.path().to_str().unwrap()]);
+        let results = Searcher::new(&args)?.search_file(&path)?;
+        assert!(results[0]
+            .replacement
+            .as_ref()
+            .unwrap()
+            .line
+            .contains("($1, want)"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_diff_output() -> Result<(), Box<dyn Error>> {
+        // Within the current directory, which the patch is applied from.
+        fs::create_dir_all("target")?;
+        let dir = tempfile::tempdir_in("target")?;
+        let tree = fs::canonicalize(dir.path())?;
+        fs::write(tree.join("b.txt"), "foo\n")?;
+        fs::write(tree.join("a.txt"), "keep\nfoo\n")?;
+        let outside = tempdir()?;
+        fs::write(outside.path().join("c.txt"), "foo\n")?;
+        let patch = tree.join("changes.patch");
+
+        let args = parse_args(&[
+            "foo",
+            "--replace",
+            "bar",
+            "--diff-output",
+            patch.to_str().unwrap(),
+            tree.to_str().unwrap(),
+        ]);
+        let searcher = Searcher::new(&args)?;
+        searcher.search_dir(&tree)?;
+        searcher.search_dir(outside.path())?;
+        searcher.write_patch()?;
+
+        let relative = tree.strip_prefix(std::env::current_dir()?)?.display();
+        assert_eq!(
+            fs::read_to_string(&patch)?,
+            format!(
+                "--- a/{0}/a.txt\n+++ b/{0}/a.txt\n@@ -1,2 +1,2 @@\n keep\n-foo\n+bar\n\
+                 --- a/{0}/b.txt\n+++ b/{0}/b.txt\n@@ -1,1 +1,1 @@\n-foo\n+bar\n",
+                relative
+            )
+        );
+        let checked = std::process::Command::new("git")
+            .args(["apply", "--check"])
+            .arg(&patch)
+            .output()?;
+        assert!(
+            checked.status.success(),
+            "{}",
+            String::from_utf8_lossy(&checked.stderr)
+        );
+        // The file outside the current directory couldn't be in the patch.
+        assert_eq!(searcher.errors.load(Ordering::Relaxed), 1);
+        assert_eq!(searcher.skipped.lock().unwrap().len(), 1);
+        // The files themselves are left alone.
+        assert_eq!(fs::read_to_string(tree.join("b.txt"))?, "foo\n");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_backup() -> Result<(), Box<dyn Error>> {
+        let dir = tempdir()?;
+        let tree = dir.path().join("tree");
+        fs::create_dir_all(tree.join("sub"))?;
+        fs::write(tree.join("sub/a.txt"), "foo\n")?;
+        let tree_arg = tree.to_str().unwrap();
+
+        let args = parse_args(&["foo", "--replace", "bar", "--write", "--backup", tree_arg]);
+        let searcher = Searcher::new(&args)?;
+        searcher.search_dir(&tree)?;
+        assert_eq!(fs::read_to_string(tree.join("sub/a.txt"))?, "bar\n");
+        assert_eq!(fs::read_to_string(tree.join("sub/a.txt.bak"))?, "foo\n");
+        // The backup made during the walk wasn't searched and rewritten too.
+        assert_eq!(searcher.rewritten.load(Ordering::Relaxed), 1);
+
+        let backups = dir.path().join("backups");
+        let args = parse_args(&[
+            "bar",
+            "--replace",
+            "baz",
+            "--write",
+            "--backup-dir",
+            backups.to_str().unwrap(),
+            tree_arg,
+        ]);
+        Searcher::new(&args)?.search_dir(&tree)?;
+        let relative: PathBuf = tree
+            .join("sub/a.txt")
+            .components()
+            .filter(|c| matches!(c, std::path::Component::Normal(_)))
+            .collect();
+        assert_eq!(fs::read_to_string(backups.join(relative))?, "bar\n");
+
+        let args = parse_args(&[
+            "baz",
+            "--replace",
+            "qux",
+            "--write",
+            "--backup-suffix",
+            "",
+            tree_arg,
+        ]);
+        assert!(Searcher::new(&args).is_err());
+        let backup = Backup {
+            suffix: String::new(),
+            dir: Some(PathBuf::from("/")),
+        };
+        let file = tree.join("sub/a.txt");
+        assert!(backup.path_for(&file).is_err());
+
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_write_keeps_metadata() -> Result<(), Box<dyn Error>> {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempdir()?;
+        let script = dir.path().join("run.sh");
+        fs::write(&script, "echo foo\n")?;
+        fs::set_permissions(&script, fs::Permissions::from_mode(0o754))?;
+        let mtime = SystemTime::now() - Duration::from_secs(86400);
+        File::options()
+            .write(true)
+            .open(&script)?
+            .set_modified(mtime)?;
+        let link = dir.path().join("link.sh");
+        std::os::unix::fs::symlink(&script, &link)?;
+
+        write_atomically(&link, b"echo bar\n", true)?;
+        assert_eq!(fs::read_to_string(&script)?, "echo bar\n");
+        assert!(fs::symlink_metadata(&link)?.file_type().is_symlink());
+        let metadata = fs::metadata(&script)?;
+        assert_eq!(metadata.permissions().mode() & 0o7777, 0o754);
+        assert_eq!(metadata.modified()?, mtime);
+        assert_eq!(fs::read_dir(dir.path())?.count(), 2);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_replace_nth_and_max() -> Result<(), Box<dyn Error>> {
+        let dir = tempdir()?;
+        let path = dir.path().join("a.txt");
+        fs::write(&path, "foo\nfoo foo\n")?;
+        let replaced = |argv: &[&str]| -> Result<Vec<Option<String>>, Box<dyn Error>> {
+            let mut argv = argv.to_vec();
+            argv.extend(["foo", "--replace", "bar"]);
+            let results = Searcher::new(&parse_args(&argv))?.search_file(&path)?;
+            Ok(results
+                .into_iter()
+                .map(|result| result.replacement.map(|replacement| replacement.line))
+                .collect())
+        };
+
+        assert_eq!(
+            replaced(&["--replace-nth", "2"])?,
+            [None, Some("bar foo".to_string())]
+        );
+        assert_eq!(
+            replaced(&["--replace-max", "2"])?,
+            [Some("bar".to_string()), Some("bar foo".to_string())]
+        );
+        assert!(Args::try_parse_from([
+            "rusty-scout",
+            "foo",
+            "--replace",
+            "bar",
+            "--replace-nth",
+            "0"
+        ])
+        .is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_preserve_case() -> Result<(), Box<dyn Error>> {
+        let dir = tempdir()?;
+        let path = dir.path().join("a.txt");
+        fs::write(&path, "color Color COLOR\n")?;
+
+        let args = parse_args(&["color", "--replace", "colour", "--preserve-case"]);
+        let results = Searcher::new(&args)?.search_file(&path)?;
+        assert_eq!(
+            results[0].replacement.as_ref().unwrap().line,
+            "colour Colour COLOUR"
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,226 @@
+use crate::rules::{Rule, Severity};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// A second line reported alongside a result, such as the partner of a
+/// `--near` match.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct RelatedLine {
+    pub line_number: usize,
+    pub line: String,
+    pub matches: Vec<(usize, usize)>,
+}
+
+/// A match, and the line it was found on.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct SearchResult {
+    pub file_path: PathBuf,
+    /// Counting from 1, or 0 for results about a whole file.
+    pub line_number: usize,
+    pub line: String,
+    /// Byte ranges of the matches within `line`.
+    pub matches: Vec<(usize, usize)>,
+    /// How good a fuzzy match is; higher is better.
+    pub score: Option<i64>,
+    pub byte_offset: Option<usize>,
+    pub related: Option<RelatedLine>,
+    /// The rule that matched, with `--rules`.
+    pub rule: Option<Arc<Rule>>,
+    /// Kind of the syntax node the match is in.
+    pub syntax_node: Option<String>,
+    /// (name, text) bound by a structural match.
+    pub metavariables: Vec<(String, String)>,
+    pub edit_distance: Option<usize>,
+    /// A match in a binary file, reported without its line.
+    pub binary: bool,
+    pub replacement: Option<Replacement>,
+    /// The file's `--replace` preview, on its first result.
+    pub diff: Option<String>,
+}
+
+/// A line as `--replace` would leave it.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Replacement {
+    pub line: String,
+    /// Where the replacement text went.
+    pub matches: Vec<(usize, usize)>,
+}
+
+/// Version of the `--json` output's schema, which changes only when a field
+/// is removed or changes meaning.
+pub const JSON_SCHEMA_VERSION: u32 = 1;
+
+/// The JSON Schema of what `--json` prints for each result.
+pub const JSON_SCHEMA: &str = include_str!("result.schema.json");
+
+/// A result as printed by `--json`, as described by [`JSON_SCHEMA`].
+#[derive(Serialize)]
+pub(crate) struct JsonResult<'a> {
+    pub(crate) schema_version: u32,
+    pub(crate) path: String,
+    pub(crate) line_number: usize,
+    pub(crate) line: &'a str,
+    pub(crate) matches: &'a [(usize, usize)],
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) score: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) edit_distance: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) byte_offset: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) rule: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) severity: Option<Severity>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) syntax_node: Option<&'a str>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub(crate) metavariables: BTreeMap<&'a str, &'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) related_line_number: Option<usize>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub(crate) binary: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) replacement: Option<&'a str>,
+}
+
+impl SearchResult {
+    pub(crate) fn to_json(&self) -> JsonResult<'_> {
+        JsonResult {
+            schema_version: JSON_SCHEMA_VERSION,
+            path: self.file_path.display().to_string(),
+            line_number: self.line_number,
+            line: &self.line,
+            matches: &self.matches,
+            score: self.score,
+            edit_distance: self.edit_distance,
+            byte_offset: self.byte_offset,
+            rule: self.rule.as_ref().map(|rule| rule.name.as_str()),
+            severity: self.rule.as_ref().map(|rule| rule.severity),
+            syntax_node: self.syntax_node.as_deref(),
+            metavariables: self
+                .metavariables
+                .iter()
+                .map(|(name, text)| (name.as_str(), text.as_str()))
+                .collect(),
+            related_line_number: self.related.as_ref().map(|related| related.line_number),
+            binary: self.binary,
+            replacement: self.replacement.as_ref().map(|r| r.line.as_str()),
+        }
+    }
+
+    pub(crate) fn new(
+        file_path: &Path,
+        line_number: usize,
+        line: &str,
+        matches: Vec<(usize, usize)>,
+    ) -> Self {
+        SearchResult {
+            file_path: file_path.to_path_buf(),
+            line_number,
+            line: line.to_string(),
+            matches,
+            score: None,
+            byte_offset: None,
+            related: None,
+            rule: None,
+            syntax_node: None,
+            metavariables: Vec::new(),
+            edit_distance: None,
+            binary: false,
+            replacement: None,
+            diff: None,
+        }
+    }
+
+    /// Roughly how many bytes this result holds, for `--max-memory`.
+    pub(crate) fn approximate_size(&self) -> usize {
+        std::mem::size_of::<Self>()
+            + self.file_path.as_os_str().len()
+            + self.line.len()
+            + self
+                .replacement
+                .as_ref()
+                .map_or(0, |replacement| replacement.line.len())
+            + self.diff.as_ref().map_or(0, String::len)
+            + self.matches.len() * std::mem::size_of::<(usize, usize)>()
+            + self
+                .related
+                .as_ref()
+                .map_or(0, |related| related.line.len())
+            + self
+                .metavariables
+                .iter()
+                .map(|(name, text)| name.len() + text.len())
+                .sum::<usize>()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::Value;
+
+    #[test]
+    fn test_json_schema() {
+        let schema: Value = serde_json::from_str(JSON_SCHEMA).unwrap();
+        assert_eq!(
+            schema["properties"]["schema_version"]["const"],
+            JSON_SCHEMA_VERSION
+        );
+
+        let plain = SearchResult::new(Path::new("a.rs"), 3, "// TODO", vec![(3, 7)]);
+        let plain = serde_json::to_value(plain.to_json()).unwrap();
+        let required: Vec<&str> = schema["required"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|name| name.as_str().unwrap())
+            .collect();
+        let mut present: Vec<&str> = plain
+            .as_object()
+            .unwrap()
+            .keys()
+            .map(|k| k.as_str())
+            .collect();
+        present.sort_unstable();
+        let mut sorted = required.clone();
+        sorted.sort_unstable();
+        assert_eq!(present, sorted);
+
+        // Every field a result can have is described.
+        let full = SearchResult {
+            score: Some(1),
+            byte_offset: Some(0),
+            related: Some(RelatedLine {
+                line_number: 4,
+                line: String::new(),
+                matches: Vec::new(),
+            }),
+            rule: Some(Arc::new(Rule {
+                name: "todo".to_string(),
+                regex: "TODO".to_string(),
+                severity: Severity::Info,
+                description: None,
+            })),
+            syntax_node: Some("line_comment".to_string()),
+            metavariables: vec![("$X".to_string(), "TODO".to_string())],
+            edit_distance: Some(0),
+            binary: true,
+            replacement: Some(Replacement {
+                line: "// DONE".to_string(),
+                matches: vec![(3, 7)],
+            }),
+            ..SearchResult::new(Path::new("a.rs"), 3, "// TODO", vec![(3, 7)])
+        };
+        let full = serde_json::to_value(full.to_json()).unwrap();
+        for name in full.as_object().unwrap().keys() {
+            assert!(schema["properties"].get(name).is_some(), "{}", name);
+        }
+        assert_eq!(
+            full.as_object().unwrap().len(),
+            schema["properties"].as_object().unwrap().len()
+        );
+    }
+}
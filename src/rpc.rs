@@ -0,0 +1,446 @@
+//! `--rpc`: JSON-RPC 2.0 over stdio, one message per line, so an editor can
+//! keep one process running instead of starting one for every search.
+//!
+//! - `search` takes a `path` (default `.`) and the fields of
+//!   [`SearchOptions`]. Each result is sent as soon as it's found, in a
+//!   `match` notification carrying the request's `id`; the response comes
+//!   once the search is over. Its `id` can't be that of a search still
+//!   running.
+//! - `cancel` stops the search whose request had the `id` in its params.
+//! - `list-files` takes the same params, without patterns, and responds
+//!   with the files a search would look at.
+//!
+//! Requests are served concurrently, so a `cancel` isn't stuck behind the
+//! search it stops.
+
+use crate::iter::{Cancel, SearchIter};
+use crate::options::{SearchOptions, SearcherBuilder};
+use crate::searcher::Searcher;
+use serde::Deserialize;
+use serde_json::{json, Map, Value};
+use std::collections::HashMap;
+use std::io::{self, BufRead, Write};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+pub(crate) const METHOD_NOT_FOUND: i64 = -32601;
+pub(crate) const INVALID_PARAMS: i64 = -32602;
+/// A search that failed after it started.
+const SEARCH_FAILED: i64 = -32000;
+
+#[derive(Deserialize)]
+pub(crate) struct Request {
+    /// Missing from notifications, which get no response.
+    pub(crate) id: Option<Value>,
+    pub(crate) method: String,
+    #[serde(default)]
+    pub(crate) params: Value,
+}
+
+/// Searches still running, by their request's id.
+type Running = Arc<Mutex<HashMap<String, Cancel>>>;
+
+/// Writes one message and its newline. Messages from different searches
+/// never interleave, since each is written whole under the lock.
+pub(crate) fn send<W: Write>(output: &Mutex<W>, message: &Value) -> io::Result<()> {
+    let mut line = serde_json::to_vec(message)?;
+    line.push(b'\n');
+    let mut output = output.lock().unwrap();
+    output.write_all(&line)?;
+    output.flush()
+}
+
+pub(crate) fn response(id: &Value, result: Value) -> Value {
+    json!({"jsonrpc": "2.0", "id": id, "result": result})
+}
+
+pub(crate) fn error(id: &Value, code: i64, message: impl ToString) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "error": {"code": code, "message": message.to_string()},
+    })
+}
+
+/// Parses one line of input, or returns the error to send back for it.
+pub(crate) fn parse_request(line: &str) -> Result<Request, Value> {
+    serde_json::from_str(line).map_err(|e| {
+        let code = match serde_json::from_str::<Value>(line) {
+            Ok(_) => INVALID_REQUEST,
+            Err(_) => PARSE_ERROR,
+        };
+        error(&Value::Null, code, e)
+    })
+}
+
+/// Splits `search` and `list-files` params into the path and the rest.
+fn search_params(params: Value) -> Result<(PathBuf, SearchOptions), String> {
+    let mut params = match params {
+        Value::Object(params) => params,
+        Value::Null => Map::new(),
+        _ => return Err("params must be an object".to_string()),
+    };
+    let path = match params.remove("path") {
+        None => PathBuf::from("."),
+        Some(Value::String(path)) => PathBuf::from(path),
+        Some(_) => return Err("path must be a string".to_string()),
+    };
+    let options = serde_json::from_value(Value::Object(params)).map_err(|e| e.to_string())?;
+    Ok((path, options))
+}
+
+/// Builds the searcher a `search` or `list-files` request asks for.
+fn searcher(method: &str, options: SearchOptions) -> Result<Searcher, String> {
+    if method == "list-files" {
+        let mut args = options.to_args();
+        args.files = true;
+        Searcher::new(&args)
+    } else {
+        SearcherBuilder::from_options(options).build()
+    }
+    .map_err(|e| e.to_string())
+}
+
+/// Serves requests read from `input` until it ends, then waits for the
+/// searches still running.
+pub(crate) fn serve<R, W>(input: R, output: W) -> io::Result<()>
+where
+    R: BufRead,
+    W: Write + Send + 'static,
+{
+    let output = Arc::new(Mutex::new(output));
+    let running = Running::default();
+    let mut searches: Vec<JoinHandle<()>> = Vec::new();
+    for line in input.lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let request = match parse_request(&line) {
+            Ok(request) => request,
+            Err(message) => {
+                send(&output, &message)?;
+                continue;
+            }
+        };
+        let reply = request.id.is_some();
+        let id = request.id.unwrap_or(Value::Null);
+        let message = match request.method.as_str() {
+            "search" | "list-files" => {
+                match search_params(request.params)
+                    .and_then(|(path, options)| Ok((path, searcher(&request.method, options)?)))
+                {
+                    // Otherwise a `cancel` couldn't tell the two apart.
+                    Ok(_) if running.lock().unwrap().contains_key(&id.to_string()) => error(
+                        &id,
+                        INVALID_REQUEST,
+                        format!("a search with id {} is still running", id),
+                    ),
+                    Ok((path, searcher)) => {
+                        // Registered before the next request is read, so a
+                        // `cancel` right behind it finds it. Notifications
+                        // have no id to cancel them by.
+                        let found = searcher.search_iter(path);
+                        if reply {
+                            running
+                                .lock()
+                                .unwrap()
+                                .insert(id.to_string(), found.cancel_handle());
+                        }
+                        let list = request.method == "list-files";
+                        let (output, running) = (Arc::clone(&output), Arc::clone(&running));
+                        searches.push(std::thread::spawn(move || {
+                            let message = forward(&id, found, list, &output, &running);
+                            if reply {
+                                // Nothing is left to tell if the output is gone.
+                                let _ = send(&output, &message);
+                            }
+                        }));
+                        searches.retain(|search| !search.is_finished());
+                        continue;
+                    }
+                    Err(message) => error(&id, INVALID_PARAMS, message),
+                }
+            }
+            "cancel" => match request.params.get("id") {
+                Some(target) => {
+                    let cancel = running.lock().unwrap().remove(&target.to_string());
+                    if let Some(cancel) = &cancel {
+                        cancel.cancel();
+                    }
+                    response(&id, json!(cancel.is_some()))
+                }
+                None => error(&id, INVALID_PARAMS, "params.id is missing"),
+            },
+            method => error(
+                &id,
+                METHOD_NOT_FOUND,
+                format!("unknown method {:?}", method),
+            ),
+        };
+        if reply {
+            send(&output, &message)?;
+        }
+    }
+    for search in searches {
+        search.join().unwrap();
+    }
+    Ok(())
+}
+
+/// Sends on what a search finds, returning the response to its request.
+fn forward<W: Write>(
+    id: &Value,
+    found: SearchIter,
+    list: bool,
+    output: &Mutex<W>,
+    running: &Mutex<HashMap<String, Cancel>>,
+) -> Value {
+    let mut files = Vec::new();
+    let mut matches = 0;
+    let mut failed = None;
+    for result in found {
+        match result {
+            Ok(result) if list => files.push(result.file_path.display().to_string()),
+            Ok(result) => {
+                matches += 1;
+                let notification = json!({
+                    "jsonrpc": "2.0",
+                    "method": "match",
+                    "params": {"id": id, "result": result.to_json()},
+                });
+                if send(output, &notification).is_err() {
+                    break;
+                }
+            }
+            Err(e) => {
+                failed = Some(e);
+                break;
+            }
+        }
+    }
+    // A `cancel` takes the search out of `running` itself.
+    let cancelled = running.lock().unwrap().remove(&id.to_string()).is_none();
+    match failed {
+        Some(e) => error(id, SEARCH_FAILED, e),
+        None if list => response(id, json!(files)),
+        None => response(id, json!({"matches": matches, "cancelled": cancelled})),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::sync::mpsc;
+    use tempfile::tempdir;
+
+    /// Output the test can read back once `serve` has taken it.
+    #[derive(Clone, Default)]
+    struct SharedOutput(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedOutput {
+        fn write(&mut self, bytes: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(bytes)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// Input that arrives as the test sends it, ending once it stops. Each
+    /// line taken is acknowledged on `taken`.
+    struct Sent {
+        lines: mpsc::Receiver<String>,
+        taken: mpsc::Sender<()>,
+        pending: Vec<u8>,
+    }
+
+    impl io::Read for Sent {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            if self.pending.is_empty() {
+                match self.lines.recv() {
+                    Ok(line) => self.pending = line.into_bytes(),
+                    Err(_) => return Ok(0),
+                }
+                let _ = self.taken.send(());
+            }
+            let n = buf.len().min(self.pending.len());
+            buf[..n].copy_from_slice(&self.pending[..n]);
+            self.pending.drain(..n);
+            Ok(n)
+        }
+    }
+
+    /// Told when the first match is held back, then waited on to let it go.
+    type Gate = (mpsc::Sender<()>, mpsc::Receiver<()>);
+
+    /// Output that holds back the first match until the test opens the
+    /// gate, so the search is still running when later requests arrive.
+    #[derive(Clone)]
+    struct GatedOutput {
+        output: SharedOutput,
+        gate: Arc<Mutex<Option<Gate>>>,
+    }
+
+    impl Write for GatedOutput {
+        fn write(&mut self, bytes: &[u8]) -> io::Result<usize> {
+            let is_match =
+                std::str::from_utf8(bytes).is_ok_and(|line| line.contains(r#""method":"match""#));
+            if is_match {
+                if let Some((blocked, open)) = self.gate.lock().unwrap().take() {
+                    blocked.send(()).unwrap();
+                    open.recv().unwrap();
+                }
+            }
+            self.output.write(bytes)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn serve_lines(requests: &[Value]) -> io::Result<Vec<Value>> {
+        let input: String = requests.iter().map(|r| format!("{}\n", r)).collect();
+        let output = SharedOutput::default();
+        serve(input.as_bytes(), output.clone())?;
+        let output = output.0.lock().unwrap();
+        Ok(std::str::from_utf8(&output)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect())
+    }
+
+    #[test]
+    fn test_serve() -> Result<(), Box<dyn std::error::Error>> {
+        let dir = tempdir()?;
+        fs::write(dir.path().join("a.txt"), "a needle\nhay\nNEEDLE\n")?;
+        fs::write(dir.path().join("b.rs"), "hay\n")?;
+        let path = dir.path().display().to_string();
+
+        let messages = serve_lines(&[
+            json!({"jsonrpc": "2.0", "id": 1, "method": "search", "params": {
+                "path": path, "patterns": ["needle"], "ignore_case": true,
+            }}),
+            json!({"jsonrpc": "2.0", "id": 2, "method": "list-files", "params": {
+                "path": path, "globs": ["*.rs"],
+            }}),
+            json!({"jsonrpc": "2.0", "id": 3, "method": "search", "params": {
+                "path": path, "patterns": ["x"], "colour": true,
+            }}),
+            json!({"jsonrpc": "2.0", "id": 4, "method": "grep"}),
+        ])?;
+        let reply = |id: i64| messages.iter().find(|m| m["id"] == id).unwrap();
+
+        let matched = messages
+            .iter()
+            .filter(|m| m["method"] == "match")
+            .inspect(|m| assert_eq!(m["params"]["id"], 1))
+            .count();
+        assert_eq!(matched, 2);
+        assert_eq!(
+            reply(1)["result"],
+            json!({"matches": 2, "cancelled": false})
+        );
+        assert_eq!(
+            reply(2)["result"],
+            json!([dir.path().join("b.rs").display().to_string()])
+        );
+        assert_eq!(reply(3)["error"]["code"], INVALID_PARAMS);
+        assert!(reply(3)["error"]["message"]
+            .as_str()
+            .unwrap()
+            .contains("colour"));
+        assert_eq!(reply(4)["error"]["code"], METHOD_NOT_FOUND);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_cancel() -> io::Result<()> {
+        let messages = serve_lines(&[
+            json!({"jsonrpc": "2.0", "id": "a", "method": "cancel", "params": {"id": "b"}}),
+        ])?;
+        assert_eq!(
+            messages,
+            [json!({"jsonrpc": "2.0", "id": "a", "result": false})]
+        );
+
+        let output = SharedOutput::default();
+        serve("not json\n".as_bytes(), output.clone())?;
+        let output: Value = serde_json::from_slice(&output.0.lock().unwrap())?;
+        assert_eq!(output["error"]["code"], PARSE_ERROR);
+        Ok(())
+    }
+
+    #[test]
+    fn test_cancel_running() -> Result<(), Box<dyn std::error::Error>> {
+        const FILES: usize = 2000;
+        let dir = tempdir()?;
+        for i in 0..FILES {
+            fs::write(dir.path().join(format!("{}.txt", i)), "needle\n")?;
+        }
+        let search = json!({"jsonrpc": "2.0", "id": 1, "method": "search", "params": {
+            "path": dir.path(), "patterns": ["needle"],
+        }});
+
+        let (send_line, lines) = mpsc::channel();
+        let (taken_line, taken) = mpsc::channel();
+        let (blocked, held) = mpsc::channel();
+        let (opened, open) = mpsc::channel();
+        let output = GatedOutput {
+            output: SharedOutput::default(),
+            gate: Arc::new(Mutex::new(Some((blocked, open)))),
+        };
+        let serving = std::thread::spawn({
+            let output = output.clone();
+            move || {
+                let input = Sent {
+                    lines,
+                    taken: taken_line,
+                    pending: Vec::new(),
+                };
+                serve(io::BufReader::new(input), output)
+            }
+        });
+        send_line.send(format!("{}\n", search))?;
+        taken.recv()?;
+        held.recv()?;
+        // The same id again while the first search is held back, then its
+        // cancel, taken together before the search may go on.
+        send_line.send(format!(
+            "{}\n{}\n",
+            search,
+            json!({"jsonrpc": "2.0", "id": 2, "method": "cancel", "params": {"id": 1}})
+        ))?;
+        taken.recv()?;
+        opened.send(())?;
+        drop(send_line);
+        serving.join().unwrap()?;
+
+        let output = output.output.0.lock().unwrap();
+        let messages: Vec<Value> = std::str::from_utf8(&output)?
+            .lines()
+            .map(serde_json::from_str)
+            .collect::<Result<_, _>>()?;
+        let replies: Vec<_> = messages.iter().filter(|m| m["id"] == 1).collect();
+        assert_eq!(replies.len(), 2);
+        assert_eq!(replies[0]["error"]["code"], INVALID_REQUEST);
+        let matches = replies[1]["result"]["matches"].as_u64().unwrap() as usize;
+        assert!(matches < FILES, "{} matches", matches);
+        assert_eq!(replies[1]["result"]["cancelled"], true);
+        assert_eq!(
+            messages.iter().find(|m| m["id"] == 2).unwrap()["result"],
+            true
+        );
+        let matched = messages.iter().filter(|m| m["method"] == "match").count();
+        assert_eq!(matched, matches);
+        Ok(())
+    }
+}
@@ -0,0 +1,57 @@
+//! Confining searches asked for by someone else, as `--mcp` and `--serve`
+//! do, to one directory.
+
+use crate::result::SearchResult;
+use serde_json::Value;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// The directory searches are confined to. Paths given are taken from it,
+/// and must stay inside it once symlinks are resolved. Searches don't
+/// follow symlinks while walking, so nothing outside it is ever read.
+pub(crate) struct Sandbox {
+    /// Canonical, so it can be compared with resolved paths.
+    root: PathBuf,
+}
+
+impl Sandbox {
+    pub(crate) fn new(root: &Path) -> io::Result<Self> {
+        let root = root.canonicalize()?;
+        if !root.is_dir() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{} isn't a directory", root.display()),
+            ));
+        }
+        Ok(Sandbox { root })
+    }
+
+    /// Resolves a path given to search, refusing any outside the root.
+    pub(crate) fn resolve(&self, path: Option<&str>) -> Result<PathBuf, String> {
+        let given = path.unwrap_or(".");
+        let resolved = self
+            .root
+            .join(given)
+            .canonicalize()
+            .map_err(|e| format!("{}: {}", given, e))?;
+        if !resolved.starts_with(&self.root) {
+            return Err(format!("{} is outside the project root", given));
+        }
+        Ok(resolved)
+    }
+
+    /// How a path is shown: relative to the root.
+    pub(crate) fn relative(&self, path: &Path) -> String {
+        match path.strip_prefix(&self.root) {
+            Ok(relative) if !relative.as_os_str().is_empty() => relative.display().to_string(),
+            _ => ".".to_string(),
+        }
+    }
+
+    /// A result as `--json` prints it, with its path relative to the root.
+    pub(crate) fn to_json(&self, result: &SearchResult) -> Value {
+        let mut json = result.to_json();
+        json.path = self.relative(&result.file_path);
+        serde_json::to_value(json).unwrap()
+    }
+}
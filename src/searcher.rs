@@ -0,0 +1,2292 @@
+use crate::cli::{Args, Engine, IoBackend, LineRange, LongLines};
+use crate::clock::Instant;
+use crate::fold::Folder;
+use crate::matcher::{
+    ApproximateMatcher, EntropyMatcher, FilePredicates, FuzzyMatcher, IdentifierMatcher,
+    LiteralMatcher, Matcher, MultiLiteralMatcher, RegexMatcher, RegexOptions, WholeLineMatcher,
+};
+use crate::output::Printer;
+use crate::query::QueryMatcher;
+use crate::replace::Backup;
+use crate::result::{RelatedLine, SearchResult};
+use crate::rules::RuleSet;
+#[cfg(feature = "syntax")]
+use crate::syntax;
+use crate::{budget, buffer, diff, matcher, mime, perms, stats, types, SearchError};
+use indicatif::{MultiProgress, ProgressDrawTarget};
+use memchr::memmem;
+use memmap2::Mmap;
+use regex::Regex;
+use std::borrow::Cow;
+use std::collections::HashSet;
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc::SyncSender;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+
+pub(crate) fn regex_options(args: &Args) -> RegexOptions {
+    RegexOptions {
+        case_insensitive: args.ignore_case(),
+        size_limit: args.regex_size_limit,
+        dfa_size_limit: args.dfa_size_limit,
+    }
+}
+
+/// Compiles `patterns` into the matcher selected by the command line flags.
+pub(crate) fn build_matcher(
+    args: &Args,
+    patterns: &[String],
+) -> Result<Box<dyn Matcher>, Box<dyn Error>> {
+    if args.fuzzy {
+        if patterns.len() > 1 {
+            return Err(Box::new(SearchError::new(
+                "InvalidPattern",
+                "--fuzzy takes a single pattern",
+            )));
+        }
+        return Ok(Box::new(FuzzyMatcher::new(
+            &patterns[0],
+            args.ignore_case(),
+        )));
+    }
+
+    if let Some(max_errors) = args.max_errors {
+        if patterns.len() > 1 {
+            return Err(Box::new(SearchError::new(
+                "InvalidPattern",
+                "--max-errors takes a single pattern",
+            )));
+        }
+        return Ok(Box::new(ApproximateMatcher::new(
+            &patterns[0],
+            max_errors,
+            args.ignore_case(),
+        )?));
+    }
+
+    if args.regex || args.glob_pattern {
+        let mut combined = if args.glob_pattern {
+            let translated: Vec<String> = patterns
+                .iter()
+                .map(|p| matcher::wildcard_to_regex(p))
+                .collect();
+            matcher::combine_patterns(&translated)
+        } else {
+            matcher::combine_patterns(patterns)
+        };
+        if args.line_regexp {
+            combined = format!("^(?:{})$", combined);
+        }
+        let options = regex_options(args);
+        return Ok(match args.engine {
+            Engine::Default => Box::new(RegexMatcher::new(&combined, &options)?),
+            #[cfg(feature = "fancy-regex")]
+            Engine::Fancy => Box::new(matcher::FancyRegexMatcher::new(&combined, &options)?),
+        });
+    }
+
+    let folder = Folder {
+        ignore_case: args.ignore_case(),
+        unicode: args.unicode,
+    };
+    let matcher: Box<dyn Matcher> = if patterns.len() > 1 {
+        Box::new(MultiLiteralMatcher::new(patterns, folder)?)
+    } else {
+        Box::new(LiteralMatcher::new(&patterns[0], folder))
+    };
+    if args.line_regexp {
+        Ok(Box::new(WholeLineMatcher::new(matcher)))
+    } else {
+        Ok(matcher)
+    }
+}
+
+/// What `search_file` looks for in each file.
+pub(crate) enum SearchTarget {
+    /// Lines of text accepted by a matcher
+    Lines(Box<dyn Matcher>),
+    /// A raw byte sequence, reported by its offset in the file
+    Bytes(Box<memmem::Finder<'static>>),
+    /// Every rule from a rules file
+    Rules(RuleSet),
+    /// Two patterns on lines at most `distance` apart
+    Near {
+        first: Box<dyn Matcher>,
+        second: Box<dyn Matcher>,
+        distance: usize,
+    },
+    /// Whole files, reported by path; used when only file predicates are given
+    Files,
+    /// Syntax tree nodes matching a code template
+    #[cfg(feature = "syntax")]
+    Structural(syntax::StructuralPattern),
+}
+
+/// Outcome of `Searcher::line_step`.
+pub(crate) enum LineStep {
+    Search,
+    Skip,
+    Stop,
+}
+
+/// Bytes of context shown on either side of a `--bytes` match.
+pub(crate) const BYTE_CONTEXT: usize = 8;
+
+/// One file's results on their way to be printed, with the `--max-memory`
+/// room they take up until then.
+pub(crate) struct ResultBatch {
+    pub(crate) results: Vec<SearchResult>,
+    pub(crate) _reservation: Option<budget::Reservation>,
+}
+
+/// What a search sends, as it goes, to whatever is reading its results.
+pub(crate) enum Event {
+    Results(ResultBatch),
+    /// A file that couldn't be searched, and why.
+    FileError(PathBuf, String),
+}
+
+/// The path results from stdin are reported under.
+pub(crate) const STDIN_PATH: &str = "<stdin>";
+
+/// Files whose results can wait to be printed before searching blocks.
+pub(crate) const RESULT_CHANNEL_CAPACITY: usize = 256;
+
+/// How much is read at a time, with `--file-timeout` checked in between.
+const READ_CHUNK: u64 = 1024 * 1024;
+
+/// How a file's contents are brought in, chosen by its size.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum ReadStrategy {
+    /// Read whole onto the heap: the fastest way for small files
+    Read,
+    /// Memory-mapped, avoiding a copy of large files
+    Map,
+    /// Searched line by line as it's read, so memory use stays flat
+    Stream,
+}
+
+/// A file's bytes, either read onto the heap or mapped from disk.
+pub(crate) enum FileContents {
+    Read(buffer::PooledBuffer),
+    Mapped(Mmap),
+    /// Read ahead of time by `--io-backend uring`.
+    #[cfg_attr(not(all(feature = "io-uring", target_os = "linux")), allow(dead_code))]
+    Prefetched(Vec<u8>),
+}
+
+impl std::ops::Deref for FileContents {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self {
+            FileContents::Read(bytes) => bytes,
+            FileContents::Mapped(map) => map,
+            FileContents::Prefetched(bytes) => bytes,
+        }
+    }
+}
+
+/// Searches files and directories as configured by an [`Args`].
+///
+/// Results are kept until taken by [`Searcher::search`], so one searcher can
+/// be reused for several searches.
+pub struct Searcher {
+    pub(crate) target: SearchTarget,
+    pub(crate) extensions: Vec<String>,
+    /// The `--type` filter, kept apart from the globs so it can't bring
+    /// back ignored or hidden files.
+    pub(crate) types: ignore::types::Types,
+    pub(crate) globs: Vec<String>,
+    pub(crate) exclude: Vec<String>,
+    pub(crate) exclude_dir: Vec<String>,
+    pub(crate) max_depth: Option<usize>,
+    pub(crate) max_filesize: Option<usize>,
+    pub(crate) newer_than: Option<SystemTime>,
+    pub(crate) older_than: Option<SystemTime>,
+    pub(crate) owner: Option<perms::Owner>,
+    pub(crate) perm: Option<perms::PermFilter>,
+    pub(crate) hidden: bool,
+    pub(crate) no_ignore: bool,
+    pub(crate) no_ignore_vcs: bool,
+    pub(crate) no_ignore_parent: bool,
+    pub(crate) follow: bool,
+    pub(crate) one_file_system: bool,
+    pub(crate) hardlink_dedup: bool,
+    /// (device, inode) of the multiply-linked files searched so far.
+    pub(crate) seen_links: Mutex<HashSet<(u64, u64)>>,
+    pub(crate) path_pattern: Option<Regex>,
+    pub(crate) file_predicates: Option<FilePredicates>,
+    pub(crate) mime: Vec<String>,
+    pub(crate) max_count: Option<usize>,
+    pub(crate) line_range: Option<LineRange>,
+    #[cfg(feature = "syntax")]
+    pub(crate) scope: Option<syntax::Scope>,
+    pub(crate) match_paths: bool,
+    pub(crate) json: bool,
+    /// Keep the summary and other notes off stdout, see
+    /// [`Args::results_only`].
+    pub(crate) results_only: bool,
+    pub(crate) replace: Option<String>,
+    pub(crate) replace_nth: Option<usize>,
+    pub(crate) replace_max: Option<usize>,
+    pub(crate) preserve_case: bool,
+    /// Occurrences replaced so far, for `--replace-max`.
+    pub(crate) replaced: AtomicUsize,
+    pub(crate) write: bool,
+    pub(crate) rewritten: AtomicUsize,
+    pub(crate) diff_output: Option<PathBuf>,
+    pub(crate) backup: Option<Backup>,
+    pub(crate) preserve_mtime: bool,
+    /// Each file's part of the `--diff-output` patch.
+    pub(crate) patches: Mutex<Vec<(PathBuf, String)>>,
+    pub(crate) files_with_matches: bool,
+    /// Print how many lines matched in each file, for `--count`.
+    pub(crate) count: bool,
+    pub(crate) recent_first: bool,
+    pub(crate) limit: Option<usize>,
+    pub(crate) match_timeout: Option<Duration>,
+    pub(crate) file_timeout: Option<Duration>,
+    pub(crate) binary: bool,
+    pub(crate) text: bool,
+    pub(crate) threads: usize,
+    /// `None` with `--no-mmap`.
+    pub(crate) mmap_min_size: Option<u64>,
+    pub(crate) io_backend: IoBackend,
+    pub(crate) stream_min_size: u64,
+    /// Set once `--limit` is reached, or from a `SearchIter` that's been
+    /// dropped.
+    pub(crate) cancelled: Arc<AtomicBool>,
+    /// Set from the Ctrl-C handler.
+    pub(crate) interrupted: Arc<AtomicBool>,
+    pub(crate) binary_skipped: AtomicUsize,
+    pub(crate) max_line_length: Option<usize>,
+    pub(crate) long_lines: LongLines,
+    /// Lines truncated or skipped for being over `max_line_length`.
+    pub(crate) long_lines_seen: AtomicUsize,
+    pub(crate) results: Arc<Mutex<Vec<SearchResult>>>,
+    /// Where results go instead of `results` once `stream_to` is called.
+    pub(crate) output: Option<SyncSender<Event>>,
+    /// Halves of `--max-memory`: one for file contents being searched, one
+    /// for results waiting to be printed. Kept apart so that workers holding
+    /// contents never wait on each other for room for their results.
+    pub(crate) content_budget: Option<Arc<budget::MemoryBudget>>,
+    pub(crate) result_budget: Option<Arc<budget::MemoryBudget>>,
+    pub(crate) streamed: bool,
+    /// Results found so far, counting any past `--limit`.
+    pub(crate) found: AtomicUsize,
+    pub(crate) printer: Printer,
+    pub(crate) skipped: Mutex<Vec<(PathBuf, String)>>,
+    /// Files and directories that couldn't be read, or files `--write`
+    /// couldn't rewrite.
+    pub(crate) errors: AtomicUsize,
+    /// Whether each error is reported on stderr as it happens, as only the
+    /// command line wants.
+    pub(crate) messages: bool,
+    pub(crate) stats: stats::Stats,
+    pub(crate) progress: Arc<MultiProgress>,
+}
+
+/// Compiles `--structural`, if given.
+#[cfg(feature = "syntax")]
+pub(crate) fn structural_target(args: &Args) -> Result<Option<SearchTarget>, Box<dyn Error>> {
+    match &args.structural {
+        Some(pattern) => syntax::StructuralPattern::new(pattern)
+            .map(|pattern| Some(SearchTarget::Structural(pattern)))
+            .map_err(|e| SearchError::new("InvalidPattern", e).into()),
+        None => Ok(None),
+    }
+}
+
+#[cfg(not(feature = "syntax"))]
+pub(crate) fn structural_target(_args: &Args) -> Result<Option<SearchTarget>, Box<dyn Error>> {
+    Ok(None)
+}
+
+/// Collapses a binary file's results into one saying that it matched, since
+/// its lines are mostly noise.
+pub(crate) fn binary_result(path: &Path, results: Vec<SearchResult>) -> Vec<SearchResult> {
+    match results.first() {
+        Some(first) => vec![SearchResult {
+            binary: true,
+            ..SearchResult::new(path, first.line_number, "", Vec::new())
+        }],
+        None => results,
+    }
+}
+
+#[cfg(feature = "syntax")]
+pub(crate) type ScopedTree = (syntax::Scope, syntax::SyntaxTree);
+
+/// Drops the matches of `line` (a slice of `contents`) outside the `--scope`,
+/// returning the rest with the syntax node kind of the first one.
+#[cfg(feature = "syntax")]
+pub(crate) fn filter_scope(
+    syntax: &Option<ScopedTree>,
+    contents: &str,
+    line: &str,
+    matches: Vec<(usize, usize)>,
+) -> (Vec<(usize, usize)>, Option<String>) {
+    match syntax {
+        Some((scope, tree)) if !matches.is_empty() => {
+            let line_start = line.as_ptr() as usize - contents.as_ptr() as usize;
+            tree.filter(*scope, line_start, matches)
+        }
+        _ => (matches, None),
+    }
+}
+
+impl Searcher {
+    /// Builds a searcher, failing if the pattern or any other option is
+    /// invalid.
+    pub fn new(args: &Args) -> Result<Self, Box<dyn Error>> {
+        let target = if args.files {
+            SearchTarget::Files
+        } else if let Some(bytes) = &args.bytes {
+            let needle = matcher::parse_byte_pattern(bytes)?;
+            SearchTarget::Bytes(Box::new(memmem::Finder::new(&needle).into_owned()))
+        } else if let Some(target) = structural_target(args)? {
+            target
+        } else if let Some(rules) = &args.rules {
+            SearchTarget::Rules(RuleSet::load(rules, &regex_options(args))?)
+        } else if let Some(near) = &args.near {
+            SearchTarget::Near {
+                first: build_matcher(args, std::slice::from_ref(&near.first))?,
+                second: build_matcher(args, std::slice::from_ref(&near.second))?,
+                distance: near.distance,
+            }
+        } else if args.entropy_scan {
+            SearchTarget::Lines(Box::new(EntropyMatcher::new(
+                args.entropy_threshold,
+                args.entropy_min_length,
+            )))
+        } else if let Some(name) = &args.identifier {
+            let matcher: Box<dyn Matcher> =
+                Box::new(IdentifierMatcher::new(name, &regex_options(args))?);
+            SearchTarget::Lines(if args.line_regexp {
+                Box::new(WholeLineMatcher::new(matcher))
+            } else {
+                matcher
+            })
+        } else if let Some(query) = &args.query {
+            SearchTarget::Lines(Box::new(QueryMatcher::new(
+                query,
+                args.query_scope,
+                |term| build_matcher(args, &[term.to_string()]),
+            )?))
+        } else {
+            let mut patterns = args.pattern.clone();
+            if let Some(pattern_file) = &args.pattern_file {
+                let contents = fs::read_to_string(pattern_file)?;
+                patterns.extend(
+                    contents
+                        .lines()
+                        .filter(|line| !line.is_empty())
+                        .map(str::to_string),
+                );
+            }
+            if patterns.is_empty()
+                && (!args.file_contains.is_empty() || !args.file_not_contains.is_empty())
+            {
+                SearchTarget::Files
+            } else if patterns.is_empty() {
+                return Err(Box::new(SearchError::new(
+                    "InvalidPattern",
+                    "no patterns given",
+                )));
+            } else {
+                SearchTarget::Lines(build_matcher(args, &patterns)?)
+            }
+        };
+
+        let file_predicates = if args.file_contains.is_empty() && args.file_not_contains.is_empty()
+        {
+            None
+        } else {
+            Some(FilePredicates::new(
+                &args.file_contains,
+                &args.file_not_contains,
+                args.regex,
+                &regex_options(args),
+            )?)
+        };
+
+        let types = types::matcher(&args.types, &args.type_add)?;
+
+        let extensions = args
+            .extensions
+            .split(',')
+            .map(|s| s.trim().trim_start_matches('.').to_lowercase())
+            .filter(|s| !s.is_empty())
+            .collect();
+
+        Ok(Searcher {
+            target,
+            extensions,
+            types,
+            globs: args.globs.clone(),
+            exclude: args.exclude.clone(),
+            exclude_dir: args.exclude_dir.clone(),
+            max_depth: args.max_depth,
+            max_filesize: args.max_filesize,
+            newer_than: args.newer_than,
+            older_than: args.older_than,
+            owner: args.owner,
+            perm: args.perm,
+            hidden: args.hidden,
+            no_ignore: args.no_ignore,
+            no_ignore_vcs: args.no_ignore_vcs,
+            no_ignore_parent: args.no_ignore_parent,
+            follow: args.follow,
+            one_file_system: args.one_file_system,
+            hardlink_dedup: !args.no_hardlink_dedup,
+            seen_links: Mutex::new(HashSet::new()),
+            path_pattern: args.path_pattern.clone(),
+            file_predicates,
+            mime: args.mime.clone(),
+            // Only whether there's a match matters when nothing but paths or
+            // an exit status is printed.
+            max_count: if args.files_with_matches || args.quiet {
+                Some(1)
+            } else {
+                args.max_count
+            },
+            line_range: args.line_range,
+            #[cfg(feature = "syntax")]
+            scope: args.scope,
+            match_paths: args.match_paths,
+            json: args.json,
+            results_only: args.results_only(),
+            replace: args.replace.clone(),
+            replace_nth: args.replace_nth.map(|nth| nth.get()),
+            replace_max: args.replace_max,
+            preserve_case: args.preserve_case,
+            replaced: AtomicUsize::new(0),
+            write: args.write,
+            rewritten: AtomicUsize::new(0),
+            diff_output: args.diff_output.clone(),
+            backup: Backup::from_args(args)?,
+            preserve_mtime: args.preserve_mtime,
+            patches: Mutex::new(Vec::new()),
+            recent_first: args.recent_first,
+            limit: if args.quiet { Some(1) } else { args.limit },
+            files_with_matches: args.files_with_matches,
+            count: args.count,
+            match_timeout: args.match_timeout,
+            file_timeout: args.file_timeout,
+            binary: args.binary,
+            text: args.text,
+            threads: args.threads,
+            mmap_min_size: (!args.no_mmap).then_some(args.mmap_threshold as u64),
+            stream_min_size: args.stream_threshold as u64,
+            io_backend: args.io_backend,
+            cancelled: Arc::new(AtomicBool::new(false)),
+            interrupted: Arc::new(AtomicBool::new(false)),
+            binary_skipped: AtomicUsize::new(0),
+            max_line_length: args.max_line_length,
+            long_lines: args.long_lines,
+            long_lines_seen: AtomicUsize::new(0),
+            results: Arc::new(Mutex::new(Vec::new())),
+            output: None,
+            content_budget: args
+                .max_memory
+                .map(|limit| budget::MemoryBudget::new(limit / 2)),
+            result_budget: args
+                .max_memory
+                .map(|limit| budget::MemoryBudget::new(limit / 2)),
+            streamed: false,
+            found: AtomicUsize::new(0),
+            printer: Printer::new(args),
+            skipped: Mutex::new(Vec::new()),
+            errors: AtomicUsize::new(0),
+            messages: false,
+            stats: stats::Stats::default(),
+            // Shown only by the command line, which sets a draw target.
+            progress: Arc::new(MultiProgress::with_draw_target(ProgressDrawTarget::hidden())),
+        })
+    }
+
+    /// Decides what to do with a line before matching it, given how many
+    /// results the file has produced so far. `opened` is when the file's
+    /// search began and `started` when matching it did.
+    pub(crate) fn line_step(
+        &self,
+        line_number: usize,
+        found: usize,
+        opened: Instant,
+        started: Instant,
+    ) -> Result<LineStep, Box<dyn Error>> {
+        if self.max_count.is_some_and(|max| found >= max) || self.is_cancelled() {
+            return Ok(LineStep::Stop);
+        }
+        if let Some(range) = &self.line_range {
+            if range.is_past(line_number) {
+                return Ok(LineStep::Stop);
+            }
+            if !range.contains(line_number) {
+                return Ok(LineStep::Skip);
+            }
+        }
+        if let Some(timeout) = self.match_timeout {
+            if started.elapsed() > timeout {
+                return Err(Box::new(SearchError::new(
+                    "Timeout",
+                    format!("matching took longer than {:?}", timeout),
+                )));
+            }
+        }
+        self.check_file_timeout(opened)?;
+        Ok(LineStep::Search)
+    }
+
+    /// Applies `--max-line-length`, returning the part of `line` to search or
+    /// `None` to skip it.
+    pub(crate) fn clip_line<'a>(&self, line: &'a str) -> Option<&'a str> {
+        let max = match self.max_line_length {
+            Some(max) if line.len() > max => max,
+            _ => return Some(line),
+        };
+        self.long_lines_seen.fetch_add(1, Ordering::Relaxed);
+        match self.long_lines {
+            LongLines::Skip => None,
+            LongLines::Truncate => {
+                let end = (0..=max).rev().find(|&end| line.is_char_boundary(end))?;
+                Some(&line[..end])
+            }
+        }
+    }
+
+    /// Fails once a file has taken longer than `--file-timeout` to search.
+    pub(crate) fn check_file_timeout(&self, opened: Instant) -> Result<(), Box<dyn Error>> {
+        match self.file_timeout {
+            Some(timeout) if opened.elapsed() > timeout => Err(Box::new(SearchError::new(
+                "Timeout",
+                format!("searching took longer than {:?}", timeout),
+            ))),
+            _ => Ok(()),
+        }
+    }
+
+    /// Parses the file when `--scope` is given. Files in unsupported
+    /// languages can't be scoped, so `Err` tells the caller to skip them.
+    #[cfg(feature = "syntax")]
+    pub(crate) fn syntax_tree(
+        &self,
+        path: &Path,
+        contents: &str,
+    ) -> Result<Option<ScopedTree>, ()> {
+        match self.scope {
+            None => Ok(None),
+            Some(scope) => syntax::SyntaxTree::parse(path, contents)
+                .map(|tree| Some((scope, tree)))
+                .ok_or(()),
+        }
+    }
+
+    /// Whether this search could take files' lines one at a time.
+    pub(crate) fn streamable(&self) -> bool {
+        matches!(&self.target, SearchTarget::Lines(matcher) if self.can_stream(matcher.as_ref()))
+    }
+
+    /// Picks how to read a file of `len` bytes; `streamable` says whether
+    /// the search could take its lines one at a time.
+    pub(crate) fn read_strategy(&self, len: u64, streamable: bool) -> ReadStrategy {
+        // A file that would take half of --max-memory is streamed too.
+        let over_budget = self
+            .content_budget
+            .as_ref()
+            .is_some_and(|budget| len as usize > budget.limit());
+        if streamable && (len >= self.stream_min_size || over_budget) {
+            ReadStrategy::Stream
+        } else if self.mmap_min_size.is_some_and(|min| len >= min) {
+            ReadStrategy::Map
+        } else {
+            ReadStrategy::Read
+        }
+    }
+
+    /// Reads a file whole, or maps it, giving up between chunks once it's
+    /// taken longer than `--file-timeout`.
+    pub(crate) fn read_file(
+        &self,
+        file: &fs::File,
+        strategy: ReadStrategy,
+        opened: Instant,
+    ) -> Result<FileContents, Box<dyn Error>> {
+        if strategy == ReadStrategy::Map {
+            // SAFETY: the map is only read, and dropped before this file's
+            // search returns. A file truncated by another process meanwhile
+            // can still fault, as with any tool that maps its input.
+            if let Ok(map) = unsafe { Mmap::map(file) } {
+                return Ok(FileContents::Mapped(map));
+            }
+        }
+        let mut bytes = buffer::PooledBuffer::take();
+        while std::io::Read::read_to_end(&mut std::io::Read::take(file, READ_CHUNK), &mut bytes)?
+            > 0
+        {
+            self.check_file_timeout(opened)?;
+        }
+        Ok(FileContents::Read(bytes))
+    }
+
+    pub(crate) fn path_result(&self, path: &Path) -> SearchResult {
+        SearchResult::new(path, 0, &path.display().to_string(), Vec::new())
+    }
+
+    /// Searches a directory tree, or a single file, and returns what was
+    /// found, best first when results are ranked. Every result is kept
+    /// until the search ends, so `--max-memory` only bounds file contents
+    /// here; [`Searcher::search_iter`] and [`Searcher::search_to`] hand
+    /// results over as they're found.
+    pub fn search(&self, path: &Path) -> Result<Vec<SearchResult>, Box<dyn Error>> {
+        self.clear();
+        self.search_dir(path)?;
+        Ok(self.take_results())
+    }
+
+    /// Takes the results found so far, best first when they're ranked.
+    pub(crate) fn take_results(&self) -> Vec<SearchResult> {
+        let mut results = std::mem::take(&mut *self.results.lock().unwrap());
+        if results.iter().any(|r| r.score.is_some()) {
+            results.sort_by_key(|r| std::cmp::Reverse(r.score));
+        }
+        results
+    }
+
+    /// Searches one file, whatever the filters would say about it.
+    pub fn search_file(&self, path: &Path) -> Result<Vec<SearchResult>, Box<dyn Error>> {
+        self.search_file_from(path, None)
+    }
+
+    /// Searches what's read from `input`, as stdin is for a path of `-`, as
+    /// one file reported as `<stdin>`.
+    pub(crate) fn search_input(&self, mut input: impl std::io::Read) -> Result<(), Box<dyn Error>> {
+        if self.write {
+            return Err(Box::new(SearchError::new(
+                "InvalidOptions",
+                "only files on disk can be rewritten",
+            )));
+        }
+        let path = Path::new(STDIN_PATH);
+        let opened = Instant::now();
+        let searched = match &self.target {
+            // Read a line at a time, so memory doesn't grow with what's
+            // piped in.
+            SearchTarget::Lines(matcher) if self.streamable() => {
+                self.search_stream(path, opened, input, matcher.as_ref())
+            }
+            _ => {
+                let mut bytes = Vec::new();
+                input.read_to_end(&mut bytes)?;
+                self.stats.record_read(bytes.len() as u64, opened.elapsed());
+                self.search_file_from(path, Some(bytes))
+            }
+        };
+        self.stats.record_file(opened.elapsed());
+        self.collect_file(path, searched);
+        Ok(())
+    }
+
+    /// Searches a file, using `prefetched` as its contents when they've
+    /// already been read.
+    pub(crate) fn search_file_from(
+        &self,
+        path: &Path,
+        prefetched: Option<Vec<u8>>,
+    ) -> Result<Vec<SearchResult>, Box<dyn Error>> {
+        let opened = Instant::now();
+        // Listing and path matching only need the contents for file
+        // predicates.
+        if self.file_predicates.is_none() && self.mime.is_empty() {
+            match &self.target {
+                SearchTarget::Lines(matcher) if self.match_paths => {
+                    return Ok(self.search_path(path, matcher.as_ref()));
+                }
+                SearchTarget::Files => return Ok(vec![self.path_result(path)]),
+                _ => {}
+            }
+        }
+
+        let mut _reservation = None;
+        let bytes = match prefetched {
+            // Already counted in the stats with the rest of its batch.
+            Some(bytes) => FileContents::Prefetched(bytes),
+            None => {
+                let file = fs::File::open(path)?;
+                let len = file.metadata()?.len();
+                let strategy = self.read_strategy(len, self.streamable());
+                if let (ReadStrategy::Stream, SearchTarget::Lines(matcher)) =
+                    (strategy, &self.target)
+                {
+                    // Reading and matching overlap, so it all counts as matching.
+                    self.stats.record_read(len, Duration::ZERO);
+                    return self.search_stream(path, opened, file, matcher.as_ref());
+                }
+
+                // Held until this file has been searched.
+                _reservation = self
+                    .content_budget
+                    .as_ref()
+                    .map(|budget| budget.reserve(len as usize));
+                let bytes = self.read_file(&file, strategy, opened)?;
+                self.stats.record_read(bytes.len() as u64, opened.elapsed());
+                bytes
+            }
+        };
+        self.search_contents(path, opened, &bytes)
+    }
+
+    /// Searches a file's contents once they've been read.
+    pub(crate) fn search_contents(
+        &self,
+        path: &Path,
+        opened: Instant,
+        bytes: &[u8],
+    ) -> Result<Vec<SearchResult>, Box<dyn Error>> {
+        self.check_file_timeout(opened)?;
+        if !self.mime.is_empty() {
+            let mime = mime::sniff(bytes);
+            if !self.mime.iter().any(|pattern| mime::matches(pattern, mime)) {
+                return Ok(Vec::new());
+            }
+        }
+        if let Some(predicates) = &self.file_predicates {
+            if !predicates.matches(bytes) {
+                return Ok(Vec::new());
+            }
+        }
+        match &self.target {
+            SearchTarget::Bytes(finder) => return self.search_bytes(path, opened, bytes, finder),
+            SearchTarget::Files => return Ok(vec![self.path_result(path)]),
+            SearchTarget::Lines(matcher) if self.match_paths => {
+                return Ok(self.search_path(path, matcher.as_ref()));
+            }
+            _ => {}
+        }
+
+        let binary = !self.text && memchr::memchr(0, bytes).is_some();
+        if binary && !self.binary {
+            log::info!(
+                "skipping {}: binary (--binary or -a searches it)",
+                path.display()
+            );
+            self.binary_skipped.fetch_add(1, Ordering::Relaxed);
+            return Ok(Vec::new());
+        }
+        // Valid UTF-8 is searched in place, without copying.
+        let contents: Cow<str> = String::from_utf8_lossy(bytes);
+
+        let mut results = match &self.target {
+            SearchTarget::Lines(matcher) => {
+                self.search_lines(path, opened, &contents, matcher.as_ref())
+            }
+            SearchTarget::Rules(rules) => self.search_rules(path, opened, &contents, rules),
+            SearchTarget::Near {
+                first,
+                second,
+                distance,
+            } => self.search_near(
+                path,
+                opened,
+                &contents,
+                first.as_ref(),
+                second.as_ref(),
+                *distance,
+            ),
+            #[cfg(feature = "syntax")]
+            SearchTarget::Structural(pattern) => {
+                self.search_structural(path, opened, &contents, pattern)
+            }
+            SearchTarget::Bytes(_) | SearchTarget::Files => unreachable!(),
+        }?;
+
+        if let (Some(text), SearchTarget::Lines(matcher)) = (&self.replace, &self.target) {
+            self.replace_in(&mut results, text, matcher.as_ref());
+        }
+
+        if (self.printer.diff || self.diff_output.is_some()) && !binary {
+            let changes: Vec<_> = results
+                .iter()
+                .filter_map(|result| {
+                    Some(diff::Change {
+                        line_number: result.line_number,
+                        searched: result.line.len(),
+                        replaced: &result.replacement.as_ref()?.line,
+                    })
+                })
+                .collect();
+            let hunks = diff::hunks(&contents, &changes, diff::CONTEXT);
+            if !hunks.is_empty() {
+                if self.diff_output.is_some() {
+                    let cwd = std::env::current_dir()?;
+                    match diff::relative_to(path, &cwd) {
+                        Some(relative) => {
+                            let patch = diff::patch(&relative, &hunks);
+                            self.patches
+                                .lock()
+                                .unwrap()
+                                .push((path.to_path_buf(), patch));
+                        }
+                        None => {
+                            self.errors.fetch_add(1, Ordering::Relaxed);
+                            self.skip(
+                                path,
+                                "is outside the current directory, so left out of the patch"
+                                    .to_string(),
+                            );
+                        }
+                    }
+                }
+                if let Some(first) = results.first_mut().filter(|_| self.printer.diff) {
+                    first.diff = Some(diff::render(path, &hunks));
+                }
+            }
+        }
+
+        if self.write && !results.is_empty() {
+            let problem = match &contents {
+                // Writing back a lossy copy would corrupt the rest of it.
+                Cow::Owned(_) => Some("not valid UTF-8, so left unchanged".to_string()),
+                Cow::Borrowed(contents) => self.rewrite(path, contents, &results).err().map(|e| {
+                    self.errors.fetch_add(1, Ordering::Relaxed);
+                    format!("couldn't be rewritten: {}", e)
+                }),
+            };
+            match problem {
+                Some(problem) => self.skip(path, problem),
+                None => {
+                    self.rewritten.fetch_add(1, Ordering::Relaxed);
+                }
+            }
+        }
+
+        Ok(if binary {
+            binary_result(path, results)
+        } else {
+            results
+        })
+    }
+
+    /// Reads up to and including the next newline into `line`, as
+    /// `read_until` does, checking `--file-timeout` after each read so one
+    /// huge line can't hold the search past it.
+    fn read_line(
+        &self,
+        reader: &mut impl std::io::BufRead,
+        line: &mut Vec<u8>,
+        opened: Instant,
+    ) -> Result<usize, Box<dyn Error>> {
+        let start = line.len();
+        loop {
+            let available = match reader.fill_buf() {
+                Ok(available) => available,
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e.into()),
+            };
+            if available.is_empty() {
+                break;
+            }
+            let (taken, done) = match memchr::memchr(b'\n', available) {
+                Some(end) => (end + 1, true),
+                None => (available.len(), false),
+            };
+            line.extend_from_slice(&available[..taken]);
+            reader.consume(taken);
+            if done {
+                break;
+            }
+            self.check_file_timeout(opened)?;
+        }
+        Ok(line.len() - start)
+    }
+
+    /// Whether lines can be searched as they're read, without the whole file.
+    pub(crate) fn can_stream(&self, matcher: &dyn Matcher) -> bool {
+        #[cfg(feature = "syntax")]
+        let scoped = self.scope.is_some();
+        #[cfg(not(feature = "syntax"))]
+        let scoped = false;
+
+        !self.match_paths
+            && self.file_predicates.is_none()
+            && self.mime.is_empty()
+            && !scoped
+            && !matcher.needs_whole_file()
+            && self.replace.is_none()
+    }
+
+    /// Searches a huge file through a buffered reader, one line at a time, so
+    /// memory use doesn't grow with the file.
+    pub(crate) fn search_stream(
+        &self,
+        path: &Path,
+        opened: Instant,
+        file: impl std::io::Read,
+        matcher: &dyn Matcher,
+    ) -> Result<Vec<SearchResult>, Box<dyn Error>> {
+        let started = Instant::now();
+        let mut reader = std::io::BufReader::with_capacity(256 * 1024, file);
+        let mut file_results = Vec::new();
+        let mut binary = false;
+        let mut buffer = buffer::PooledBuffer::take();
+        let mut line_number = 0;
+        loop {
+            buffer.clear();
+            if self.read_line(&mut reader, &mut buffer, opened)? == 0 {
+                break;
+            }
+            line_number += 1;
+            if !self.text && !binary && memchr::memchr(0, &buffer).is_some() {
+                if !self.binary {
+                    log::info!(
+                        "skipping {}: binary (--binary or -a searches it)",
+                        path.display()
+                    );
+                    self.binary_skipped.fetch_add(1, Ordering::Relaxed);
+                    return Ok(Vec::new());
+                }
+                binary = true;
+            }
+            // A binary file is only reported once, so its first match will do.
+            if binary && !file_results.is_empty() {
+                break;
+            }
+            match self.line_step(line_number, file_results.len(), opened, started)? {
+                LineStep::Stop => break,
+                LineStep::Skip => continue,
+                LineStep::Search => {}
+            }
+
+            // Line endings are dropped as `str::lines` drops them.
+            if buffer.last() == Some(&b'\n') {
+                buffer.pop();
+                if buffer.last() == Some(&b'\r') {
+                    buffer.pop();
+                }
+            }
+            let line = String::from_utf8_lossy(&buffer);
+            let Some(line) = self.clip_line(&line) else {
+                continue;
+            };
+            let matches = matcher.try_find_matches(line)?;
+            if !matches.is_empty() {
+                file_results.push(SearchResult {
+                    score: matcher.score(line),
+                    edit_distance: matcher.edit_distance(line),
+                    ..SearchResult::new(path, line_number, line, matches)
+                });
+            }
+        }
+
+        Ok(if binary {
+            binary_result(path, file_results)
+        } else {
+            file_results
+        })
+    }
+
+    /// Matches a `--match-paths` pattern against the path itself.
+    pub(crate) fn search_path(&self, path: &Path, matcher: &dyn Matcher) -> Vec<SearchResult> {
+        let path_text = path.display().to_string();
+        let matches = matcher.find_matches(&path_text);
+        if matches.is_empty() {
+            return Vec::new();
+        }
+        vec![SearchResult {
+            score: matcher.score(&path_text),
+            ..SearchResult::new(path, 0, &path_text, matches)
+        }]
+    }
+
+    /// Reports each line accepted by a matcher.
+    pub(crate) fn search_lines(
+        &self,
+        path: &Path,
+        opened: Instant,
+        contents: &str,
+        matcher: &dyn Matcher,
+    ) -> Result<Vec<SearchResult>, Box<dyn Error>> {
+        let started = Instant::now();
+        let mut file_results = Vec::new();
+        if !matcher.matches_file(contents) {
+            return Ok(file_results);
+        }
+        #[cfg(feature = "syntax")]
+        let syntax = match self.syntax_tree(path, contents) {
+            Ok(syntax) => syntax,
+            Err(()) => return Ok(file_results),
+        };
+
+        for (index, line) in contents.lines().enumerate() {
+            match self.line_step(index + 1, file_results.len(), opened, started)? {
+                LineStep::Stop => break,
+                LineStep::Skip => continue,
+                LineStep::Search => {}
+            }
+            let Some(line) = self.clip_line(line) else {
+                continue;
+            };
+
+            let matches = matcher.try_find_matches(line)?;
+            #[cfg(feature = "syntax")]
+            let (matches, syntax_node) = filter_scope(&syntax, contents, line, matches);
+            #[cfg(not(feature = "syntax"))]
+            let syntax_node = None;
+
+            if !matches.is_empty() {
+                file_results.push(SearchResult {
+                    score: matcher.score(line),
+                    edit_distance: matcher.edit_distance(line),
+                    syntax_node,
+                    ..SearchResult::new(path, index + 1, line, matches)
+                });
+            }
+        }
+
+        Ok(file_results)
+    }
+
+    /// Reports one result per rule firing on each line, tagged with the rule.
+    pub(crate) fn search_rules(
+        &self,
+        path: &Path,
+        opened: Instant,
+        contents: &str,
+        rules: &RuleSet,
+    ) -> Result<Vec<SearchResult>, Box<dyn Error>> {
+        let started = Instant::now();
+        let mut file_results = Vec::new();
+        #[cfg(feature = "syntax")]
+        let syntax = match self.syntax_tree(path, contents) {
+            Ok(syntax) => syntax,
+            Err(()) => return Ok(file_results),
+        };
+
+        for (index, line) in contents.lines().enumerate() {
+            match self.line_step(index + 1, file_results.len(), opened, started)? {
+                LineStep::Stop => break,
+                LineStep::Skip => continue,
+                LineStep::Search => {}
+            }
+            let Some(line) = self.clip_line(line) else {
+                continue;
+            };
+
+            for (rule, matches) in rules.find_matches(line) {
+                #[cfg(feature = "syntax")]
+                let (matches, syntax_node) = filter_scope(&syntax, contents, line, matches);
+                #[cfg(not(feature = "syntax"))]
+                let syntax_node = None;
+                if matches.is_empty() {
+                    continue;
+                }
+
+                file_results.push(SearchResult {
+                    rule: Some(rule),
+                    syntax_node,
+                    ..SearchResult::new(path, index + 1, line, matches)
+                });
+            }
+        }
+
+        Ok(file_results)
+    }
+
+    /// Finds every occurrence of a byte sequence, reporting each with a hex
+    /// dump of the surrounding bytes in place of a line.
+    pub(crate) fn search_bytes(
+        &self,
+        path: &Path,
+        opened: Instant,
+        contents: &[u8],
+        finder: &memmem::Finder,
+    ) -> Result<Vec<SearchResult>, Box<dyn Error>> {
+        let mut file_results = Vec::new();
+        let mut line_number = 1;
+        let mut counted_to = 0;
+
+        let started = Instant::now();
+
+        for offset in finder.find_iter(contents) {
+            line_number += memchr::memchr_iter(b'\n', &contents[counted_to..offset]).count();
+            counted_to = offset;
+            match self.line_step(line_number, file_results.len(), opened, started)? {
+                LineStep::Stop => break,
+                LineStep::Skip => continue,
+                LineStep::Search => {}
+            }
+
+            let context_start = offset.saturating_sub(BYTE_CONTEXT);
+            let match_end = offset + finder.needle().len();
+            let context_end = (match_end + BYTE_CONTEXT).min(contents.len());
+            let hex = |bytes: &[u8]| {
+                bytes
+                    .iter()
+                    .map(|b| format!("{:02x}", b))
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            };
+
+            let before = hex(&contents[context_start..offset]);
+            let matched = hex(&contents[offset..match_end]);
+            let after = hex(&contents[match_end..context_end]);
+            let start = if before.is_empty() {
+                0
+            } else {
+                before.len() + 1
+            };
+            let line = [before, matched, after]
+                .into_iter()
+                .filter(|part| !part.is_empty())
+                .collect::<Vec<_>>()
+                .join(" ");
+            let end = start + 3 * finder.needle().len() - 1;
+
+            file_results.push(SearchResult {
+                byte_offset: Some(offset),
+                ..SearchResult::new(path, line_number, &line, vec![(start, end)])
+            });
+        }
+
+        Ok(file_results)
+    }
+
+    /// Reports each line where one pattern matches and the other matched on
+    /// the same line or on one of the previous `distance` lines. The nearest
+    /// earlier partner is attached as the related line.
+    pub(crate) fn search_near(
+        &self,
+        path: &Path,
+        opened: Instant,
+        contents: &str,
+        first: &dyn Matcher,
+        second: &dyn Matcher,
+        distance: usize,
+    ) -> Result<Vec<SearchResult>, Box<dyn Error>> {
+        let started = Instant::now();
+        let mut file_results = Vec::new();
+        // Most recent line matching each pattern.
+        let mut last_first: Option<RelatedLine> = None;
+        let mut last_second: Option<RelatedLine> = None;
+
+        for (index, line) in contents.lines().enumerate() {
+            let line_number = index + 1;
+            match self.line_step(line_number, file_results.len(), opened, started)? {
+                LineStep::Stop => break,
+                LineStep::Skip => continue,
+                LineStep::Search => {}
+            }
+            let Some(line) = self.clip_line(line) else {
+                continue;
+            };
+
+            let first_matches = first.try_find_matches(line)?;
+            let second_matches = second.try_find_matches(line)?;
+            let in_window = |related: &&RelatedLine| line_number - related.line_number <= distance;
+
+            let result = if !first_matches.is_empty() && !second_matches.is_empty() {
+                let mut matches = [first_matches.clone(), second_matches.clone()].concat();
+                matches.sort_unstable();
+                matches.dedup();
+                Some((matches, None))
+            } else if !first_matches.is_empty() {
+                last_second
+                    .as_ref()
+                    .filter(in_window)
+                    .map(|partner| (first_matches.clone(), Some(partner.clone())))
+            } else if !second_matches.is_empty() {
+                last_first
+                    .as_ref()
+                    .filter(in_window)
+                    .map(|partner| (second_matches.clone(), Some(partner.clone())))
+            } else {
+                None
+            };
+
+            if let Some((matches, partner)) = result {
+                file_results.push(SearchResult {
+                    related: partner,
+                    ..SearchResult::new(path, line_number, line, matches)
+                });
+            }
+
+            if !first_matches.is_empty() {
+                last_first = Some(RelatedLine {
+                    line_number,
+                    line: line.to_string(),
+                    matches: first_matches,
+                });
+            }
+            if !second_matches.is_empty() {
+                last_second = Some(RelatedLine {
+                    line_number,
+                    line: line.to_string(),
+                    matches: second_matches,
+                });
+            }
+        }
+
+        Ok(file_results)
+    }
+
+    /// Reports each node matching a structural pattern on the line where it
+    /// starts, with the match clipped to that line. Files in languages the
+    /// pattern can't be parsed in are skipped.
+    #[cfg(feature = "syntax")]
+    pub(crate) fn search_structural(
+        &self,
+        path: &Path,
+        opened: Instant,
+        contents: &str,
+        pattern: &syntax::StructuralPattern,
+    ) -> Result<Vec<SearchResult>, Box<dyn Error>> {
+        let started = Instant::now();
+        let mut file_results = Vec::new();
+        let Some(found) = pattern.find_matches(path, contents) else {
+            return Ok(file_results);
+        };
+
+        let line_starts: Vec<usize> = std::iter::once(0)
+            .chain(memchr::memchr_iter(b'\n', contents.as_bytes()).map(|i| i + 1))
+            .collect();
+        for found in found {
+            let index = line_starts.partition_point(|&start| start <= found.start) - 1;
+            match self.line_step(index + 1, file_results.len(), opened, started)? {
+                LineStep::Stop => break,
+                LineStep::Skip => continue,
+                LineStep::Search => {}
+            }
+
+            let line_start = line_starts[index];
+            let line = contents[line_start..].lines().next().unwrap_or_default();
+            let end = found.end.min(line_start + line.len());
+            file_results.push(SearchResult {
+                metavariables: found.bindings,
+                ..SearchResult::new(
+                    path,
+                    index + 1,
+                    line,
+                    vec![(found.start - line_start, end - line_start)],
+                )
+            });
+        }
+
+        Ok(file_results)
+    }
+
+    /// Adds a file's results to the shared list, cancelling the remaining
+    /// work once `--limit` is reached.
+    pub(crate) fn collect(&self, mut results: Vec<SearchResult>) {
+        let before = self.found.fetch_add(results.len(), Ordering::Relaxed);
+        if let Some(limit) = self.limit {
+            results.truncate(limit.saturating_sub(before));
+            if before + results.len() >= limit {
+                self.cancelled.store(true, Ordering::Relaxed);
+            }
+        }
+        if results.is_empty() {
+            return;
+        }
+        if self.output.is_none() {
+            self.results.lock().unwrap().extend(results);
+            return;
+        }
+        // A file's results go as one message so they print together.
+        let reservation = self
+            .result_budget
+            .as_ref()
+            .map(|budget| budget.reserve(results.iter().map(SearchResult::approximate_size).sum()));
+        self.send(Event::Results(ResultBatch {
+            results,
+            _reservation: reservation,
+        }));
+    }
+
+    /// Sends `event` to the `stream_to` output, if there is one.
+    pub(crate) fn send(&self, event: Event) {
+        // Sending only fails once nothing is reading any more, so there's no
+        // point searching on.
+        if let Some(output) = &self.output {
+            if output.send(event).is_err() {
+                self.cancelled.store(true, Ordering::Relaxed);
+            }
+        }
+    }
+
+    /// Records a file left out of the search, and why.
+    pub(crate) fn skip(&self, path: &Path, reason: String) {
+        self.send(Event::FileError(path.to_path_buf(), reason.clone()));
+        self.skipped
+            .lock()
+            .unwrap()
+            .push((path.to_path_buf(), reason));
+    }
+
+    /// Counts something that couldn't be searched, saying what on stderr
+    /// when `messages` is set.
+    pub(crate) fn error(&self, message: std::fmt::Arguments) {
+        self.errors.fetch_add(1, Ordering::Relaxed);
+        if self.messages {
+            self.progress
+                .suspend(|| eprintln!("rusty-scout: {}", message));
+        } else {
+            log::warn!("{}", message);
+        }
+    }
+
+    /// Sends results to `output` as they're found instead of keeping them,
+    /// so they can be printed while the search runs.
+    pub(crate) fn stream_to(&mut self, output: SyncSender<Event>) {
+        self.output = Some(output);
+        self.streamed = true;
+    }
+
+    /// Closes the stream from `stream_to`, letting its receiver finish.
+    pub(crate) fn close_output(&mut self) {
+        self.output = None;
+    }
+
+    /// Forgets everything the last search found, so the searcher can run
+    /// again as if it had just been built.
+    #[cfg(unix)]
+    pub(crate) fn reset(&mut self) {
+        self.close_output();
+        self.streamed = false;
+        self.clear();
+    }
+
+    /// Forgets what the last search found and counted, so each search
+    /// starts afresh: the limit, hard links and errors are its own.
+    pub(crate) fn clear(&self) {
+        self.found.store(0, Ordering::Relaxed);
+        self.results.lock().unwrap().clear();
+        self.skipped.lock().unwrap().clear();
+        self.errors.store(0, Ordering::Relaxed);
+        self.seen_links.lock().unwrap().clear();
+        self.patches.lock().unwrap().clear();
+        self.replaced.store(0, Ordering::Relaxed);
+        self.rewritten.store(0, Ordering::Relaxed);
+        self.binary_skipped.store(0, Ordering::Relaxed);
+        self.long_lines_seen.store(0, Ordering::Relaxed);
+        self.cancelled.store(false, Ordering::Relaxed);
+        self.interrupted.store(false, Ordering::Relaxed);
+        self.stats.clear();
+    }
+
+    pub(crate) fn found(&self) -> usize {
+        let found = self.found.load(Ordering::Relaxed);
+        self.limit.map_or(found, |limit| found.min(limit))
+    }
+
+    pub(crate) fn errors(&self) -> usize {
+        self.errors.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed) || self.was_interrupted()
+    }
+
+    pub(crate) fn was_interrupted(&self) -> bool {
+        self.interrupted.load(Ordering::Relaxed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::{parse_args, NearSpec};
+    use std::fs::File;
+    use std::io::Write;
+    use std::sync::mpsc;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_file_search() -> Result<(), Box<dyn Error>> {
+        let dir = tempdir()?;
+        let file_path = dir.path().join("test.txt");
+        let mut file = File::create(&file_path)?;
+        writeln!(file, "Hello, world!\nThis is a test file.\nHello again!")?;
+
+        let args = parse_args(&["-p", "Hello"]);
+
+        let searcher = Searcher::new(&args)?;
+        let results = searcher.search_file(&file_path)?;
+
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].line_number, 1);
+        assert_eq!(results[1].line_number, 3);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_case_insensitive_search() -> Result<(), Box<dyn Error>> {
+        let dir = tempdir()?;
+        let file_path = dir.path().join("test.txt");
+        let mut file = File::create(&file_path)?;
+        writeln!(file, "Hello, HELLO, hello")?;
+
+        let args = parse_args(&["-p", "hello", "-i"]);
+
+        let searcher = Searcher::new(&args)?;
+        let results = searcher.search_file(&file_path)?;
+
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].matches.len(), 3);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_multiple_literal_patterns() -> Result<(), Box<dyn Error>> {
+        let dir = tempdir()?;
+        let file_path = dir.path().join("test.txt");
+        let mut file = File::create(&file_path)?;
+        writeln!(file, "alpha beta\ngamma\nBETA delta")?;
+
+        let args = parse_args(&["-p", "beta", "-p", "delta", "-i"]);
+
+        let searcher = Searcher::new(&args)?;
+        let results = searcher.search_file(&file_path)?;
+
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].matches, vec![(6, 10)]);
+        assert_eq!(results[1].matches, vec![(0, 4), (5, 10)]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_pattern_file() -> Result<(), Box<dyn Error>> {
+        let dir = tempdir()?;
+        let patterns_path = dir.path().join("patterns.txt");
+        fs::write(&patterns_path, "foo\n\nbar\n")?;
+        let file_path = dir.path().join("test.txt");
+        fs::write(&file_path, "foo\nbaz\nbar\n")?;
+
+        let args = parse_args(&["--pattern-file", patterns_path.to_str().unwrap()]);
+
+        let searcher = Searcher::new(&args)?;
+        let results = searcher.search_file(&file_path)?;
+
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[1].line_number, 3);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_query_search() -> Result<(), Box<dyn Error>> {
+        let dir = tempdir()?;
+        let file_path = dir.path().join("test.rs");
+        fs::write(&file_path, "unsafe fn a() {}\n// unsafe test\nfn b() {}\n")?;
+
+        let args = parse_args(&["--query", "unsafe AND NOT test"]);
+        let searcher = Searcher::new(&args)?;
+        let results = searcher.search_file(&file_path)?;
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].line_number, 1);
+
+        let args = parse_args(&["--query", "UNSAFE NOT test", "-i", "--query-scope", "file"]);
+        let searcher = Searcher::new(&args)?;
+        assert!(searcher.search_file(&file_path)?.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_max_count() -> Result<(), Box<dyn Error>> {
+        let dir = tempdir()?;
+        let file_path = dir.path().join("test.log");
+        fs::write(&file_path, "error 1\nok\nerror 2\nerror 3\n")?;
+
+        let args = parse_args(&["-p", "error", "-m", "2"]);
+        let searcher = Searcher::new(&args)?;
+        let results = searcher.search_file(&file_path)?;
+
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[1].line_number, 3);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_result_limit() -> Result<(), Box<dyn Error>> {
+        let dir = tempdir()?;
+        for name in ["a.txt", "b.txt", "c.txt"] {
+            fs::write(dir.path().join(name), "match\nmatch\n")?;
+        }
+
+        let args = parse_args(&["-p", "match", "--limit", "3"]);
+        let searcher = Searcher::new(&args)?;
+        searcher.search_dir(dir.path())?;
+
+        assert_eq!(searcher.results.lock().unwrap().len(), 3);
+        assert!(searcher.is_cancelled());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_line_regexp() -> Result<(), Box<dyn Error>> {
+        let dir = tempdir()?;
+        let file_path = dir.path().join("hosts.conf");
+        fs::write(&file_path, "localhost\nlocalhost.localdomain\nLOCALHOST\n")?;
+
+        let args = parse_args(&["-p", "localhost", "-x", "-i"]);
+        let searcher = Searcher::new(&args)?;
+        let results = searcher.search_file(&file_path)?;
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[1].line_number, 3);
+
+        let args = parse_args(&["-p", "local[a-z]+", "-E", "-x"]);
+        let searcher = Searcher::new(&args)?;
+        let results = searcher.search_file(&file_path)?;
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].matches, vec![(0, 9)]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_unicode_case_folding() -> Result<(), Box<dyn Error>> {
+        let dir = tempdir()?;
+        let file_path = dir.path().join("cities.txt");
+        fs::write(&file_path, "STRASSE\nİstanbul\ncafe\u{301}\n")?;
+
+        let args = parse_args(&["-p", "straße", "-i", "--unicode"]);
+        let searcher = Searcher::new(&args)?;
+        assert_eq!(searcher.search_file(&file_path)?.len(), 1);
+
+        let args = parse_args(&["-p", "café", "--unicode"]);
+        let searcher = Searcher::new(&args)?;
+        let results = searcher.search_file(&file_path)?;
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].matches, vec![(0, 6)]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_byte_search() -> Result<(), Box<dyn Error>> {
+        let dir = tempdir()?;
+        let file_path = dir.path().join("blob.bin");
+        fs::write(&file_path, b"\x00\x01\n\xde\xad\xbe\xef\x02")?;
+
+        let args = parse_args(&["--bytes", "DE AD BE EF"]);
+        let searcher = Searcher::new(&args)?;
+        let results = searcher.search_file(&file_path)?;
+
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].byte_offset, Some(3));
+        assert_eq!(results[0].line_number, 2);
+        assert_eq!(results[0].line, "00 01 0a de ad be ef 02");
+        assert_eq!(results[0].matches, vec![(9, 20)]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_match_timeout_skips_file() -> Result<(), Box<dyn Error>> {
+        let dir = tempdir()?;
+        fs::write(dir.path().join("big.txt"), "needle\n".repeat(1000))?;
+
+        let args = parse_args(&["-p", "needle", "--match-timeout", "0ms"]);
+        let searcher = Searcher::new(&args)?;
+        searcher.search_dir(dir.path())?;
+
+        assert!(searcher.results.lock().unwrap().is_empty());
+        assert_eq!(searcher.skipped.lock().unwrap().len(), 1);
+
+        Ok(())
+    }
+
+    #[cfg(feature = "fancy-regex")]
+    #[test]
+    fn test_backtrack_limit_skips_file() -> Result<(), Box<dyn Error>> {
+        let dir = tempdir()?;
+        fs::write(
+            dir.path().join("a.txt"),
+            format!("aab\n{}\n", "a".repeat(30)),
+        )?;
+
+        let args = parse_args(&["-p", r"((?=a)a+)+b", "--regex", "--engine", "fancy"]);
+        let searcher = Searcher::new(&args)?;
+        searcher.search_dir(dir.path())?;
+
+        // The line it gave up on isn't passed over as not matching.
+        let skipped = searcher.skipped.lock().unwrap();
+        assert_eq!(skipped.len(), 1);
+        assert!(skipped[0].1.contains("backtrack limit"), "{:?}", skipped);
+        assert!(searcher.results.lock().unwrap().is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_line_range() -> Result<(), Box<dyn Error>> {
+        assert_eq!(
+            "10:".parse::<LineRange>(),
+            Ok(LineRange {
+                start: 10,
+                end: None
+            })
+        );
+        assert!("5:2".parse::<LineRange>().is_err());
+        assert!("0:2".parse::<LineRange>().is_err());
+
+        let dir = tempdir()?;
+        let file_path = dir.path().join("generated.rs");
+        fs::write(&file_path, "item\n".repeat(10))?;
+
+        let args = parse_args(&["-p", "item", "--line-range", "3:5"]);
+        let searcher = Searcher::new(&args)?;
+        let results = searcher.search_file(&file_path)?;
+        let lines: Vec<usize> = results.iter().map(|r| r.line_number).collect();
+        assert_eq!(lines, vec![3, 4, 5]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_near_search() -> Result<(), Box<dyn Error>> {
+        assert!("foo,5".parse::<NearSpec>().is_err());
+
+        let dir = tempdir()?;
+        let file_path = dir.path().join("test.rs");
+        fs::write(
+            &file_path,
+            "lock()\n\nunsafe {}\n\n\n\n\nunsafe {}\nlock() unsafe\n",
+        )?;
+
+        let args = parse_args(&["--near", "lock,unsafe,2"]);
+        let searcher = Searcher::new(&args)?;
+        let results = searcher.search_file(&file_path)?;
+
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].line_number, 3);
+        assert_eq!(results[0].related.as_ref().unwrap().line_number, 1);
+        assert_eq!(results[1].line_number, 9);
+        assert!(results[1].related.is_none());
+        assert_eq!(results[1].matches, vec![(0, 4), (7, 13)]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_rules_search() -> Result<(), Box<dyn Error>> {
+        let dir = tempdir()?;
+        let rules_path = dir.path().join("secrets.toml");
+        fs::write(
+            &rules_path,
+            r#"
+[[rules]]
+name = "password"
+regex = 'password\s*='
+severity = "error"
+
+[[rules]]
+name = "todo"
+regex = "TODO"
+"#,
+        )?;
+        let file_path = dir.path().join("config.py");
+        fs::write(&file_path, "password = 'hunter2'  # TODO\nok = 1\n")?;
+
+        let args = parse_args(&["--rules", rules_path.to_str().unwrap()]);
+        let searcher = Searcher::new(&args)?;
+        let results = searcher.search_file(&file_path)?;
+
+        let names: Vec<&str> = results
+            .iter()
+            .map(|r| r.rule.as_ref().unwrap().name.as_str())
+            .collect();
+        assert_eq!(names, vec!["password", "todo"]);
+        assert_eq!(results[0].matches, vec![(0, 10)]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_regex_ignore_case() -> Result<(), Box<dyn Error>> {
+        let dir = tempdir()?;
+        let file_path = dir.path().join("app.log");
+        fs::write(&file_path, "ERROR: disk full\nerror: retry\nok\n")?;
+
+        let args = parse_args(&["-p", "^error:", "-E"]);
+        let searcher = Searcher::new(&args)?;
+        assert_eq!(searcher.search_file(&file_path)?.len(), 1);
+
+        let args = parse_args(&["-p", "^error:", "-E", "-i"]);
+        let searcher = Searcher::new(&args)?;
+        let results = searcher.search_file(&file_path)?;
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].matches, vec![(0, 6)]);
+
+        let args = parse_args(&["-p", "ERR*:", "--glob-pattern", "-i"]);
+        let searcher = Searcher::new(&args)?;
+        assert_eq!(searcher.search_file(&file_path)?.len(), 2);
+
+        Ok(())
+    }
+
+    #[cfg(feature = "syntax")]
+    #[test]
+    fn test_scope_search() -> Result<(), Box<dyn Error>> {
+        let dir = tempdir()?;
+        let file_path = dir.path().join("lib.py");
+        fs::write(&file_path, "# TODO: remove\nTODO = 'TODO'\n")?;
+        let text_path = dir.path().join("notes.txt");
+        fs::write(&text_path, "TODO\n")?;
+
+        let args = parse_args(&["-p", "TODO", "--scope", "comments"]);
+        let searcher = Searcher::new(&args)?;
+        let results = searcher.search_file(&file_path)?;
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].syntax_node.as_deref(), Some("comment"));
+        assert!(searcher.search_file(&text_path)?.is_empty());
+
+        let args = parse_args(&["-p", "TODO", "--scope", "code"]);
+        let searcher = Searcher::new(&args)?;
+        let results = searcher.search_file(&file_path)?;
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].matches, vec![(0, 4)]);
+
+        Ok(())
+    }
+
+    #[cfg(feature = "syntax")]
+    #[test]
+    fn test_structural_search() -> Result<(), Box<dyn Error>> {
+        let dir = tempdir()?;
+        fs::write(
+            dir.path().join("lib.rs"),
+            "fn f() {\n    let a = x.unwrap();\n    // y.unwrap()\n    let b = opts\n        .get(0)\n        .unwrap();\n}\n",
+        )?;
+        fs::write(dir.path().join("notes.txt"), "x.unwrap()\n")?;
+
+        let args = parse_args(&[
+            "--structural",
+            "$X.unwrap()",
+            "-d",
+            dir.path().to_str().unwrap(),
+        ]);
+        let searcher = Searcher::new(&args)?;
+        searcher.search_dir(dir.path())?;
+
+        let mut results = searcher.results.lock().unwrap();
+        results.sort_by_key(|r| r.line_number);
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].line_number, 2);
+        assert_eq!(results[0].matches, vec![(12, 22)]);
+        assert_eq!(results[1].line_number, 4);
+        assert_eq!(
+            results[1].metavariables,
+            vec![("X".to_string(), "opts\n        .get(0)".to_string())]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_identifier_search() -> Result<(), Box<dyn Error>> {
+        let dir = tempdir()?;
+        fs::write(
+            dir.path().join("user.rs"),
+            "let valid = true;\nlet id = user.id;\nlet user_id = 1;\n",
+        )?;
+
+        let args = parse_args(&["--identifier", "id", "-d", dir.path().to_str().unwrap()]);
+        let searcher = Searcher::new(&args)?;
+        searcher.search_dir(dir.path())?;
+
+        let results = searcher.results.lock().unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].line_number, 2);
+        assert_eq!(results[0].matches, vec![(4, 6), (14, 16)]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_match_paths() -> Result<(), Box<dyn Error>> {
+        let dir = tempdir()?;
+        fs::create_dir(dir.path().join("handlers"))?;
+        fs::write(
+            dir.path().join("handlers").join("user.rs"),
+            "fn main() {}\n",
+        )?;
+        fs::write(dir.path().join("main.rs"), "handlers\n")?;
+
+        let args = parse_args(&[
+            "--match-paths",
+            "-p",
+            "handlers",
+            "-d",
+            dir.path().to_str().unwrap(),
+        ]);
+        let searcher = Searcher::new(&args)?;
+        searcher.search_dir(dir.path())?;
+
+        let results = searcher.results.lock().unwrap();
+        assert_eq!(results.len(), 1);
+        assert!(results[0].file_path.ends_with("handlers/user.rs"));
+        assert_eq!(results[0].line, results[0].file_path.display().to_string());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_max_errors() -> Result<(), Box<dyn Error>> {
+        let dir = tempdir()?;
+        fs::write(
+            dir.path().join("notes.txt"),
+            "please recieve this\nwe receve it\nreceive\n",
+        )?;
+
+        let args = parse_args(&[
+            "--max-errors",
+            "1",
+            "-p",
+            "receive",
+            "-d",
+            dir.path().to_str().unwrap(),
+        ]);
+        let searcher = Searcher::new(&args)?;
+        searcher.search_dir(dir.path())?;
+
+        let mut results = searcher.results.lock().unwrap();
+        results.sort_by_key(|r| r.line_number);
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].edit_distance, Some(1));
+        assert_eq!(results[1].edit_distance, Some(0));
+
+        let json = serde_json::to_value(results[0].to_json())?;
+        assert_eq!(json["edit_distance"], 1);
+        assert_eq!(json["matches"][0], serde_json::json!([3, 9]));
+        assert!(json.get("score").is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_file_predicates() -> Result<(), Box<dyn Error>> {
+        let dir = tempdir()?;
+        fs::write(dir.path().join("a.rs"), "static M: Mutex<u8>;\n")?;
+        fs::write(dir.path().join("b.rs"), "static M: Mutex<u8>;\nM.lock();\n")?;
+        fs::write(dir.path().join("c.rs"), "fn main() {}\n")?;
+
+        let args = parse_args(&[
+            "--file-contains",
+            "Mutex",
+            "--file-not-contains",
+            "lock()",
+            "-d",
+            dir.path().to_str().unwrap(),
+        ]);
+        let searcher = Searcher::new(&args)?;
+        searcher.search_dir(dir.path())?;
+
+        let results = searcher.results.lock().unwrap();
+        assert_eq!(results.len(), 1);
+        assert!(results[0].file_path.ends_with("a.rs"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_entropy_scan() -> Result<(), Box<dyn Error>> {
+        let dir = tempdir()?;
+        fs::write(
+            dir.path().join("config.py"),
+            "name = \"some_ordinary_identifier_name\"\nsecret = \"wJalrXUtnFEMI/K7MDENG/bPxRfiCYEXAMPLEKEY\"\n",
+        )?;
+
+        let args = parse_args(&["--entropy-scan", "-d", dir.path().to_str().unwrap()]);
+        let searcher = Searcher::new(&args)?;
+        searcher.search_dir(dir.path())?;
+
+        let results = searcher.results.lock().unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].line_number, 2);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_binary_files() -> Result<(), Box<dyn Error>> {
+        let dir = tempdir()?;
+        fs::write(dir.path().join("blob.bin"), b"\x00\x01needle\x00")?;
+        fs::write(dir.path().join("latin1.txt"), b"caf\xe9 needle\n")?;
+
+        let search = |flags: &[&str]| -> Result<Vec<SearchResult>, Box<dyn Error>> {
+            let mut argv = vec!["-p", "needle", "-d", dir.path().to_str().unwrap()];
+            argv.extend_from_slice(flags);
+            let searcher = Searcher::new(&parse_args(&argv))?;
+            searcher.search_dir(dir.path())?;
+            let mut results = std::mem::take(&mut *searcher.results.lock().unwrap());
+            results.sort_by(|a, b| a.file_path.cmp(&b.file_path));
+            Ok(results)
+        };
+
+        // Invalid UTF-8 alone doesn't make a file binary.
+        let results = search(&[])?;
+        assert_eq!(results.len(), 1);
+        assert!(results[0].file_path.ends_with("latin1.txt"));
+
+        let results = search(&["--binary"])?;
+        assert_eq!(results.len(), 2);
+        assert!(results[0].binary && results[0].line.is_empty());
+
+        let results = search(&["--text"])?;
+        assert_eq!(results.len(), 2);
+        assert!(!results[0].binary);
+        assert_eq!(results[0].matches, vec![(2, 8)]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_mime_filter() -> Result<(), Box<dyn Error>> {
+        let dir = tempdir()?;
+        fs::write(dir.path().join("deploy"), "#!/bin/sh\necho needle\n")?;
+        fs::write(dir.path().join("notes"), "needle\n")?;
+        fs::write(dir.path().join("logo"), b"\x89PNG\r\n\x1a\nneedle\0")?;
+
+        let args = parse_args(&[
+            "needle",
+            "--mime",
+            "text/x-shellscript",
+            "--binary",
+            dir.path().to_str().unwrap(),
+        ]);
+        let searcher = Searcher::new(&args)?;
+        searcher.search_dir(dir.path())?;
+        let results = searcher.results.lock().unwrap();
+        assert_eq!(results.len(), 1);
+        assert!(results[0].file_path.ends_with("deploy"));
+        drop(results);
+
+        let args = parse_args(&["needle", "--mime", "text/*", dir.path().to_str().unwrap()]);
+        let searcher = Searcher::new(&args)?;
+        searcher.search_dir(dir.path())?;
+        assert_eq!(searcher.results.lock().unwrap().len(), 2);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_mmap_large_file() -> Result<(), Box<dyn Error>> {
+        let dir = tempdir()?;
+        let path = dir.path().join("big.log");
+        let filler = "ordinary log line\n".repeat(4 * 1024 * 1024 / 18 + 1);
+        fs::write(&path, format!("{}needle\n", filler))?;
+        let len = fs::metadata(&path)?.len();
+
+        let args = parse_args(&["needle", dir.path().to_str().unwrap()]);
+        let searcher = Searcher::new(&args)?;
+        let strategy = searcher.read_strategy(len, true);
+        assert_eq!(strategy, ReadStrategy::Map);
+        assert!(matches!(
+            searcher.read_file(&fs::File::open(&path)?, strategy, Instant::now())?,
+            FileContents::Mapped(_)
+        ));
+        let results = searcher.search_file(&path)?;
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].line_number, filler.lines().count() + 1);
+
+        let args = parse_args(&["needle", "--no-mmap", dir.path().to_str().unwrap()]);
+        let searcher = Searcher::new(&args)?;
+        assert_eq!(searcher.read_strategy(len, true), ReadStrategy::Read);
+        assert_eq!(searcher.search_file(&path)?.len(), 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_stream_large_file() -> Result<(), Box<dyn Error>> {
+        let dir = tempdir()?;
+        let path = dir.path().join("huge.log");
+        fs::write(&path, b"first needle\r\nsecond\nthird needle\n\xff needle")?;
+
+        let args = parse_args(&["needle", "-m", "2", dir.path().to_str().unwrap()]);
+        let mut searcher = Searcher::new(&args)?;
+        searcher.stream_min_size = 0;
+        let results = searcher.search_file(&path)?;
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].line, "first needle");
+        assert_eq!(results[1].line_number, 3);
+
+        fs::write(&path, "needle\nbinary\0needle\n")?;
+        assert!(searcher.search_file(&path)?.is_empty());
+        assert_eq!(searcher.binary_skipped.load(Ordering::Relaxed), 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_stream_results() -> Result<(), Box<dyn Error>> {
+        let dir = tempdir()?;
+        for name in ["a.txt", "b.txt", "c.txt"] {
+            fs::write(dir.path().join(name), "needle\nneedle\n")?;
+        }
+
+        let args = parse_args(&["needle", "--limit", "5", dir.path().to_str().unwrap()]);
+        let mut searcher = Searcher::new(&args)?;
+        let (sender, receiver) = mpsc::sync_channel(RESULT_CHANNEL_CAPACITY);
+        searcher.stream_to(sender);
+        searcher.search_dir(dir.path())?;
+        searcher.close_output();
+
+        let batches: Vec<ResultBatch> = receiver
+            .iter()
+            .filter_map(|event| match event {
+                Event::Results(batch) => Some(batch),
+                Event::FileError(..) => None,
+            })
+            .collect();
+        assert_eq!(
+            batches
+                .iter()
+                .map(|batch| batch.results.len())
+                .sum::<usize>(),
+            5
+        );
+        assert!(searcher.results.lock().unwrap().is_empty());
+        assert_eq!(searcher.found(), 5);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_search_input() -> Result<(), Box<dyn Error>> {
+        let input = "ok\nERROR one\nfine\nerror two\n";
+        for argv in [
+            &["-p", "error", "-i", "-d", "-"][..],
+            &["-p", "EROR", "--fuzzy"],
+        ] {
+            let searcher = Searcher::new(&parse_args(argv))?;
+            searcher.search_input(input.as_bytes())?;
+            let results = searcher.take_results();
+            assert!(!results.is_empty(), "{:?}", argv);
+            assert_eq!(results[0].file_path, Path::new(STDIN_PATH));
+            assert_eq!(results[0].line_number, 2);
+        }
+
+        let args = parse_args(&["-p", "ERROR", "--replace", "OK", "--write", "-d", "-"]);
+        assert!(Searcher::new(&args)?
+            .search_input(input.as_bytes())
+            .is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_file_timeout() -> Result<(), Box<dyn Error>> {
+        let dir = tempdir()?;
+        fs::write(dir.path().join("slow.txt"), "needle\n".repeat(1000))?;
+
+        let args = parse_args(&[
+            "needle",
+            "--file-timeout",
+            "5s",
+            dir.path().to_str().unwrap(),
+        ]);
+        let mut searcher = Searcher::new(&args)?;
+        assert_eq!(searcher.file_timeout, Some(Duration::from_secs(5)));
+        searcher.file_timeout = Some(Duration::ZERO);
+        searcher.search_dir(dir.path())?;
+
+        assert!(searcher.results.lock().unwrap().is_empty());
+        let skipped = searcher.skipped.lock().unwrap();
+        assert_eq!(skipped.len(), 1);
+        assert!(skipped[0].1.starts_with("searching took longer than"));
+        drop(skipped);
+
+        // A line, or a file, that never ends is given up on part way.
+        let mut endless = std::io::BufReader::new(std::io::repeat(b'a'));
+        assert!(searcher
+            .read_line(&mut endless, &mut Vec::new(), Instant::now())
+            .is_err());
+        #[cfg(target_os = "linux")]
+        assert!(searcher
+            .read_file(
+                &fs::File::open("/dev/zero")?,
+                ReadStrategy::Read,
+                Instant::now()
+            )
+            .is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_max_line_length() -> Result<(), Box<dyn Error>> {
+        let dir = tempdir()?;
+        let path = dir.path().join("bundle.min.js");
+        fs::write(
+            &path,
+            format!(
+                "needle short\n{}needle\nneedle {}\n",
+                "x".repeat(100),
+                "y".repeat(100)
+            ),
+        )?;
+
+        let args = parse_args(&[
+            "needle",
+            "--max-line-length",
+            "20",
+            dir.path().to_str().unwrap(),
+        ]);
+        let searcher = Searcher::new(&args)?;
+        let results = searcher.search_file(&path)?;
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[1].line.len(), 20);
+        assert_eq!(results[1].line_number, 3);
+        assert_eq!(searcher.long_lines_seen.load(Ordering::Relaxed), 2);
+        // Truncation doesn't split a character.
+        let euros = "€".repeat(10);
+        assert_eq!(searcher.clip_line(&euros), Some("€€€€€€"));
+
+        let args = parse_args(&[
+            "needle",
+            "--max-line-length",
+            "20",
+            "--long-lines",
+            "skip",
+            dir.path().to_str().unwrap(),
+        ]);
+        let searcher = Searcher::new(&args)?;
+        assert_eq!(searcher.search_file(&path)?.len(), 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_max_memory() -> Result<(), Box<dyn Error>> {
+        let dir = tempdir()?;
+        let path = dir.path().join("big.txt");
+        fs::write(&path, "needle\n".repeat(1000))?;
+
+        let args = parse_args(&["needle", "--max-memory", "4K", dir.path().to_str().unwrap()]);
+        let mut searcher = Searcher::new(&args)?;
+        // Over half the budget, so it's streamed instead of read whole.
+        assert_eq!(searcher.search_file(&path)?.len(), 1000);
+
+        let (sender, receiver) = mpsc::sync_channel(RESULT_CHANNEL_CAPACITY);
+        searcher.stream_to(sender);
+        searcher.collect(searcher.search_file(&path)?);
+        let budget = searcher.result_budget.clone().unwrap();
+        assert!(budget.used() > 0);
+        drop(receiver.recv()?);
+        assert_eq!(budget.used(), 0);
+        assert_eq!(searcher.content_budget.as_ref().unwrap().used(), 0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_files_with_matches() -> Result<(), Box<dyn Error>> {
+        let dir = tempdir()?;
+        fs::write(dir.path().join("a.txt"), "needle\nneedle\nneedle\n")?;
+        fs::write(dir.path().join("b.txt"), "nothing\n")?;
+
+        let args = parse_args(&["-l", "needle", dir.path().to_str().unwrap()]);
+        let searcher = Searcher::new(&args)?;
+        assert_eq!(searcher.search_file(&dir.path().join("a.txt"))?.len(), 1);
+        searcher.search_dir(dir.path())?;
+        let results = searcher.results.lock().unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(
+            results[0].line,
+            dir.path().join("a.txt").display().to_string()
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_quiet_stops_at_first_match() -> Result<(), Box<dyn Error>> {
+        let dir = tempdir()?;
+        for name in ["a.txt", "b.txt", "c.txt"] {
+            fs::write(dir.path().join(name), "needle\nneedle\n")?;
+        }
+
+        let args = parse_args(&["-q", "needle", dir.path().to_str().unwrap()]);
+        let searcher = Searcher::new(&args)?;
+        searcher.search_dir(dir.path())?;
+        assert_eq!(searcher.found(), 1);
+        assert!(searcher.is_cancelled());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_thresholds() -> Result<(), Box<dyn Error>> {
+        let args = parse_args(&[
+            "needle",
+            "--mmap-threshold",
+            "1K",
+            "--stream-threshold",
+            "1M",
+        ]);
+        let searcher = Searcher::new(&args)?;
+        assert_eq!(searcher.read_strategy(100, true), ReadStrategy::Read);
+        assert_eq!(searcher.read_strategy(4096, true), ReadStrategy::Map);
+        assert_eq!(searcher.read_strategy(2 << 20, true), ReadStrategy::Stream);
+        // A search that needs the whole file maps it however big it is.
+        assert_eq!(searcher.read_strategy(2 << 20, false), ReadStrategy::Map);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_search() -> Result<(), Box<dyn Error>> {
+        let dir = tempdir()?;
+        fs::write(dir.path().join("a.txt"), "needle\nhay\n")?;
+        fs::write(dir.path().join("b.txt"), "more needle\n")?;
+
+        let searcher = Searcher::new(&Args::from_argv(["needle"])?)?;
+        let mut results = searcher.search(dir.path())?;
+        results.sort_by(|a, b| a.file_path.cmp(&b.file_path));
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].file_path, dir.path().join("a.txt"));
+        assert_eq!((results[0].line_number, results[0].matches[0]), (1, (0, 6)));
+        assert_eq!(results[1].line, "more needle");
+        // Results are handed over, not kept for the next search.
+        assert_eq!(searcher.search(&dir.path().join("b.txt"))?.len(), 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_search_twice() -> Result<(), Box<dyn Error>> {
+        let dir = tempdir()?;
+        fs::write(dir.path().join("a.txt"), "needle\nneedle\n")?;
+        fs::hard_link(dir.path().join("a.txt"), dir.path().join("b.txt"))?;
+
+        // Each search has its own limit, and sees the hard links afresh.
+        let searcher = Searcher::builder().pattern("needle").limit(1).build()?;
+        for _ in 0..2 {
+            assert_eq!(searcher.search(dir.path())?.len(), 1);
+        }
+        let searcher = Searcher::builder().pattern("needle").build()?;
+        for _ in 0..2 {
+            assert_eq!(searcher.search(dir.path())?.len(), 2);
+            assert_eq!(searcher.stats.snapshot().files, 1);
+        }
+        let mut searcher = Searcher::builder().pattern("needle").build()?;
+        for _ in 0..2 {
+            let mut counter = crate::sink::Counter::default();
+            searcher.search_to(dir.path(), &mut counter)?;
+            assert_eq!(searcher.found(), 2);
+        }
+
+        Ok(())
+    }
+}
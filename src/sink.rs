@@ -0,0 +1,213 @@
+use crate::result::SearchResult;
+use crate::searcher::{Event, Searcher, RESULT_CHANNEL_CAPACITY};
+use std::error::Error;
+use std::io::{self, Write};
+use std::path::Path;
+use std::sync::mpsc;
+
+/// Receives a search's results as it runs, for [`Searcher::search_to`].
+///
+/// Calls come from one thread at a time. Each file's results arrive
+/// together, just after `on_file_start` for it; files without results get
+/// no calls at all. An error from any method stops the search.
+pub trait Sink {
+    fn on_file_start(&mut self, _path: &Path) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn on_match(&mut self, result: &SearchResult) -> io::Result<()>;
+
+    /// A file that couldn't be searched, or was skipped, and why.
+    fn on_file_error(&mut self, _path: &Path, _error: &str) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// Called once the search is over, with how many results it found.
+    fn on_finish(&mut self, _found: usize) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl<S: Sink + ?Sized> Sink for Box<S> {
+    fn on_file_start(&mut self, path: &Path) -> io::Result<()> {
+        (**self).on_file_start(path)
+    }
+
+    fn on_match(&mut self, result: &SearchResult) -> io::Result<()> {
+        (**self).on_match(result)
+    }
+
+    fn on_file_error(&mut self, path: &Path, error: &str) -> io::Result<()> {
+        (**self).on_file_error(path, error)
+    }
+
+    fn on_finish(&mut self, found: usize) -> io::Result<()> {
+        (**self).on_finish(found)
+    }
+}
+
+/// Writes each result as a line of JSON, as `--json` does.
+pub struct JsonWriter<W> {
+    writer: W,
+}
+
+impl<W: Write> JsonWriter<W> {
+    pub fn new(writer: W) -> Self {
+        JsonWriter { writer }
+    }
+}
+
+impl<W: Write> Sink for JsonWriter<W> {
+    fn on_match(&mut self, result: &SearchResult) -> io::Result<()> {
+        serde_json::to_writer(&mut self.writer, &result.to_json())?;
+        self.writer.write_all(b"\n")
+    }
+
+    fn on_finish(&mut self, _found: usize) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+/// Counts what a search found without keeping any of it.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Counter {
+    pub files: usize,
+    pub matches: usize,
+    pub errors: usize,
+}
+
+impl Sink for Counter {
+    fn on_file_start(&mut self, _path: &Path) -> io::Result<()> {
+        self.files += 1;
+        Ok(())
+    }
+
+    fn on_match(&mut self, _result: &SearchResult) -> io::Result<()> {
+        self.matches += 1;
+        Ok(())
+    }
+
+    fn on_file_error(&mut self, _path: &Path, _error: &str) -> io::Result<()> {
+        self.errors += 1;
+        Ok(())
+    }
+}
+
+/// Hands results to `sink`, starting a new file whenever the path changes.
+pub(crate) fn feed(sink: &mut (impl Sink + ?Sized), results: &[SearchResult]) -> io::Result<()> {
+    let mut file = None;
+    for result in results {
+        if file != Some(&result.file_path) {
+            file = Some(&result.file_path);
+            sink.on_file_start(&result.file_path)?;
+        }
+        sink.on_match(result)?;
+    }
+    Ok(())
+}
+
+/// Hands one event from a streamed search to `sink`.
+pub(crate) fn deliver(sink: &mut (impl Sink + ?Sized), event: Event) -> io::Result<()> {
+    match event {
+        Event::Results(batch) => feed(sink, &batch.results),
+        Event::FileError(path, error) => sink.on_file_error(&path, &error),
+    }
+}
+
+impl Searcher {
+    /// Searches a directory tree, or a single file, handing results to
+    /// `sink` while the search runs instead of keeping them.
+    pub fn search_to<S: Sink + Send + ?Sized>(
+        &mut self,
+        path: &Path,
+        sink: &mut S,
+    ) -> Result<(), Box<dyn Error>> {
+        self.clear();
+        self.stream_into(sink, |searcher| searcher.search_dir(path))
+    }
+
+    /// Runs `search`, handing what it finds to `sink` as it goes, and then
+    /// finishes `sink`.
+    pub(crate) fn stream_into<S: Sink + Send + ?Sized>(
+        &mut self,
+        sink: &mut S,
+        search: impl FnOnce(&Searcher) -> Result<(), Box<dyn Error>>,
+    ) -> Result<(), Box<dyn Error>> {
+        let (sender, events) = mpsc::sync_channel(RESULT_CHANNEL_CAPACITY);
+        self.stream_to(sender);
+        let (searched, delivered) = std::thread::scope(|scope| {
+            // Returning early drops `events`, which stops the search.
+            let delivering = scope.spawn(|| {
+                events
+                    .into_iter()
+                    .try_for_each(|event| deliver(&mut *sink, event))
+            });
+            let searched = search(self);
+            self.close_output();
+            let delivered = delivering
+                .join()
+                .unwrap_or_else(|panic| std::panic::resume_unwind(panic));
+            (searched, delivered)
+        });
+        searched?;
+        delivered?;
+        sink.on_finish(self.found())?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_search_to() -> Result<(), Box<dyn Error>> {
+        let dir = tempdir()?;
+        fs::write(dir.path().join("a.txt"), "needle\nneedle\n")?;
+        fs::write(dir.path().join("b.txt"), "needle\n")?;
+        fs::write(dir.path().join("c.txt"), "hay\n")?;
+
+        let mut searcher = Searcher::builder().pattern("needle").build()?;
+        let mut counter = Counter::default();
+        searcher.search_to(dir.path(), &mut counter)?;
+        assert_eq!(
+            counter,
+            Counter {
+                files: 2,
+                matches: 3,
+                errors: 0
+            }
+        );
+
+        let mut json = JsonWriter::new(Vec::new());
+        let path = dir.path().join("b.txt");
+        searcher.search_to(&path, &mut json)?;
+        let line: serde_json::Value = serde_json::from_slice(&json.writer)?;
+        assert_eq!(line["line"], "needle");
+        assert_eq!(line["path"], path.display().to_string());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_sink_error_stops_search() -> Result<(), Box<dyn Error>> {
+        struct Failing;
+        impl Sink for Failing {
+            fn on_match(&mut self, _result: &SearchResult) -> io::Result<()> {
+                Err(io::Error::other("full"))
+            }
+        }
+
+        let dir = tempdir()?;
+        for i in 0..10 {
+            fs::write(dir.path().join(format!("{}.txt", i)), "needle\n")?;
+        }
+        let mut searcher = Searcher::builder().pattern("needle").build()?;
+        let error = searcher.search_to(dir.path(), &mut Failing).unwrap_err();
+        assert_eq!(error.to_string(), "full");
+
+        Ok(())
+    }
+}
@@ -0,0 +1,112 @@
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::time::Duration;
+
+/// Counters updated as files are searched, read back for `--bench`.
+#[derive(Default)]
+pub struct Stats {
+    files: AtomicUsize,
+    bytes: AtomicU64,
+    read_nanos: AtomicU64,
+    search_nanos: AtomicU64,
+}
+
+/// The totals from `Stats` at one point in time. Durations are summed
+/// across threads, so they can exceed the wall-clock time.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Snapshot {
+    pub files: usize,
+    pub bytes: u64,
+    pub reading: Duration,
+    pub matching: Duration,
+}
+
+impl Stats {
+    pub fn record_read(&self, bytes: u64, took: Duration) {
+        self.bytes.fetch_add(bytes, Ordering::Relaxed);
+        self.read_nanos
+            .fetch_add(took.as_nanos() as u64, Ordering::Relaxed);
+    }
+
+    /// Counts one searched file, `took` including any time spent reading it.
+    pub fn record_file(&self, took: Duration) {
+        self.files.fetch_add(1, Ordering::Relaxed);
+        self.search_nanos
+            .fetch_add(took.as_nanos() as u64, Ordering::Relaxed);
+    }
+
+    /// Starts the counts again from zero.
+    pub fn clear(&self) {
+        self.files.store(0, Ordering::Relaxed);
+        self.bytes.store(0, Ordering::Relaxed);
+        self.read_nanos.store(0, Ordering::Relaxed);
+        self.search_nanos.store(0, Ordering::Relaxed);
+    }
+
+    pub fn snapshot(&self) -> Snapshot {
+        let reading = Duration::from_nanos(self.read_nanos.load(Ordering::Relaxed));
+        let searching = Duration::from_nanos(self.search_nanos.load(Ordering::Relaxed));
+        Snapshot {
+            files: self.files.load(Ordering::Relaxed),
+            bytes: self.bytes.load(Ordering::Relaxed),
+            reading,
+            matching: searching.saturating_sub(reading),
+        }
+    }
+}
+
+/// Summarizes timed runs of the same search: wall time, throughput and where
+/// the time went.
+pub fn bench_report(runs: &[(Duration, Snapshot)]) -> String {
+    if runs.is_empty() {
+        return String::from("no runs");
+    }
+    let walls: Vec<f64> = runs.iter().map(|(wall, _)| wall.as_secs_f64()).collect();
+    let mean = walls.iter().sum::<f64>() / walls.len() as f64;
+    let min = walls.iter().copied().fold(f64::INFINITY, f64::min);
+    let max = walls.iter().copied().fold(0.0, f64::max);
+    let (_, last) = runs[runs.len() - 1];
+    let per_run = |total: Duration| total.as_secs_f64() * 1000.0 / runs.len() as f64;
+    let reading: Duration = runs.iter().map(|(_, stats)| stats.reading).sum();
+    let matching: Duration = runs.iter().map(|(_, stats)| stats.matching).sum();
+
+    format!(
+        "{} runs: mean {:.1} ms, min {:.1} ms, max {:.1} ms\n\
+         {} files, {:.1} MB per run: {:.0} files/s, {:.1} MB/s\n\
+         per run, summed across threads: {:.1} ms reading, {:.1} ms matching",
+        runs.len(),
+        mean * 1000.0,
+        min * 1000.0,
+        max * 1000.0,
+        last.files,
+        last.bytes as f64 / 1e6,
+        last.files as f64 / mean,
+        last.bytes as f64 / 1e6 / mean,
+        per_run(reading),
+        per_run(matching),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bench_report() {
+        let stats = Stats::default();
+        stats.record_read(2_000_000, Duration::from_millis(30));
+        stats.record_file(Duration::from_millis(80));
+        stats.record_file(Duration::from_millis(20));
+        let snapshot = stats.snapshot();
+        assert_eq!(snapshot.files, 2);
+        assert_eq!(snapshot.matching, Duration::from_millis(70));
+
+        let runs = [
+            (Duration::from_millis(100), snapshot),
+            (Duration::from_millis(300), snapshot),
+        ];
+        let report = bench_report(&runs);
+        assert!(report.starts_with("2 runs: mean 200.0 ms, min 100.0 ms, max 300.0 ms"));
+        assert!(report.contains("2 files, 2.0 MB per run: 10 files/s, 10.0 MB/s"));
+        assert!(report.contains("30.0 ms reading, 70.0 ms matching"));
+    }
+}
@@ -0,0 +1,107 @@
+use crate::iter::SearchIter;
+use crate::result::SearchResult;
+use crate::searcher::{Searcher, RESULT_CHANNEL_CAPACITY};
+use crate::SearchError;
+use futures_core::Stream;
+use std::path::PathBuf;
+use std::pin::Pin;
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+
+/// A [`SearchIter`] as an async stream, so results can be awaited without
+/// blocking an executor thread. Dropping it stops the search.
+pub struct SearchStream {
+    items: Receiver<Result<SearchResult, SearchError>>,
+    waker: Arc<Mutex<Option<Waker>>>,
+}
+
+impl Stream for SearchStream {
+    type Item = Result<SearchResult, SearchError>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        // Stored before looking, so an item sent after the look still wakes
+        // this task.
+        *self.waker.lock().unwrap() = Some(cx.waker().clone());
+        match self.items.try_recv() {
+            Ok(item) => Poll::Ready(Some(item)),
+            Err(TryRecvError::Empty) => Poll::Pending,
+            Err(TryRecvError::Disconnected) => Poll::Ready(None),
+        }
+    }
+}
+
+impl Searcher {
+    /// Like [`Searcher::search_iter`], but as a [`Stream`].
+    pub fn search_async(self, path: impl Into<PathBuf>) -> SearchStream {
+        let results: SearchIter = self.search_iter(path);
+        let (sender, items) = mpsc::sync_channel(RESULT_CHANNEL_CAPACITY);
+        let waker: Arc<Mutex<Option<Waker>>> = Arc::default();
+        let wake = {
+            let waker = Arc::clone(&waker);
+            move || {
+                if let Some(waker) = waker.lock().unwrap().take() {
+                    waker.wake();
+                }
+            }
+        };
+        std::thread::spawn(move || {
+            for item in results {
+                // The stream was dropped; dropping `results` stops the search.
+                if sender.send(item).is_err() {
+                    return;
+                }
+                wake();
+            }
+            drop(sender);
+            wake();
+        });
+        SearchStream { items, waker }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::error::Error;
+    use std::fs;
+    use std::task::Wake;
+    use std::thread::Thread;
+    use tempfile::tempdir;
+
+    struct Unpark(Thread);
+
+    impl Wake for Unpark {
+        fn wake(self: Arc<Self>) {
+            self.0.unpark();
+        }
+    }
+
+    /// Polls the stream to its end, parking between wakes.
+    fn collect(mut stream: SearchStream) -> Vec<Result<SearchResult, SearchError>> {
+        let waker = Waker::from(Arc::new(Unpark(std::thread::current())));
+        let mut cx = Context::from_waker(&waker);
+        let mut items = Vec::new();
+        loop {
+            match Pin::new(&mut stream).poll_next(&mut cx) {
+                Poll::Ready(Some(item)) => items.push(item),
+                Poll::Ready(None) => return items,
+                Poll::Pending => std::thread::park(),
+            }
+        }
+    }
+
+    #[test]
+    fn test_search_stream() -> Result<(), Box<dyn Error>> {
+        let dir = tempdir()?;
+        for i in 0..20 {
+            fs::write(dir.path().join(format!("{}.txt", i)), "needle\n")?;
+        }
+        let searcher = Searcher::builder().pattern("needle").build()?;
+        let items = collect(searcher.search_async(dir.path()));
+        assert_eq!(items.len(), 20);
+        assert!(items.iter().all(Result::is_ok));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,434 @@
+use clap::ValueEnum;
+use regex::Regex;
+use std::path::Path;
+use tree_sitter::{Language, Node, Parser, Tree};
+
+/// Which part of the source code matches may fall in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Scope {
+    /// Inside comments
+    Comments,
+    /// Inside string and character literals
+    Strings,
+    /// Outside comments and string literals
+    Code,
+    /// Exactly covering an identifier
+    Identifiers,
+}
+
+/// Picks a grammar from the file extension.
+fn language_for(path: &Path) -> Option<Language> {
+    let language = match path.extension()?.to_str()? {
+        "rs" => tree_sitter_rust::LANGUAGE,
+        "py" | "pyi" => tree_sitter_python::LANGUAGE,
+        "js" | "mjs" | "cjs" | "jsx" => tree_sitter_javascript::LANGUAGE,
+        _ => return None,
+    };
+    Some(language.into())
+}
+
+fn is_comment(kind: &str) -> bool {
+    kind.contains("comment")
+}
+
+fn is_string(kind: &str) -> bool {
+    kind.contains("string") || kind == "char_literal"
+}
+
+fn is_identifier(kind: &str) -> bool {
+    kind.ends_with("identifier")
+}
+
+/// A parsed source file used to decide which scope each match falls in.
+pub struct SyntaxTree {
+    tree: Tree,
+}
+
+impl SyntaxTree {
+    /// Parses `contents` with the grammar for `path`, or returns `None` when
+    /// the language isn't supported.
+    pub fn parse(path: &Path, contents: &str) -> Option<Self> {
+        let mut parser = Parser::new();
+        parser.set_language(&language_for(path)?).ok()?;
+        let tree = parser.parse(contents, None)?;
+        Some(SyntaxTree { tree })
+    }
+
+    /// Finds the innermost node of interest enclosing a byte range: the
+    /// nearest comment or string ancestor, or else the smallest node.
+    fn enclosing(&self, start: usize, end: usize) -> Option<Node<'_>> {
+        let smallest = self
+            .tree
+            .root_node()
+            .descendant_for_byte_range(start, end)?;
+        let mut node = Some(smallest);
+        while let Some(current) = node {
+            if is_comment(current.kind()) || is_string(current.kind()) {
+                return Some(current);
+            }
+            node = current.parent();
+        }
+        Some(smallest)
+    }
+
+    /// Keeps the matches of a line starting at file offset `line_start` that
+    /// fall in `scope`, together with the node kind of the first one kept.
+    pub fn filter(
+        &self,
+        scope: Scope,
+        line_start: usize,
+        matches: Vec<(usize, usize)>,
+    ) -> (Vec<(usize, usize)>, Option<String>) {
+        let mut node_kind = None;
+        let kept = matches
+            .into_iter()
+            .filter(|&(start, end)| {
+                let (start, end) = (line_start + start, line_start + end);
+                let Some(node) = self.enclosing(start, end) else {
+                    return false;
+                };
+                let kind = node.kind();
+                let keep = match scope {
+                    Scope::Comments => is_comment(kind),
+                    Scope::Strings => is_string(kind),
+                    Scope::Code => !is_comment(kind) && !is_string(kind),
+                    Scope::Identifiers => {
+                        is_identifier(kind) && node.start_byte() == start && node.end_byte() == end
+                    }
+                };
+                if keep && node_kind.is_none() {
+                    node_kind = Some(kind.to_string());
+                }
+                keep
+            })
+            .collect();
+        (kept, node_kind)
+    }
+}
+
+/// Placeholder identifiers that metavariables are rewritten to before the
+/// pattern is parsed, since `$X` isn't a valid identifier in most grammars.
+const METAVAR_PREFIX: &str = "__METAVAR_";
+const MULTI_METAVAR_PREFIX: &str = "__METAVARS_";
+
+/// Parse tree wrappers that are looked through to find the node a pattern
+/// actually describes (e.g. `unwrap($X)` parses as a file containing an
+/// expression statement containing the call).
+const WRAPPER_KINDS: &[&str] = &["source_file", "module", "program", "expression_statement"];
+
+/// A code template such as `$X.unwrap()` matched against syntax trees.
+///
+/// `$NAME` matches any single node, `$$$NAME` matches any run of sibling
+/// nodes (including none), and `$_` matches a node without binding it. A
+/// metavariable used twice must match identical text both times.
+pub struct StructuralPattern {
+    source: String,
+}
+
+/// A node that matched a structural pattern.
+#[derive(Debug, PartialEq, Eq)]
+pub struct StructuralMatch {
+    pub start: usize,
+    pub end: usize,
+    /// Metavariable names (without `$`) and the text they matched.
+    pub bindings: Vec<(String, String)>,
+}
+
+/// A pattern parsed with one language's grammar.
+struct CompiledPattern<'p> {
+    source: &'p str,
+    tree: Tree,
+}
+
+impl StructuralPattern {
+    pub fn new(pattern: &str) -> Result<Self, String> {
+        let multi = Regex::new(r"\$\$\$([A-Z_][A-Z0-9_]*)").unwrap();
+        let single = Regex::new(r"\$([A-Z_][A-Z0-9_]*)").unwrap();
+        let source = multi.replace_all(pattern, format!("{}$1", MULTI_METAVAR_PREFIX));
+        let source = single
+            .replace_all(&source, format!("{}$1", METAVAR_PREFIX))
+            .into_owned();
+
+        let original = pattern;
+        let pattern = StructuralPattern { source };
+        let parses = ["main.rs", "main.py", "main.js"]
+            .iter()
+            .any(|name| pattern.compile(Path::new(name)).is_some());
+        if !parses {
+            return Err(format!(
+                "{:?} is not valid Rust, Python or JavaScript code",
+                original
+            ));
+        }
+        Ok(pattern)
+    }
+
+    /// Parses the pattern with the grammar for `path`, retrying with a
+    /// trailing `;` for statement-oriented grammars.
+    fn compile(&self, path: &Path) -> Option<CompiledPattern<'_>> {
+        let language = language_for(path)?;
+        let mut parser = Parser::new();
+        parser.set_language(&language).ok()?;
+        for suffix in ["", ";"] {
+            let source = format!("{}{}", self.source, suffix);
+            if let Some(tree) = parser.parse(&source, None) {
+                if !tree.root_node().has_error() {
+                    return Some(CompiledPattern {
+                        source: &self.source,
+                        tree,
+                    });
+                }
+            }
+        }
+        None
+    }
+
+    /// Finds every node of `contents` matching the pattern, in source order.
+    /// Returns `None` when the file's language isn't supported or the
+    /// pattern isn't valid code in that language.
+    pub fn find_matches(&self, path: &Path, contents: &str) -> Option<Vec<StructuralMatch>> {
+        let compiled = self.compile(path)?;
+        let target = SyntaxTree::parse(path, contents)?;
+        let pattern_root = compiled.root();
+        if compiled.metavariable(pattern_root).is_some() {
+            // A bare metavariable would match every node in the file.
+            return None;
+        }
+
+        let mut found = Vec::new();
+        let mut cursor = target.tree.walk();
+        'walk: loop {
+            let node = cursor.node();
+            if node.kind() == pattern_root.kind() {
+                let mut bindings = Vec::new();
+                if compiled.match_node(pattern_root, node, contents, &mut bindings) {
+                    found.push(StructuralMatch {
+                        start: node.start_byte(),
+                        end: node.end_byte(),
+                        bindings,
+                    });
+                }
+            }
+            if cursor.goto_first_child() {
+                continue;
+            }
+            while !cursor.goto_next_sibling() {
+                if !cursor.goto_parent() {
+                    break 'walk;
+                }
+            }
+        }
+        Some(found)
+    }
+}
+
+/// A metavariable found in a pattern.
+enum Metavariable<'a> {
+    Single(&'a str),
+    Multi(&'a str),
+}
+
+fn is_metavariable_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_uppercase() || c.is_ascii_digit() || c == '_')
+}
+
+/// Children that take part in matching: everything except comments and
+/// other extras.
+fn significant_children(node: Node<'_>) -> Vec<Node<'_>> {
+    let mut cursor = node.walk();
+    node.children(&mut cursor)
+        .filter(|child| !child.is_extra())
+        .collect()
+}
+
+/// Records a metavariable binding, or checks it against an earlier one.
+fn bind(name: &str, text: &str, bindings: &mut Vec<(String, String)>) -> bool {
+    if name == "_" {
+        return true;
+    }
+    match bindings.iter().find(|(bound, _)| bound == name) {
+        Some((_, bound_text)) => bound_text == text,
+        None => {
+            bindings.push((name.to_string(), text.to_string()));
+            true
+        }
+    }
+}
+
+impl CompiledPattern<'_> {
+    fn root(&self) -> Node<'_> {
+        let mut node = self.tree.root_node();
+        while WRAPPER_KINDS.contains(&node.kind()) && node.named_child_count() == 1 {
+            node = node.named_child(0).unwrap();
+        }
+        node
+    }
+
+    fn text(&self, node: Node) -> &str {
+        &self.source[node.start_byte()..node.end_byte().min(self.source.len())]
+    }
+
+    fn metavariable(&self, node: Node) -> Option<Metavariable<'_>> {
+        let text = self.text(node);
+        if let Some(name) = text.strip_prefix(MULTI_METAVAR_PREFIX) {
+            return is_metavariable_name(name).then_some(Metavariable::Multi(name));
+        }
+        let name = text.strip_prefix(METAVAR_PREFIX)?;
+        is_metavariable_name(name).then_some(Metavariable::Single(name))
+    }
+
+    fn match_node(
+        &self,
+        pattern: Node,
+        target: Node,
+        contents: &str,
+        bindings: &mut Vec<(String, String)>,
+    ) -> bool {
+        let target_text = &contents[target.start_byte()..target.end_byte()];
+        match self.metavariable(pattern) {
+            Some(Metavariable::Single(name)) | Some(Metavariable::Multi(name)) => {
+                return bind(name, target_text, bindings)
+            }
+            None => {}
+        }
+        if pattern.kind() != target.kind() {
+            return false;
+        }
+
+        let pattern_children = significant_children(pattern);
+        let target_children = significant_children(target);
+        if pattern_children.is_empty() {
+            return target_children.is_empty() && self.text(pattern) == target_text;
+        }
+        self.match_sequence(&pattern_children, &target_children, contents, bindings)
+    }
+
+    fn match_sequence(
+        &self,
+        patterns: &[Node],
+        targets: &[Node],
+        contents: &str,
+        bindings: &mut Vec<(String, String)>,
+    ) -> bool {
+        let Some((first, rest)) = patterns.split_first() else {
+            return targets.is_empty();
+        };
+
+        if let Some(Metavariable::Multi(name)) = self.metavariable(*first) {
+            for taken in 0..=targets.len() {
+                let text = match (targets.first(), targets[..taken].last()) {
+                    (Some(start), Some(end)) if taken > 0 => {
+                        &contents[start.start_byte()..end.end_byte()]
+                    }
+                    _ => "",
+                };
+                let mut attempt = bindings.clone();
+                if bind(name, text, &mut attempt)
+                    && self.match_sequence(rest, &targets[taken..], contents, &mut attempt)
+                {
+                    *bindings = attempt;
+                    return true;
+                }
+            }
+            return false;
+        }
+
+        let Some((target, target_rest)) = targets.split_first() else {
+            return false;
+        };
+        let mut attempt = bindings.clone();
+        if self.match_node(*first, *target, contents, &mut attempt)
+            && self.match_sequence(rest, target_rest, contents, &mut attempt)
+        {
+            *bindings = attempt;
+            return true;
+        }
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SOURCE: &str =
+        "// TODO: unwrap less\nfn main() {\n    let todo = \"TODO\";\n    todo.unwrap();\n}\n";
+
+    fn spans_of(needle: &str, line: &str) -> Vec<(usize, usize)> {
+        line.match_indices(needle)
+            .map(|(i, m)| (i, i + m.len()))
+            .collect()
+    }
+
+    fn filter_line(scope: Scope, line_index: usize, needle: &str) -> (usize, Option<String>) {
+        let tree = SyntaxTree::parse(Path::new("main.rs"), SOURCE).unwrap();
+        let line_start: usize = SOURCE.lines().take(line_index).map(|l| l.len() + 1).sum();
+        let line = SOURCE.lines().nth(line_index).unwrap();
+        let (kept, kind) = tree.filter(scope, line_start, spans_of(needle, line));
+        (kept.len(), kind)
+    }
+
+    #[test]
+    fn test_filter_scopes() {
+        assert_eq!(
+            filter_line(Scope::Comments, 0, "TODO"),
+            (1, Some("line_comment".to_string()))
+        );
+        assert_eq!(filter_line(Scope::Code, 0, "unwrap").0, 0);
+        assert_eq!(filter_line(Scope::Code, 3, "unwrap").0, 1);
+        assert_eq!(filter_line(Scope::Strings, 2, "TODO").0, 1);
+        assert_eq!(filter_line(Scope::Identifiers, 2, "todo").0, 1);
+        assert_eq!(filter_line(Scope::Identifiers, 3, "tod").0, 0);
+    }
+
+    #[test]
+    fn test_unsupported_language() {
+        assert!(SyntaxTree::parse(Path::new("notes.txt"), "TODO").is_none());
+    }
+
+    fn structural(pattern: &str, path: &str, contents: &str) -> Vec<StructuralMatch> {
+        StructuralPattern::new(pattern)
+            .unwrap()
+            .find_matches(Path::new(path), contents)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_structural_rust_method_call() {
+        let source = "fn f() { let a = x.unwrap(); let b = y.get(0).unwrap(); z.expect(\"\"); }";
+        let found = structural("$X.unwrap()", "lib.rs", source);
+        assert_eq!(found.len(), 2);
+        assert_eq!(&source[found[0].start..found[0].end], "x.unwrap()");
+        assert_eq!(
+            found[1].bindings,
+            vec![("X".to_string(), "y.get(0)".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_structural_repeated_and_multi_metavariables() {
+        let source = "a + a\nb + c\nprint(1, 2, 3)\nprint()\n";
+        assert_eq!(structural("$A + $A", "t.py", source).len(), 1);
+
+        let calls = structural("print($$$ARGS)", "t.py", source);
+        assert_eq!(calls.len(), 2);
+        assert_eq!(calls[0].bindings[0].1, "1, 2, 3");
+        assert_eq!(calls[1].bindings[0].1, "");
+    }
+
+    #[test]
+    fn test_structural_javascript() {
+        let source = "console.log(x); console.warn(y);";
+        let found = structural("console.log($_)", "app.js", source);
+        assert_eq!(found.len(), 1);
+        assert!(found[0].bindings.is_empty());
+    }
+
+    #[test]
+    fn test_structural_invalid_pattern() {
+        assert!(StructuralPattern::new("fn (").is_err());
+    }
+}
@@ -0,0 +1,147 @@
+use crate::SearchError;
+use ignore::types::{Types, TypesBuilder};
+use std::error::Error;
+
+/// Built-in file types for `--type`, each with the globs it covers.
+pub const FILE_TYPES: &[(&str, &[&str])] = &[
+    ("c", &["*.c", "*.h"]),
+    (
+        "cpp",
+        &["*.cpp", "*.cc", "*.cxx", "*.hpp", "*.hh", "*.hxx", "*.h"],
+    ),
+    ("csharp", &["*.cs"]),
+    ("css", &["*.css", "*.scss", "*.sass", "*.less"]),
+    ("go", &["*.go"]),
+    ("html", &["*.html", "*.htm"]),
+    ("java", &["*.java"]),
+    ("js", &["*.js", "*.mjs", "*.cjs", "*.jsx"]),
+    ("json", &["*.json"]),
+    ("kotlin", &["*.kt", "*.kts"]),
+    ("md", &["*.md", "*.markdown"]),
+    ("php", &["*.php"]),
+    ("py", &["*.py", "*.pyi"]),
+    ("ruby", &["*.rb", "Gemfile", "Rakefile"]),
+    ("rust", &["*.rs"]),
+    ("sh", &["*.sh", "*.bash", "*.zsh"]),
+    ("sql", &["*.sql"]),
+    ("swift", &["*.swift"]),
+    ("toml", &["*.toml", "Cargo.lock"]),
+    ("ts", &["*.ts", "*.tsx", "*.mts", "*.cts"]),
+    ("yaml", &["*.yml", "*.yaml"]),
+];
+
+/// A file type added with `--type-add 'name:glob,glob'` or in the config
+/// file. Adding to a built-in type extends its globs.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TypeDefinition {
+    pub name: String,
+    pub globs: Vec<String>,
+}
+
+impl std::str::FromStr for TypeDefinition {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, globs) = s
+            .split_once(':')
+            .ok_or_else(|| format!("expected NAME:GLOB[,GLOB...], got {:?}", s))?;
+        let name = name.trim();
+        let globs: Vec<String> = globs
+            .split(',')
+            .map(str::trim)
+            .filter(|glob| !glob.is_empty())
+            .map(str::to_string)
+            .collect();
+        if name.is_empty() || globs.is_empty() {
+            return Err(format!("expected NAME:GLOB[,GLOB...], got {:?}", s));
+        }
+        Ok(TypeDefinition {
+            name: name.to_string(),
+            globs,
+        })
+    }
+}
+
+/// Looks up the globs for a file type, built-in or added.
+pub fn globs_for(name: &str, added: &[TypeDefinition]) -> Option<Vec<String>> {
+    let builtin = FILE_TYPES
+        .iter()
+        .find(|(type_name, _)| *type_name == name)
+        .map(|(_, globs)| *globs);
+    let mut globs: Vec<String> = builtin
+        .unwrap_or_default()
+        .iter()
+        .map(|glob| glob.to_string())
+        .collect();
+    for definition in added.iter().filter(|definition| definition.name == name) {
+        globs.extend(definition.globs.iter().cloned());
+    }
+    (builtin.is_some() || !globs.is_empty()).then_some(globs)
+}
+
+/// The walker's filter for files of the `selected` types. Unlike a glob
+/// override, it only ever leaves files out, so ignored and hidden files
+/// stay out too.
+pub(crate) fn matcher(
+    selected: &[String],
+    added: &[TypeDefinition],
+) -> Result<Types, Box<dyn Error>> {
+    let mut builder = TypesBuilder::new();
+    for name in selected {
+        let globs = globs_for(name, added).ok_or_else(|| {
+            SearchError::new(
+                "UnknownType",
+                format!("unknown file type {:?} (see --type-list)", name),
+            )
+        })?;
+        for glob in &globs {
+            builder.add(name, glob)?;
+        }
+        builder.select(name);
+    }
+    Ok(builder.build()?)
+}
+
+/// Every type name with its globs, built-in and added, sorted by name.
+pub fn all_types(added: &[TypeDefinition]) -> Vec<(String, Vec<String>)> {
+    let mut names: Vec<&str> = FILE_TYPES.iter().map(|(name, _)| *name).collect();
+    names.extend(added.iter().map(|definition| definition.name.as_str()));
+    names.sort_unstable();
+    names.dedup();
+    names
+        .into_iter()
+        .map(|name| (name.to_string(), globs_for(name, added).unwrap_or_default()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_file_types_sorted_and_known() {
+        assert!(FILE_TYPES.windows(2).all(|pair| pair[0].0 < pair[1].0));
+        assert_eq!(globs_for("rust", &[]), Some(vec!["*.rs".to_string()]));
+        assert_eq!(globs_for("cobol", &[]), None);
+    }
+
+    #[test]
+    fn test_type_add() {
+        let web: TypeDefinition = "web:*.html, *.css,*.js".parse().unwrap();
+        assert_eq!(web.globs, ["*.html", "*.css", "*.js"]);
+        let rust: TypeDefinition = "rust:*.rs.in".parse().unwrap();
+
+        let added = [web, rust];
+        assert_eq!(globs_for("web", &added).unwrap().len(), 3);
+        assert_eq!(globs_for("rust", &added).unwrap(), ["*.rs", "*.rs.in"]);
+        assert!(all_types(&added).iter().any(|(name, _)| name == "web"));
+
+        let types = matcher(&["web".to_string()], &added).unwrap();
+        assert!(types.matched("index.html", false).is_whitelist());
+        assert!(types.matched("main.rs", false).is_ignore());
+        assert!(matcher(&["cobol".to_string()], &added).is_err());
+
+        assert!("web".parse::<TypeDefinition>().is_err());
+        assert!("web:".parse::<TypeDefinition>().is_err());
+    }
+}
@@ -0,0 +1,132 @@
+use std::time::{Duration, SystemTime};
+
+/// Parses a byte size such as `4096`, `512K`, `10M` or `2G` (binary units).
+pub fn parse_size(input: &str) -> Result<usize, String> {
+    let input = input.trim();
+    let split = input
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(input.len());
+    let (digits, suffix) = input.split_at(split);
+    let value: usize = digits
+        .parse()
+        .map_err(|_| format!("invalid size {:?}", input))?;
+    let multiplier: usize = match suffix.to_ascii_uppercase().as_str() {
+        "" | "B" => 1,
+        "K" | "KB" | "KIB" => 1 << 10,
+        "M" | "MB" | "MIB" => 1 << 20,
+        "G" | "GB" | "GIB" => 1 << 30,
+        _ => return Err(format!("unknown size suffix {:?}", suffix)),
+    };
+    value
+        .checked_mul(multiplier)
+        .ok_or_else(|| format!("size {:?} is too large", input))
+}
+
+/// Parses a duration such as `500ms`, `5s`, `2m`, `1h`, `2d` or `1w`. A bare
+/// number is taken as seconds.
+pub fn parse_duration(input: &str) -> Result<Duration, String> {
+    let input = input.trim();
+    let split = input
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(input.len());
+    let (digits, suffix) = input.split_at(split);
+    let value: u64 = digits
+        .parse()
+        .map_err(|_| format!("invalid duration {:?}", input))?;
+    let seconds: u64 = match suffix {
+        "ms" => return Ok(Duration::from_millis(value)),
+        "" | "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 60 * 60 * 24,
+        "w" => 60 * 60 * 24 * 7,
+        _ => return Err(format!("unknown duration suffix {:?}", suffix)),
+    };
+    value
+        .checked_mul(seconds)
+        .map(Duration::from_secs)
+        .ok_or_else(|| format!("duration {:?} is too long", input))
+}
+
+/// Parses a point in time given either as a date (`2024-01-01`, midnight
+/// UTC) or as a duration before now (`2d`, `12h`).
+pub fn parse_time(input: &str) -> Result<SystemTime, String> {
+    let input = input.trim();
+    let parts: Vec<&str> = input.split('-').collect();
+    if let [year, month, day] = parts[..] {
+        let number = |part: &str| {
+            part.parse::<i64>()
+                .map_err(|_| format!("invalid date {:?}", input))
+        };
+        let (year, month, day) = (number(year)?, number(month)?, number(day)?);
+        // Years past 9999 would overflow the day count long before they
+        // were worth comparing a file's time with.
+        if !(1..=12).contains(&month) || !(1..=31).contains(&day) || year > 9999 {
+            return Err(format!("invalid date {:?}", input));
+        }
+        let days = days_from_civil(year, month, day);
+        let seconds = days
+            .checked_mul(86400)
+            .and_then(|seconds| u64::try_from(seconds).ok())
+            .ok_or_else(|| format!("date {:?} is before 1970", input))?;
+        return Ok(SystemTime::UNIX_EPOCH + Duration::from_secs(seconds));
+    }
+
+    let ago = parse_duration(input)?;
+    SystemTime::now()
+        .checked_sub(ago)
+        .ok_or_else(|| format!("duration {:?} is too long", input))
+}
+
+/// Days since 1970-01-01 of a proleptic Gregorian date (Howard Hinnant's
+/// `days_from_civil`).
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let month_index = (month + 9) % 12;
+    let day_of_year = (153 * month_index + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_size() {
+        assert_eq!(parse_size("4096"), Ok(4096));
+        assert_eq!(parse_size("512K"), Ok(512 * 1024));
+        assert_eq!(parse_size("10m"), Ok(10 * 1024 * 1024));
+        assert_eq!(parse_size("1GiB"), Ok(1 << 30));
+        assert!(parse_size("10X").is_err());
+        assert!(parse_size("M").is_err());
+    }
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("500ms"), Ok(Duration::from_millis(500)));
+        assert_eq!(parse_duration("5"), Ok(Duration::from_secs(5)));
+        assert_eq!(parse_duration("2d"), Ok(Duration::from_secs(2 * 86400)));
+        assert!(parse_duration("5y").is_err());
+        assert!(parse_duration("99999999999999999w").is_err());
+        assert!(parse_duration("18446744073709551615m").is_err());
+    }
+
+    #[test]
+    fn test_parse_time() {
+        let date = parse_time("2024-01-01").unwrap();
+        assert_eq!(
+            date.duration_since(SystemTime::UNIX_EPOCH).unwrap(),
+            Duration::from_secs(1_704_067_200)
+        );
+        let ago = parse_time("2d").unwrap();
+        let elapsed = SystemTime::now().duration_since(ago).unwrap();
+        assert!(elapsed >= Duration::from_secs(2 * 86400));
+        assert!(parse_time("2024-13-01").is_err());
+        assert!(parse_time("yesterday").is_err());
+        assert!(parse_time("99999999999999-01-01").is_err());
+        assert!(parse_time("99999999999999999w").is_err());
+    }
+}
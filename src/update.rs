@@ -0,0 +1,188 @@
+//! `--self-update`: replaces the running binary with the latest GitHub
+//! release's build for this platform.
+//!
+//! Each release carries a `rusty-scout-ARCH-OS` binary per platform (with
+//! `.exe` on Windows), named after [`std::env::consts`], and a `SHA256SUMS`
+//! file listing their checksums as `sha256sum` prints them. A download
+//! whose checksum isn't listed, or doesn't match, is never installed.
+
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::error::Error;
+use std::fs;
+use std::io::Write;
+
+const LATEST_RELEASE: &str = "https://api.github.com/repos/maty7253/rusty-scout/releases/latest";
+
+const USER_AGENT: &str = concat!("rusty-scout/", env!("CARGO_PKG_VERSION"));
+
+/// The largest download accepted, well over any release binary.
+const MAX_DOWNLOAD: u64 = 256 * 1024 * 1024;
+
+#[derive(Deserialize)]
+struct Release {
+    tag_name: String,
+    assets: Vec<Asset>,
+}
+
+#[derive(Deserialize)]
+struct Asset {
+    name: String,
+    browser_download_url: String,
+}
+
+impl Release {
+    fn asset(&self, name: &str) -> Result<&Asset, String> {
+        self.assets
+            .iter()
+            .find(|asset| asset.name == name)
+            .ok_or_else(|| format!("release {} has no {}", self.tag_name, name))
+    }
+}
+
+/// The release binary built for this platform.
+fn asset_name() -> String {
+    format!(
+        "rusty-scout-{}-{}{}",
+        std::env::consts::ARCH,
+        std::env::consts::OS,
+        std::env::consts::EXE_SUFFIX
+    )
+}
+
+/// `1.2.3` from `v1.2.3` or `1.2.3`, as numbers that compare in order.
+/// Missing parts are 0, so `1.0` is the same version as `1.0.0`.
+fn version(tag: &str) -> Option<Vec<u64>> {
+    let mut parts = tag
+        .trim_start_matches('v')
+        .split('.')
+        .map(|part| part.parse().ok())
+        .collect::<Option<Vec<u64>>>()?;
+    if parts.len() < 3 {
+        parts.resize(3, 0);
+    }
+    Some(parts)
+}
+
+/// The checksum `sums`, in `sha256sum`'s format, lists for `name`.
+fn listed_checksum<'a>(sums: &'a str, name: &str) -> Option<&'a str> {
+    sums.lines().find_map(|line| {
+        let (checksum, file) = line.split_once(char::is_whitespace)?;
+        // `sha256sum -b` marks binary files with a `*`.
+        (file.trim_start().trim_start_matches('*') == name).then_some(checksum)
+    })
+}
+
+fn sha256(bytes: &[u8]) -> String {
+    Sha256::digest(bytes)
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+fn download(url: &str) -> Result<Vec<u8>, ureq::Error> {
+    ureq::get(url)
+        .header("User-Agent", USER_AGENT)
+        .call()?
+        .body_mut()
+        .with_config()
+        .limit(MAX_DOWNLOAD)
+        .read_to_vec()
+}
+
+/// Installs the latest release in place of this binary, if it's newer.
+pub(crate) fn self_update() -> Result<(), Box<dyn Error>> {
+    let current = env!("CARGO_PKG_VERSION");
+    let release: Release = ureq::get(LATEST_RELEASE)
+        .header("User-Agent", USER_AGENT)
+        .header("Accept", "application/vnd.github+json")
+        .call()
+        .and_then(|mut response| response.body_mut().read_json())
+        .map_err(|e| format!("couldn't find the latest release: {}", e))?;
+    let latest = version(&release.tag_name)
+        .ok_or_else(|| format!("can't read a version from tag {:?}", release.tag_name))?;
+    if version(current).is_some_and(|current| current >= latest) {
+        println!("rusty-scout {} is the latest release", current);
+        return Ok(());
+    }
+
+    let name = asset_name();
+    let binary = release.asset(&name)?;
+    let sums = String::from_utf8(download(
+        &release.asset("SHA256SUMS")?.browser_download_url,
+    )?)?;
+    let expected = listed_checksum(&sums, &name)
+        .ok_or_else(|| format!("SHA256SUMS has no checksum for {}", name))?;
+    println!("Downloading {} {}...", name, release.tag_name);
+    let bytes = download(&binary.browser_download_url)?;
+    let actual = sha256(&bytes);
+    if !actual.eq_ignore_ascii_case(expected) {
+        return Err(format!(
+            "{} has checksum {}, not the {} SHA256SUMS lists; nothing was changed",
+            name, actual, expected
+        )
+        .into());
+    }
+
+    // Beside the binary rather than in a shared temporary directory, and
+    // only ever a new file, so what's installed is what was checked. It
+    // takes the old binary's permissions when it replaces it.
+    let exe = std::env::current_exe()?;
+    let downloaded = exe.with_file_name(format!(".{}.{}", name, std::process::id()));
+    let mut options = fs::File::options();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o700);
+    }
+    let couldnt_write = |e| format!("couldn't write {}: {}", downloaded.display(), e);
+    let mut file = options.open(&downloaded).map_err(couldnt_write)?;
+    if let Err(e) = file.write_all(&bytes) {
+        let _ = fs::remove_file(&downloaded);
+        return Err(couldnt_write(e).into());
+    }
+    drop(file);
+    let replaced = self_replace::self_replace(&downloaded);
+    let _ = fs::remove_file(&downloaded);
+    replaced?;
+    println!(
+        "Updated rusty-scout from {} to {}",
+        current, release.tag_name
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_release_checks() {
+        assert!(version("v1.10.0") > version("1.9.3"));
+        assert_eq!(version("1.0"), Some(vec![1, 0, 0]));
+        assert_eq!(version("v1.0"), version("1.0.0"));
+        assert!(version("1.0.1") > version("1"));
+        assert_eq!(version("nightly"), None);
+
+        let sums = "\
+0123abcd  rusty-scout-x86_64-linux
+4567ef01 *rusty-scout-x86_64-windows.exe
+";
+        assert_eq!(
+            listed_checksum(sums, "rusty-scout-x86_64-linux"),
+            Some("0123abcd")
+        );
+        assert_eq!(
+            listed_checksum(sums, "rusty-scout-x86_64-windows.exe"),
+            Some("4567ef01")
+        );
+        assert_eq!(listed_checksum(sums, "rusty-scout-aarch64-macos"), None);
+
+        assert_eq!(
+            sha256(b"abc"),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        assert!(asset_name().starts_with("rusty-scout-"));
+    }
+}
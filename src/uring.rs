@@ -0,0 +1,189 @@
+use io_uring::{opcode, types, IoUring};
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io;
+use std::os::fd::AsRawFd;
+
+/// Reads in flight at once on each thread's ring.
+const QUEUE_DEPTH: u32 = 64;
+
+thread_local! {
+    /// Each worker thread's ring, set up on first use. `None` inside means
+    /// the kernel refused one, so the thread doesn't keep asking.
+    static RING: RefCell<Option<Option<IoUring>>> = const { RefCell::new(None) };
+}
+
+/// Reads each file whole, up to the length given for it, submitting the
+/// reads together instead of one system call at a time. Fails as a whole
+/// only when io_uring isn't available or the ring itself fails, so the
+/// caller can fall back.
+pub fn read_files(files: &[(&File, usize)]) -> io::Result<Vec<io::Result<Vec<u8>>>> {
+    read_files_with(files, |ring| ring.submit_and_wait(1))
+}
+
+/// `read_files`, waiting for completions with `submit`.
+fn read_files_with(
+    files: &[(&File, usize)],
+    submit: impl FnMut(&mut IoUring) -> io::Result<usize>,
+) -> io::Result<Vec<io::Result<Vec<u8>>>> {
+    RING.with(|slot| {
+        let mut slot = slot.borrow_mut();
+        let Some(ring) = slot.get_or_insert_with(|| IoUring::new(QUEUE_DEPTH).ok()) else {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "io_uring is not available",
+            ));
+        };
+        let read = read_with(ring, files, submit);
+        if read.is_err() {
+            // Reads may still be in flight, and their completions must never
+            // be taken for those of another call's buffers, so the ring is
+            // dropped and the next call makes a new one.
+            *slot = None;
+        }
+        read
+    })
+}
+
+fn read_with(
+    ring: &mut IoUring,
+    files: &[(&File, usize)],
+    mut submit: impl FnMut(&mut IoUring) -> io::Result<usize>,
+) -> io::Result<Vec<io::Result<Vec<u8>>>> {
+    let mut buffers: Vec<Vec<u8>> = files
+        .iter()
+        .map(|&(_, len)| Vec::with_capacity(len))
+        .collect();
+    let mut errors: Vec<Option<io::Error>> = files.iter().map(|_| None).collect();
+    let mut pending: VecDeque<usize> = (0..files.len()).collect();
+    let mut in_flight = 0;
+
+    loop {
+        while in_flight < QUEUE_DEPTH as usize {
+            let Some(i) = pending.pop_front() else { break };
+            let (file, len) = files[i];
+            let buffer = &mut buffers[i];
+            let filled = buffer.len();
+            if filled >= len {
+                continue;
+            }
+            let read = opcode::Read::new(
+                types::Fd(file.as_raw_fd()),
+                // SAFETY: in bounds, as the buffer was allocated with `len`.
+                unsafe { buffer.as_mut_ptr().add(filled) },
+                (len - filled).min(u32::MAX as usize) as u32,
+            )
+            .offset(filled as u64)
+            .build()
+            .user_data(i as u64);
+            // SAFETY: the buffer isn't touched or moved until the read
+            // completes, which is waited for before returning, or leaked.
+            if unsafe { ring.submission().push(&read) }.is_err() {
+                pending.push_front(i);
+                break;
+            }
+            in_flight += 1;
+        }
+        if in_flight == 0 {
+            break;
+        }
+
+        loop {
+            match submit(ring) {
+                Ok(_) => break,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => {
+                    // The kernel may still write into the buffers, so they
+                    // can never be freed, and the ring is never used again.
+                    std::mem::forget(buffers);
+                    return Err(e);
+                }
+            }
+        }
+        for entry in ring.completion() {
+            in_flight -= 1;
+            let i = entry.user_data() as usize;
+            match entry.result() {
+                // End of file, shorter than it was when opened.
+                0 => {}
+                read if read > 0 => {
+                    let buffer = &mut buffers[i];
+                    // SAFETY: the kernel has just filled these bytes.
+                    unsafe { buffer.set_len(buffer.len() + read as usize) };
+                    pending.push_back(i);
+                }
+                errno => errors[i] = Some(io::Error::from_raw_os_error(-errno)),
+            }
+        }
+    }
+
+    Ok(buffers
+        .into_iter()
+        .zip(errors)
+        .map(|(buffer, error)| match error {
+            Some(e) => Err(e),
+            None => Ok(buffer),
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_files() -> io::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let mut files = Vec::new();
+        for i in 0..100 {
+            let path = dir.path().join(format!("{}.txt", i));
+            std::fs::write(&path, i.to_string().repeat(i))?;
+            files.push((File::open(&path)?, i.to_string().repeat(i)));
+        }
+        let requests: Vec<_> = files
+            .iter()
+            .map(|(file, text)| (file, text.len()))
+            .collect();
+        let Ok(read) = read_files(&requests) else {
+            // Kernels and sandboxes without io_uring.
+            return Ok(());
+        };
+        for (result, (_, text)) in read.into_iter().zip(&files) {
+            assert_eq!(result?, text.as_bytes());
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_failed_ring_is_dropped() -> io::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("a.txt");
+        std::fs::write(&path, "a".repeat(4096))?;
+        let file = File::open(&path)?;
+        if read_files(&[(&file, 4096)]).is_err() {
+            // Kernels and sandboxes without io_uring.
+            return Ok(());
+        }
+
+        // The read is in the kernel's hands when the wait fails.
+        let failed = read_files_with(&[(&file, 4096)], |ring| {
+            ring.submit()?;
+            Err(io::Error::other("injected"))
+        });
+        assert_eq!(failed.unwrap_err().to_string(), "injected");
+        assert!(RING.with(|slot| slot.borrow().is_none()));
+
+        // Its completion isn't taken for this call's, which reads a
+        // different length into a different buffer.
+        let other = dir.path().join("b.txt");
+        std::fs::write(&other, "b".repeat(10))?;
+        let other = File::open(&other)?;
+        let read = read_files(&[(&other, 10), (&file, 4096)])?;
+        assert_eq!(read[0].as_ref().unwrap(), &b"b".repeat(10));
+        assert_eq!(read[1].as_ref().unwrap(), &b"a".repeat(4096));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,247 @@
+//! Searching files that aren't on disk, such as logs held in memory by a
+//! browser, through the [`FileSystem`] trait.
+
+use crate::clock::Instant;
+use crate::result::SearchResult;
+use crate::searcher::Searcher;
+use crate::walker::is_hidden;
+use crate::SearchError;
+use ignore::overrides::Override;
+use rayon::prelude::*;
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::io;
+use std::ops::Bound;
+use std::path::{Path, PathBuf};
+
+/// Files to search other than those on disk, for [`Searcher::search_in`].
+pub trait FileSystem: Sync {
+    /// Every file below `root`, or `root` itself if it's a file.
+    fn files(&self, root: &Path) -> io::Result<Vec<PathBuf>>;
+
+    /// The contents of a file listed by `files`.
+    fn read(&self, path: &Path) -> io::Result<Cow<'_, [u8]>>;
+}
+
+/// Files held in memory, by path:
+///
+/// ```
+/// use rusty_scout::{MemoryTree, Searcher};
+/// use std::path::Path;
+///
+/// let mut tree = MemoryTree::new();
+/// tree.insert("logs/app.log", "started\nerror: disk full\n");
+/// let searcher = Searcher::builder().pattern("error").build()?;
+/// let results = searcher.search_in(&tree, Path::new("logs"))?;
+/// assert_eq!(results[0].line_number, 2);
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct MemoryTree {
+    files: BTreeMap<PathBuf, Vec<u8>>,
+}
+
+impl MemoryTree {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a file, replacing any already at `path`.
+    pub fn insert(&mut self, path: impl Into<PathBuf>, contents: impl Into<Vec<u8>>) {
+        self.files.insert(path.into(), contents.into());
+    }
+
+    pub fn remove(&mut self, path: &Path) -> Option<Vec<u8>> {
+        self.files.remove(path)
+    }
+
+    pub fn len(&self) -> usize {
+        self.files.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+}
+
+impl FileSystem for MemoryTree {
+    fn files(&self, root: &Path) -> io::Result<Vec<PathBuf>> {
+        // Paths sort by component, so those below `root` are together.
+        Ok(self
+            .files
+            .range::<Path, _>((Bound::Included(root), Bound::Unbounded))
+            .map(|(path, _)| path)
+            .take_while(|path| path.starts_with(root))
+            .cloned()
+            .collect())
+    }
+
+    fn read(&self, path: &Path) -> io::Result<Cow<'_, [u8]>> {
+        match self.files.get(path) {
+            Some(contents) => Ok(Cow::Borrowed(contents)),
+            None => Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("{} isn't in the tree", path.display()),
+            )),
+        }
+    }
+}
+
+impl Searcher {
+    /// Searches the files of `fs` below `root` as [`Searcher::search`]
+    /// searches a directory. Globs, types, hidden files and the depth and
+    /// size limits apply as they do on disk, but there are no ignore files.
+    pub fn search_in(
+        &self,
+        fs: &dyn FileSystem,
+        root: &Path,
+    ) -> Result<Vec<SearchResult>, Box<dyn Error>> {
+        if self.write {
+            return Err(Box::new(SearchError::new(
+                "InvalidOptions",
+                "only files on disk can be rewritten",
+            )));
+        }
+        self.clear();
+        let overrides = self.overrides(root)?;
+        let files: Vec<PathBuf> = fs
+            .files(root)?
+            .into_iter()
+            .filter(|path| self.should_search_listed(root, path, &overrides))
+            .collect();
+        files.par_iter().for_each(|path| {
+            if self.is_cancelled() {
+                return;
+            }
+            let opened = Instant::now();
+            let searched = match fs.read(path) {
+                Ok(bytes) => {
+                    self.stats.record_read(bytes.len() as u64, opened.elapsed());
+                    match self.max_filesize {
+                        Some(limit) if bytes.len() > limit => {
+                            self.skip(
+                                path,
+                                format!("{} bytes, over the {} byte limit", bytes.len(), limit),
+                            );
+                            Ok(Vec::new())
+                        }
+                        _ => self.search_contents(path, opened, &bytes),
+                    }
+                }
+                Err(e) => Err(e.into()),
+            };
+            self.stats.record_file(opened.elapsed());
+            self.collect_file(path, searched);
+        });
+        Ok(self.take_results())
+    }
+
+    /// Whether a file listed below `root` passes the filters a walk on disk
+    /// would apply to it and the directories above it.
+    fn should_search_listed(&self, root: &Path, path: &Path, overrides: &Override) -> bool {
+        let Ok(relative) = path.strip_prefix(root) else {
+            return false;
+        };
+        // Like a file named on the command line, `root` itself is always
+        // searched.
+        if relative.as_os_str().is_empty() {
+            return true;
+        }
+        if self
+            .max_depth
+            .is_some_and(|depth| relative.components().count() > depth + 1)
+        {
+            return false;
+        }
+        let dirs = relative
+            .ancestors()
+            .skip(1)
+            .filter(|dir| !dir.as_os_str().is_empty());
+        for dir in dirs {
+            if (!self.hidden && is_hidden(dir))
+                || overrides.matched(root.join(dir), true).is_ignore()
+            {
+                return false;
+            }
+        }
+        // Only the size is known, and it's checked once the file is read.
+        (self.hidden || !is_hidden(path))
+            && !overrides.matched(path, false).is_ignore()
+            && !self.types.matched(path, false).is_ignore()
+            && self.should_search_file(path)
+            && self.passes_metadata_filters(path, None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::options::{SearchOptions, SearcherBuilder};
+
+    fn tree() -> MemoryTree {
+        let mut tree = MemoryTree::new();
+        tree.insert("logs/app.log", "start\nERROR: disk full\n");
+        tree.insert("logs/old/app.log", "error: timeout\n");
+        tree.insert("logs/.hidden.log", "error\n");
+        tree.insert("logs/notes.txt", "no error here\n");
+        tree.insert("other/app.log", "error\n");
+        tree
+    }
+
+    #[test]
+    fn test_search_in() -> Result<(), Box<dyn Error>> {
+        let tree = tree();
+        assert_eq!(tree.len(), 5);
+        let search = |options: SearchOptions, root: &str| -> Result<Vec<String>, Box<dyn Error>> {
+            let searcher = SearcherBuilder::from_options(SearchOptions {
+                patterns: vec!["error".to_string()],
+                ignore_case: true,
+                ..options
+            })
+            .build()?;
+            let mut paths: Vec<String> = searcher
+                .search_in(&tree, Path::new(root))?
+                .iter()
+                .map(|r| r.file_path.display().to_string())
+                .collect();
+            paths.sort();
+            Ok(paths)
+        };
+
+        assert_eq!(
+            search(SearchOptions::default(), "logs")?,
+            ["logs/app.log", "logs/notes.txt", "logs/old/app.log"]
+        );
+        assert_eq!(
+            search(
+                SearchOptions {
+                    globs: vec!["*.log".to_string()],
+                    exclude_dirs: vec!["old".to_string()],
+                    hidden: true,
+                    ..SearchOptions::default()
+                },
+                "logs"
+            )?,
+            ["logs/.hidden.log", "logs/app.log"]
+        );
+        assert_eq!(
+            search(
+                SearchOptions {
+                    max_depth: Some(1),
+                    max_filesize: Some(15),
+                    ..SearchOptions::default()
+                },
+                ""
+            )?,
+            ["logs/notes.txt", "other/app.log"]
+        );
+        assert_eq!(
+            search(SearchOptions::default(), "logs/old/app.log")?,
+            ["logs/old/app.log"]
+        );
+        assert!(search(SearchOptions::default(), "missing")?.is_empty());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,1234 @@
+use crate::cli::{IoBackend, LongLines};
+use crate::clock::Instant;
+use crate::result::SearchResult;
+use crate::searcher::{Event, Searcher};
+use crate::SearchError;
+#[cfg(all(feature = "io-uring", target_os = "linux"))]
+use crate::{
+    searcher::{ReadStrategy, SearchTarget},
+    uring,
+};
+use ignore::overrides::{Override, OverrideBuilder};
+use indicatif::{ProgressBar, ProgressStyle};
+use rayon::prelude::*;
+use std::collections::HashSet;
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+/// Ignore file, in gitignore syntax, that only this tool reads.
+pub(crate) const IGNORE_FILENAME: &str = ".rscoutignore";
+
+/// Most files handed to a worker at once by `search_files`.
+pub(crate) const MAX_BATCH_FILES: usize = 256;
+
+/// Batches per thread `search_files` aims for, so a slow batch near the end
+/// doesn't leave the other threads idle for long.
+pub(crate) const BATCHES_PER_THREAD: u64 = 16;
+
+/// Splits a `--files-from` list on NULs if it has any (as from `find -print0`
+/// or `git ls-files -z`), otherwise on newlines.
+pub(crate) fn parse_file_list(contents: &str) -> Vec<PathBuf> {
+    let paths: Vec<&str> = if contents.contains('\0') {
+        contents.split('\0').collect()
+    } else {
+        contents.lines().collect()
+    };
+    paths
+        .into_iter()
+        .filter(|path| !path.is_empty())
+        .map(PathBuf::from)
+        .collect()
+}
+
+/// Splits files of the given sizes into consecutive ranges of roughly equal
+/// total size, enough for each of `threads` to get several. A file bigger
+/// than a batch's share gets one to itself.
+pub(crate) fn batch_files(sizes: &[u64], threads: usize) -> Vec<std::ops::Range<usize>> {
+    let total: u64 = sizes.iter().sum();
+    let target = (total / (threads.max(1) as u64 * BATCHES_PER_THREAD)).max(1);
+    let mut batches = Vec::new();
+    let mut start = 0;
+    let mut bytes = 0;
+    for (i, &size) in sizes.iter().enumerate() {
+        bytes += size;
+        if bytes >= target || i + 1 - start == MAX_BATCH_FILES {
+            batches.push(start..i + 1);
+            start = i + 1;
+            bytes = 0;
+        }
+    }
+    if start < sizes.len() {
+        batches.push(start..sizes.len());
+    }
+    batches
+}
+
+/// The owning user and permission bits of a file; unknown off Unix, so
+/// `--owner` and `--perm` match nothing there.
+#[cfg(unix)]
+pub(crate) fn owner_and_mode(metadata: &fs::Metadata) -> Option<(u32, u32)> {
+    use std::os::unix::fs::MetadataExt;
+    Some((metadata.uid(), metadata.mode()))
+}
+
+#[cfg(not(unix))]
+pub(crate) fn owner_and_mode(_metadata: &fs::Metadata) -> Option<(u32, u32)> {
+    None
+}
+
+/// The (device, inode) of a file with more than one hard link.
+#[cfg(unix)]
+pub(crate) fn hardlink_id(metadata: &fs::Metadata) -> Option<(u64, u64)> {
+    use std::os::unix::fs::MetadataExt;
+    (metadata.nlink() > 1).then(|| (metadata.dev(), metadata.ino()))
+}
+
+#[cfg(not(unix))]
+pub(crate) fn hardlink_id(_metadata: &fs::Metadata) -> Option<(u64, u64)> {
+    None
+}
+
+/// Identifies a directory independently of the path it was reached by.
+#[cfg(unix)]
+pub(crate) fn dir_id(path: &Path) -> Option<(u64, u64)> {
+    use std::os::unix::fs::MetadataExt;
+    let metadata = fs::metadata(path).ok()?;
+    Some((metadata.dev(), metadata.ino()))
+}
+
+#[cfg(not(unix))]
+pub(crate) fn dir_id(path: &Path) -> Option<PathBuf> {
+    fs::canonicalize(path).ok()
+}
+
+/// The (link, ancestor) of a walker error caused by a symlink cycle.
+pub(crate) fn symlink_loop(error: &ignore::Error) -> Option<(&Path, &Path)> {
+    match error {
+        ignore::Error::Loop { ancestor, child } => Some((child, ancestor)),
+        ignore::Error::WithPath { err, .. } | ignore::Error::WithDepth { err, .. } => {
+            symlink_loop(err)
+        }
+        _ => None,
+    }
+}
+
+/// What `--verbose` says about a walked entry that's a symlink being
+/// followed.
+pub(crate) fn followed_symlink(entry: &ignore::DirEntry) -> Option<String> {
+    if !entry.path_is_symlink() {
+        return None;
+    }
+    Some(match fs::read_link(entry.path()) {
+        Ok(target) => format!(
+            "following symlink {} to {}",
+            entry.path().display(),
+            target.display()
+        ),
+        Err(_) => format!("following symlink {}", entry.path().display()),
+    })
+}
+
+pub(crate) fn is_hidden(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .is_some_and(|name| name.starts_with('.'))
+}
+
+impl Searcher {
+    pub(crate) fn should_search_file(&self, path: &Path) -> bool {
+        if let Some(path_pattern) = &self.path_pattern {
+            if !path_pattern.is_match(&path.display().to_string()) {
+                log::info!("skipping {}: doesn't match --path-pattern", path.display());
+                return false;
+            }
+        }
+        if self.extensions.iter().any(|ext| ext == "*") {
+            return true;
+        }
+
+        // Each entry matches a suffix after a `.` (so `gz` and `tar.gz` both
+        // match `a.tar.gz`) or the whole file name (`Makefile`), ignoring case.
+        let matched = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .map(str::to_lowercase)
+            .is_some_and(|name| {
+                self.extensions.iter().any(|ext| {
+                    name == *ext
+                        || name
+                            .strip_suffix(ext.as_str())
+                            .is_some_and(|stem| stem.len() > 1 && stem.ends_with('.'))
+                })
+            });
+        if !matched {
+            log::info!("skipping {}: not one of the -e extensions", path.display());
+        }
+        matched
+    }
+
+    /// Checks `--newer-than`, `--older-than` and `--max-filesize`, recording
+    /// files over the size limit as skipped.
+    pub(crate) fn passes_metadata_filters(
+        &self,
+        path: &Path,
+        metadata: Option<&fs::Metadata>,
+    ) -> bool {
+        if self.newer_than.is_some() || self.older_than.is_some() {
+            let Some(modified) = metadata.and_then(|m| m.modified().ok()) else {
+                log::info!("skipping {}: modification time unknown", path.display());
+                return false;
+            };
+            if self.newer_than.is_some_and(|newer| modified <= newer)
+                || self.older_than.is_some_and(|older| modified >= older)
+            {
+                log::info!(
+                    "skipping {}: modified outside --newer-than and --older-than",
+                    path.display()
+                );
+                return false;
+            }
+        }
+        if self.owner.is_some() || self.perm.is_some() {
+            let Some((uid, mode)) = metadata.and_then(owner_and_mode) else {
+                log::info!("skipping {}: owner and mode unknown", path.display());
+                return false;
+            };
+            if self.owner.is_some_and(|owner| owner.0 != uid)
+                || self.perm.is_some_and(|perm| !perm.matches(mode))
+            {
+                log::info!(
+                    "skipping {}: --owner or --perm doesn't match",
+                    path.display()
+                );
+                return false;
+            }
+        }
+        if let (Some(limit), Some(metadata)) = (self.max_filesize, metadata) {
+            if metadata.len() > limit as u64 {
+                let reason = format!("{} bytes, over the {} byte limit", metadata.len(), limit);
+                log::info!("skipping {}: {}", path.display(), reason);
+                self.skip(path, reason);
+                return false;
+            }
+        }
+        if self.hardlink_dedup {
+            if let Some(id) = metadata.and_then(hardlink_id) {
+                let first = self.seen_links.lock().unwrap().insert(id);
+                if !first {
+                    log::info!(
+                        "skipping {}: a hard link to a file already searched",
+                        path.display()
+                    );
+                }
+                return first;
+            }
+        }
+        true
+    }
+
+    /// The `--glob`, `--exclude` and `--exclude-dir` filters for a walk
+    /// from `root`.
+    pub(crate) fn overrides(&self, root: &Path) -> Result<Override, ignore::Error> {
+        let mut overrides = OverrideBuilder::new(root);
+        for glob in &self.globs {
+            overrides.add(glob)?;
+        }
+        for glob in &self.exclude {
+            overrides.add(&format!("!{}", glob))?;
+        }
+        for name in &self.exclude_dir {
+            overrides.add(&format!("!{}/", name))?;
+        }
+        overrides.build()
+    }
+
+    pub(crate) fn search_dir(&self, dir: &Path) -> Result<(), Box<dyn Error>> {
+        let builder = self.walk_builder(dir)?;
+        let pb = self.add_progress_bar();
+        let source = dir.display().to_string();
+
+        // Ordering by recency needs every file up front, as does reading
+        // them in batches. Listing the tree before --write touches it also
+        // keeps backups and temporary files out of the search.
+        if self.recent_first || self.io_backend != IoBackend::Std || self.write {
+            let mut files: Vec<_> = builder
+                .build()
+                .filter_map(|entry| self.walk_entry(entry))
+                .map(|(path, _)| path)
+                .collect();
+            if self.recent_first {
+                files = self.order_files(files);
+            }
+            self.search_files(&files, &pb, &source);
+            return Ok(());
+        }
+
+        // Files are searched by the walker's threads as they're found, so
+        // results start arriving before the whole tree has been listed.
+        pb.set_message(format!("Searching {}...", source));
+        let before = self.skip_counts();
+        let searched = AtomicUsize::new(0);
+        builder.build_parallel().run(|| {
+            Box::new(|entry| {
+                if self.is_cancelled() {
+                    return ignore::WalkState::Quit;
+                }
+                if let Some((path, size)) = self.walk_entry(entry) {
+                    // The total grows as the walk goes, so the ETA settles
+                    // once the whole tree has been seen.
+                    pb.inc_length(size);
+                    self.search_and_collect(&path, None);
+                    pb.inc(size);
+                    searched.fetch_add(1, Ordering::Relaxed);
+                }
+                ignore::WalkState::Continue
+            })
+        });
+        self.finish_progress(&pb, searched.into_inner(), &source, before);
+        Ok(())
+    }
+
+    /// The walk of `dir` that `search_dir` makes, with ignore files and the
+    /// `--glob`, `--exclude` and depth limits applied.
+    pub(crate) fn walk_builder(&self, dir: &Path) -> Result<ignore::WalkBuilder, Box<dyn Error>> {
+        // Inside a git repository, .git/info/exclude and the global gitignore
+        // (core.excludesFile, or ~/.config/git/ignore) apply as well.
+        let vcs_ignores = !self.no_ignore && !self.no_ignore_vcs;
+        if !self.no_ignore {
+            // The walker never lists what ignore files leave out.
+            log::info!(
+                "walking {}, leaving out what {} files ignore (--no-ignore searches it)",
+                dir.display(),
+                if vcs_ignores {
+                    ".gitignore, .ignore and .rscoutignore"
+                } else {
+                    ".ignore and .rscoutignore"
+                }
+            );
+        }
+        let mut builder = ignore::WalkBuilder::new(dir);
+        builder
+            .hidden(!self.hidden)
+            .ignore(!self.no_ignore)
+            .git_ignore(vcs_ignores)
+            .git_exclude(vcs_ignores)
+            .git_global(vcs_ignores)
+            .parents(!self.no_ignore && !self.no_ignore_parent)
+            // Excluded directories are pruned by the walker, so nothing
+            // beneath them is ever listed.
+            .overrides(self.overrides(dir)?)
+            .types(self.types.clone())
+            .max_depth(self.max_depth.map(|depth| depth + 1))
+            .follow_links(self.follow)
+            .same_file_system(self.one_file_system)
+            .threads(self.threads);
+        if !self.no_ignore {
+            builder.add_custom_ignore_filename(IGNORE_FILENAME);
+        }
+        if self.follow {
+            // The walker already refuses to re-enter an ancestor; this also
+            // stops two links to the same directory being searched twice.
+            let visited = Mutex::new(HashSet::new());
+            builder.filter_entry(move |entry| {
+                if !entry.file_type().is_some_and(|ft| ft.is_dir()) {
+                    return true;
+                }
+                match dir_id(entry.path()) {
+                    Some(id) => {
+                        let first = visited.lock().unwrap().insert(id);
+                        if !first && entry.path_is_symlink() {
+                            log::info!(
+                                "not following symlink {}: its directory is already searched",
+                                entry.path().display()
+                            );
+                        }
+                        first
+                    }
+                    None => true,
+                }
+            });
+        }
+        Ok(builder)
+    }
+
+    /// Returns the path and size of a walked entry that should be searched,
+    /// recording symlink loops as skipped.
+    pub(crate) fn walk_entry(
+        &self,
+        entry: Result<ignore::DirEntry, ignore::Error>,
+    ) -> Option<(PathBuf, u64)> {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(e) => {
+                self.walk_error(&e);
+                return None;
+            }
+        };
+        if self.follow {
+            if let Some(followed) = followed_symlink(&entry) {
+                log::info!("{}", followed);
+            }
+        }
+        let metadata = entry.metadata().ok();
+        if !entry.file_type().is_some_and(|ft| ft.is_file())
+            || self.is_hidden_entry(&entry)
+            || !self.should_search_file(entry.path())
+            || !self.passes_metadata_filters(entry.path(), metadata.as_ref())
+        {
+            return None;
+        }
+        Some((entry.into_path(), metadata.map_or(0, |m| m.len())))
+    }
+
+    /// Whether a walked file is hidden and hidden files aren't searched.
+    /// The walker lets through hidden files that a `--type` selects.
+    pub(crate) fn is_hidden_entry(&self, entry: &ignore::DirEntry) -> bool {
+        !self.hidden && entry.depth() > 0 && is_hidden(entry.path())
+    }
+
+    /// Records what stopped the walk reaching an entry: symlink loops as
+    /// skipped, anything else as an error.
+    pub(crate) fn walk_error(&self, e: &ignore::Error) {
+        match symlink_loop(e) {
+            Some((child, ancestor)) => self.skip(
+                child,
+                format!("symlink loop back to {}", ancestor.display()),
+            ),
+            None => self.error(format_args!("{}", e)),
+        }
+    }
+
+    /// A progress bar counting the bytes searched out of those found.
+    pub(crate) fn add_progress_bar(&self) -> ProgressBar {
+        let pb = self.progress.add(ProgressBar::new(0));
+        pb.set_style(
+            ProgressStyle::default_bar()
+                .template(
+                    "{spinner:.green} {msg} {wide_bar} {bytes}/{total_bytes} \
+                     ({binary_bytes_per_sec}, {eta} left)",
+                )
+                .unwrap(),
+        );
+        pb
+    }
+
+    pub(crate) fn search_listed(&self, paths: Vec<PathBuf>) {
+        let files: Vec<PathBuf> = paths
+            .into_iter()
+            .filter(|path| self.should_search_file(path))
+            .filter(|path| self.passes_metadata_filters(path, fs::metadata(path).ok().as_ref()))
+            .collect();
+        let files = self.order_files(files);
+        let pb = self.add_progress_bar();
+        self.search_files(&files, &pb, "the file list");
+    }
+
+    /// Puts the most recently modified files first with `--recent-first`.
+    pub(crate) fn order_files(&self, mut files: Vec<PathBuf>) -> Vec<PathBuf> {
+        if self.recent_first {
+            files.sort_by_cached_key(|path| {
+                std::cmp::Reverse(fs::metadata(path).and_then(|m| m.modified()).ok())
+            });
+        }
+        files
+    }
+
+    pub(crate) fn search_files(&self, files: &[PathBuf], pb: &ProgressBar, source: &str) {
+        pb.set_message(format!("Searching {} files in {}...", files.len(), source));
+        let before = self.skip_counts();
+
+        let sizes: Vec<u64> = files
+            .par_iter()
+            .map(|path| fs::metadata(path).map_or(0, |m| m.len()))
+            .collect();
+        pb.set_length(sizes.iter().sum());
+
+        // One task per file costs more than searching a tiny file, so files
+        // go to the workers in batches of roughly equal size.
+        let batches = batch_files(&sizes, rayon::current_num_threads());
+        let search_batch = |batch: &std::ops::Range<usize>| {
+            let files = &files[batch.clone()];
+            let mut prefetched = self.prefetch(files).into_iter();
+            for (path, &size) in files.iter().zip(&sizes[batch.clone()]) {
+                if self.is_cancelled() {
+                    return;
+                }
+                self.search_and_collect(path, prefetched.next().flatten());
+                pb.inc(size);
+            }
+        };
+        if self.recent_first {
+            // Hand batches to the workers in order instead of splitting the
+            // list into ranges, so the newest files are searched first.
+            batches.iter().par_bridge().for_each(search_batch);
+        } else {
+            batches.par_iter().for_each(search_batch);
+        }
+
+        self.finish_progress(pb, files.len(), source, before);
+    }
+
+    /// Reads a batch of files together with `--io-backend uring`, giving
+    /// the contents of each one that would have been read whole. Nothing is
+    /// read with the standard backend, or if io_uring isn't available.
+    #[cfg(all(feature = "io-uring", target_os = "linux"))]
+    pub(crate) fn prefetch(&self, files: &[PathBuf]) -> Vec<Option<Vec<u8>>> {
+        let needs_contents = match &self.target {
+            SearchTarget::Files => self.file_predicates.is_some() || !self.mime.is_empty(),
+            _ => !self.match_paths || self.file_predicates.is_some() || !self.mime.is_empty(),
+        };
+        if self.io_backend != IoBackend::Uring || !needs_contents {
+            return Vec::new();
+        }
+
+        let started = Instant::now();
+        let streamable = self.streamable();
+        // Files that would be mapped or streamed are left to `search_file`.
+        let opened: Vec<Option<(fs::File, usize)>> = files
+            .iter()
+            .map(|path| {
+                let file = fs::File::open(path).ok()?;
+                let len = file.metadata().ok()?.len();
+                (self.read_strategy(len, streamable) == ReadStrategy::Read)
+                    .then_some((file, len as usize))
+            })
+            .collect();
+        let requests: Vec<_> = opened
+            .iter()
+            .flatten()
+            .map(|(file, len)| (file, *len))
+            .collect();
+        let Ok(read) = uring::read_files(&requests) else {
+            return Vec::new();
+        };
+
+        let mut read = read.into_iter();
+        let contents: Vec<_> = opened
+            .iter()
+            .map(|opened| opened.as_ref().and_then(|_| read.next()?.ok()))
+            .collect();
+        let bytes = contents
+            .iter()
+            .flatten()
+            .map(|bytes| bytes.len() as u64)
+            .sum();
+        self.stats.record_read(bytes, started.elapsed());
+        contents
+    }
+
+    #[cfg(not(all(feature = "io-uring", target_os = "linux")))]
+    pub(crate) fn prefetch(&self, _files: &[PathBuf]) -> Vec<Option<Vec<u8>>> {
+        Vec::new()
+    }
+
+    pub(crate) fn search_and_collect(&self, path: &Path, prefetched: Option<Vec<u8>>) {
+        let started = Instant::now();
+        let searched = self.search_file_from(path, prefetched);
+        self.stats.record_file(started.elapsed());
+        log::debug!("searched {} in {:.1?}", path.display(), started.elapsed());
+        self.collect_file(path, searched);
+    }
+
+    /// Keeps what searching `path` found, or reports why it couldn't be.
+    pub(crate) fn collect_file(
+        &self,
+        path: &Path,
+        searched: Result<Vec<SearchResult>, Box<dyn Error>>,
+    ) {
+        match searched {
+            Ok(results) if results.is_empty() => {}
+            Ok(_) if self.files_with_matches => self.collect(vec![self.path_result(path)]),
+            Ok(results) => self.collect(results),
+            // Only timeouts and patterns that gave up are listed at the end;
+            // other errors, mostly unreadable files, only go to a sink.
+            Err(e) => match e.downcast_ref::<SearchError>() {
+                Some(e) if e.kind == "Timeout" || e.kind == "BacktrackLimit" => {
+                    self.skip(path, e.message.clone())
+                }
+                _ => {
+                    self.error(format_args!("{}: {}", path.display(), e));
+                    self.send(Event::FileError(path.to_path_buf(), e.to_string()))
+                }
+            },
+        }
+    }
+
+    /// Binary files and long lines skipped so far, for `finish_progress`.
+    pub(crate) fn skip_counts(&self) -> (usize, usize) {
+        (
+            self.binary_skipped.load(Ordering::Relaxed),
+            self.long_lines_seen.load(Ordering::Relaxed),
+        )
+    }
+
+    /// Reports how many of `files` were searched from `source`, with what
+    /// was skipped since `before` was taken from `skip_counts`.
+    pub(crate) fn finish_progress(
+        &self,
+        pb: &ProgressBar,
+        files: usize,
+        source: &str,
+        before: (usize, usize),
+    ) {
+        pb.set_style(
+            ProgressStyle::default_spinner()
+                .template("{spinner:.green} {msg}")
+                .unwrap(),
+        );
+        let (binary, long_lines) = self.skip_counts();
+        let binary_skipped = binary - before.0;
+        let long_lines = long_lines - before.1;
+        let mut notes = Vec::new();
+        if binary_skipped > 0 {
+            notes.push(format!("{} binary files skipped", binary_skipped));
+        }
+        if long_lines > 0 {
+            let verb = match self.long_lines {
+                LongLines::Truncate => "truncated",
+                LongLines::Skip => "skipped",
+            };
+            notes.push(format!("{} long lines {}", long_lines, verb));
+        }
+        let searched = format!("Searched {} files in {}", files - binary_skipped, source);
+        if notes.is_empty() {
+            pb.finish_with_message(searched);
+        } else {
+            pb.finish_with_message(format!("{} ({})", searched, notes.join(", ")));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::parse_args;
+    use std::fs::File;
+    use std::time::{Duration, SystemTime};
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_exclude() -> Result<(), Box<dyn Error>> {
+        let dir = tempdir()?;
+        for sub in ["src", "target/debug", "web/node_modules/pkg"] {
+            fs::create_dir_all(dir.path().join(sub))?;
+            fs::write(dir.path().join(sub).join("file.txt"), "needle\n")?;
+        }
+
+        let args = parse_args(&[
+            "-p",
+            "needle",
+            "--exclude",
+            "target/**",
+            "--exclude-dir",
+            "node_modules",
+            "-d",
+            dir.path().to_str().unwrap(),
+        ]);
+        let searcher = Searcher::new(&args)?;
+        searcher.search_dir(dir.path())?;
+
+        let results = searcher.results.lock().unwrap();
+        assert_eq!(results.len(), 1);
+        assert!(results[0].file_path.ends_with("src/file.txt"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_globs() -> Result<(), Box<dyn Error>> {
+        let dir = tempdir()?;
+        fs::create_dir(dir.path().join("pkg"))?;
+        for name in ["main.go", "pkg/util.go", "pkg/util_test.go", "README.md"] {
+            fs::write(dir.path().join(name), "needle\n")?;
+        }
+
+        let args = parse_args(&[
+            "-p",
+            "needle",
+            "-g",
+            "*.go",
+            "-g",
+            "!*_test.go",
+            "-d",
+            dir.path().to_str().unwrap(),
+        ]);
+        let searcher = Searcher::new(&args)?;
+        searcher.search_dir(dir.path())?;
+
+        let mut found: Vec<_> = searcher
+            .results
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|r| r.file_path.strip_prefix(dir.path()).unwrap().to_path_buf())
+            .collect();
+        found.sort();
+        assert_eq!(
+            found,
+            [PathBuf::from("main.go"), PathBuf::from("pkg/util.go")]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_file_type() -> Result<(), Box<dyn Error>> {
+        let dir = tempdir()?;
+        for name in ["main.rs", "main.py", "notes.md"] {
+            fs::write(dir.path().join(name), "needle\n")?;
+        }
+
+        let args = parse_args(&[
+            "-p",
+            "needle",
+            "-t",
+            "rust",
+            "-t",
+            "md",
+            "-d",
+            dir.path().to_str().unwrap(),
+        ]);
+        let searcher = Searcher::new(&args)?;
+        searcher.search_dir(dir.path())?;
+        assert_eq!(searcher.results.lock().unwrap().len(), 2);
+
+        let args = parse_args(&[
+            "-p",
+            "needle",
+            "--type-add",
+            "notes:*.md",
+            "-t",
+            "notes",
+            "-d",
+            dir.path().to_str().unwrap(),
+        ]);
+        let searcher = Searcher::new(&args)?;
+        searcher.search_dir(dir.path())?;
+        assert_eq!(searcher.results.lock().unwrap().len(), 1);
+
+        let args = parse_args(&["-p", "needle", "-t", "cobol"]);
+        assert!(Searcher::new(&args).is_err());
+
+        // A type only narrows the search: ignored and hidden files of that
+        // type stay out.
+        let repo = tempdir()?;
+        fs::create_dir(repo.path().join(".git"))?;
+        fs::write(repo.path().join(".gitignore"), "gen.rs\n")?;
+        for name in ["a.rs", "gen.rs", ".hid.rs"] {
+            fs::write(repo.path().join(name), "needle\n")?;
+        }
+        let args = parse_args(&["-p", "needle", "-t", "rust"]);
+        let searcher = Searcher::new(&args)?;
+        searcher.search_dir(repo.path())?;
+        let results = searcher.results.lock().unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].file_path, repo.path().join("a.rs"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_max_depth() -> Result<(), Box<dyn Error>> {
+        let dir = tempdir()?;
+        fs::create_dir_all(dir.path().join("a/b"))?;
+        for name in ["top.txt", "a/one.txt", "a/b/two.txt"] {
+            fs::write(dir.path().join(name), "needle\n")?;
+        }
+
+        for (depth, expected) in [("0", 1), ("1", 2), ("2", 3)] {
+            let args = parse_args(&[
+                "-p",
+                "needle",
+                "--max-depth",
+                depth,
+                "-d",
+                dir.path().to_str().unwrap(),
+            ]);
+            let searcher = Searcher::new(&args)?;
+            searcher.search_dir(dir.path())?;
+            assert_eq!(searcher.results.lock().unwrap().len(), expected);
+        }
+
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_follow_symlinks() -> Result<(), Box<dyn Error>> {
+        let dir = tempdir()?;
+        fs::create_dir_all(dir.path().join("real/inner"))?;
+        fs::write(dir.path().join("real/inner/file.txt"), "needle\n")?;
+        std::os::unix::fs::symlink(dir.path().join("real"), dir.path().join("link"))?;
+        std::os::unix::fs::symlink(dir.path().join("real"), dir.path().join("real/inner/loop"))?;
+
+        let args = parse_args(&["-p", "needle", "-d", dir.path().to_str().unwrap()]);
+        let searcher = Searcher::new(&args)?;
+        searcher.search_dir(dir.path())?;
+        assert_eq!(searcher.results.lock().unwrap().len(), 1);
+
+        let args = parse_args(&["-p", "needle", "-L", "-d", dir.path().to_str().unwrap()]);
+        let searcher = Searcher::new(&args)?;
+        searcher.search_dir(dir.path())?;
+        // `link` and `real` are the same directory, so it's only searched
+        // once, and `real/inner/loop` is reported instead of followed.
+        assert_eq!(searcher.results.lock().unwrap().len(), 1);
+        assert_eq!(searcher.skipped.lock().unwrap().len(), 1);
+
+        // What --verbose says about following a link out of the tree.
+        fs::create_dir(dir.path().join("tree"))?;
+        std::os::unix::fs::symlink(dir.path().join("real"), dir.path().join("tree/out"))?;
+        let followed: Vec<String> = searcher
+            .walk_builder(&dir.path().join("tree"))?
+            .build()
+            .filter_map(|entry| followed_symlink(&entry.ok()?))
+            .collect();
+        assert_eq!(
+            followed,
+            [format!(
+                "following symlink {} to {}",
+                dir.path().join("tree/out").display(),
+                dir.path().join("real").display()
+            )]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_hidden() -> Result<(), Box<dyn Error>> {
+        let dir = tempdir()?;
+        fs::create_dir(dir.path().join(".github"))?;
+        fs::write(dir.path().join(".github/ci.yml"), "needle\n")?;
+        fs::write(dir.path().join(".env"), "needle\n")?;
+        fs::write(dir.path().join("main.rs"), "needle\n")?;
+
+        for (flags, expected) in [(&[][..], 1), (&["--hidden"][..], 3)] {
+            let mut argv = vec!["-p", "needle", "-d", dir.path().to_str().unwrap()];
+            argv.extend_from_slice(flags);
+            let searcher = Searcher::new(&parse_args(&argv))?;
+            searcher.search_dir(dir.path())?;
+            assert_eq!(searcher.results.lock().unwrap().len(), expected);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_no_ignore() -> Result<(), Box<dyn Error>> {
+        let dir = tempdir()?;
+        fs::create_dir_all(dir.path().join("repo/.git"))?;
+        fs::create_dir(dir.path().join("repo/build"))?;
+        fs::write(dir.path().join("repo/.gitignore"), "build/\n")?;
+        fs::write(dir.path().join("repo/.ignore"), "*.log\n")?;
+        fs::write(dir.path().join("repo/build/out.txt"), "needle\n")?;
+        fs::write(dir.path().join("repo/debug.log"), "needle\n")?;
+        fs::write(dir.path().join("repo/main.rs"), "needle\n")?;
+
+        let repo = dir.path().join("repo");
+        for (flag, expected) in [
+            (None, 1),
+            (Some("--no-ignore-vcs"), 2),
+            (Some("--no-ignore"), 3),
+        ] {
+            let mut argv = vec!["-p", "needle", "-d", repo.to_str().unwrap()];
+            argv.extend(flag);
+            let searcher = Searcher::new(&parse_args(&argv))?;
+            searcher.search_dir(&repo)?;
+            assert_eq!(
+                searcher.results.lock().unwrap().len(),
+                expected,
+                "{:?}",
+                flag
+            );
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_tool_ignore_files() -> Result<(), Box<dyn Error>> {
+        let dir = tempdir()?;
+        fs::write(dir.path().join(".ignore"), "*.log\n")?;
+        fs::write(dir.path().join(".rscoutignore"), "fixtures/\n")?;
+        fs::create_dir(dir.path().join("fixtures"))?;
+        fs::write(dir.path().join("fixtures/data.txt"), "needle\n")?;
+        fs::write(dir.path().join("debug.log"), "needle\n")?;
+        fs::write(dir.path().join("main.rs"), "needle\n")?;
+
+        let args = parse_args(&["-p", "needle", "-d", dir.path().to_str().unwrap()]);
+        let searcher = Searcher::new(&args)?;
+        searcher.search_dir(dir.path())?;
+
+        let results = searcher.results.lock().unwrap();
+        assert_eq!(results.len(), 1);
+        assert!(results[0].file_path.ends_with("main.rs"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_git_info_exclude() -> Result<(), Box<dyn Error>> {
+        let dir = tempdir()?;
+        fs::create_dir_all(dir.path().join(".git/info"))?;
+        fs::write(dir.path().join(".git/info/exclude"), "scratch.txt\n")?;
+        fs::write(dir.path().join("scratch.txt"), "needle\n")?;
+        fs::write(dir.path().join("main.rs"), "needle\n")?;
+
+        let args = parse_args(&["-p", "needle", "-d", dir.path().to_str().unwrap()]);
+        let searcher = Searcher::new(&args)?;
+        searcher.search_dir(dir.path())?;
+        assert_eq!(searcher.results.lock().unwrap().len(), 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_max_filesize() -> Result<(), Box<dyn Error>> {
+        let dir = tempdir()?;
+        fs::write(dir.path().join("small.txt"), "needle\n")?;
+        fs::write(dir.path().join("dump.txt"), "needle\n".repeat(1024))?;
+
+        let args = parse_args(&[
+            "-p",
+            "needle",
+            "--max-filesize",
+            "1K",
+            "-d",
+            dir.path().to_str().unwrap(),
+        ]);
+        let searcher = Searcher::new(&args)?;
+        searcher.search_dir(dir.path())?;
+
+        assert_eq!(searcher.results.lock().unwrap().len(), 1);
+        let skipped = searcher.skipped.lock().unwrap();
+        assert_eq!(skipped.len(), 1);
+        assert!(skipped[0].0.ends_with("dump.txt"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_modification_time_filters() -> Result<(), Box<dyn Error>> {
+        let dir = tempdir()?;
+        fs::write(dir.path().join("old.txt"), "needle\n")?;
+        fs::write(dir.path().join("new.txt"), "needle\n")?;
+        let two_weeks_ago = SystemTime::now() - Duration::from_secs(14 * 86400);
+        File::options()
+            .write(true)
+            .open(dir.path().join("old.txt"))?
+            .set_modified(two_weeks_ago)?;
+
+        for (flag, expected) in [("--newer-than", "new.txt"), ("--older-than", "old.txt")] {
+            let args = parse_args(&[
+                "-p",
+                "needle",
+                flag,
+                "1w",
+                "-d",
+                dir.path().to_str().unwrap(),
+            ]);
+            let searcher = Searcher::new(&args)?;
+            searcher.search_dir(dir.path())?;
+            let results = searcher.results.lock().unwrap();
+            assert_eq!(results.len(), 1);
+            assert!(results[0].file_path.ends_with(expected));
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_files_from() -> Result<(), Box<dyn Error>> {
+        assert_eq!(
+            parse_file_list("a.rs\nsrc/b c.rs\n\n"),
+            [PathBuf::from("a.rs"), PathBuf::from("src/b c.rs")]
+        );
+        assert_eq!(
+            parse_file_list("a\nb.rs\0c.rs\0"),
+            [PathBuf::from("a\nb.rs"), PathBuf::from("c.rs")]
+        );
+
+        let dir = tempdir()?;
+        for name in ["one.rs", "two.rs", "three.txt"] {
+            fs::write(dir.path().join(name), "needle\n")?;
+        }
+        let args = parse_args(&["needle", "-e", "rs"]);
+        let searcher = Searcher::new(&args)?;
+        searcher.search_listed(
+            ["two.rs", "three.txt"]
+                .iter()
+                .map(|name| dir.path().join(name))
+                .collect(),
+        );
+
+        let results = searcher.results.lock().unwrap();
+        assert_eq!(results.len(), 1);
+        assert!(results[0].file_path.ends_with("two.rs"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_one_file_system() -> Result<(), Box<dyn Error>> {
+        let dir = tempdir()?;
+        fs::create_dir(dir.path().join("sub"))?;
+        fs::write(dir.path().join("sub/file.txt"), "needle\n")?;
+
+        let args = parse_args(&["needle", "--one-file-system", dir.path().to_str().unwrap()]);
+        assert!(args.one_file_system);
+        let searcher = Searcher::new(&args)?;
+        searcher.search_dir(dir.path())?;
+        assert_eq!(searcher.results.lock().unwrap().len(), 1);
+
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_hardlink_dedup() -> Result<(), Box<dyn Error>> {
+        let dir = tempdir()?;
+        fs::write(dir.path().join("original.txt"), "needle\n")?;
+        fs::hard_link(
+            dir.path().join("original.txt"),
+            dir.path().join("backup.txt"),
+        )?;
+
+        for (flags, expected) in [(&[][..], 1), (&["--no-hardlink-dedup"][..], 2)] {
+            let mut argv = vec!["needle", dir.path().to_str().unwrap()];
+            argv.extend_from_slice(flags);
+            let searcher = Searcher::new(&parse_args(&argv))?;
+            searcher.search_dir(dir.path())?;
+            assert_eq!(searcher.results.lock().unwrap().len(), expected);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_extension_matching() -> Result<(), Box<dyn Error>> {
+        let args = parse_args(&["x", "-e", "txt, .tar.gz,Makefile"]);
+        let searcher = Searcher::new(&args)?;
+        for (name, expected) in [
+            ("notes.txt", true),
+            ("NOTES.TXT", true),
+            ("backup.tar.gz", true),
+            ("backup.gz", false),
+            ("Makefile", true),
+            ("src/makefile", true),
+            ("txt.bak", false),
+            (".txt", false),
+            ("main.rs", false),
+        ] {
+            assert_eq!(
+                searcher.should_search_file(Path::new(name)),
+                expected,
+                "{}",
+                name
+            );
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_recent_first_order() -> Result<(), Box<dyn Error>> {
+        let dir = tempdir()?;
+        let now = SystemTime::now();
+        for (name, age_days) in [("old.txt", 30), ("newest.txt", 0), ("middle.txt", 7)] {
+            let path = dir.path().join(name);
+            fs::write(&path, "needle\n")?;
+            File::options()
+                .write(true)
+                .open(&path)?
+                .set_modified(now - Duration::from_secs(age_days * 86400))?;
+        }
+
+        let args = parse_args(&["needle", "--recent-first", dir.path().to_str().unwrap()]);
+        let searcher = Searcher::new(&args)?;
+        let ordered = searcher.order_files(
+            ["old.txt", "newest.txt", "middle.txt"]
+                .iter()
+                .map(|name| dir.path().join(name))
+                .collect(),
+        );
+        let names: Vec<_> = ordered.iter().map(|p| p.file_name().unwrap()).collect();
+        assert_eq!(names, ["newest.txt", "middle.txt", "old.txt"]);
+
+        searcher.search_dir(dir.path())?;
+        assert_eq!(searcher.results.lock().unwrap().len(), 3);
+
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_owner_and_perm() -> Result<(), Box<dyn Error>> {
+        use std::os::unix::fs::{MetadataExt, PermissionsExt};
+
+        let dir = tempdir()?;
+        for (name, mode) in [("deploy.sh", 0o777), ("build.sh", 0o755)] {
+            let path = dir.path().join(name);
+            fs::write(&path, "needle\n")?;
+            fs::set_permissions(&path, fs::Permissions::from_mode(mode))?;
+        }
+        let uid = fs::metadata(dir.path())?.uid().to_string();
+
+        let args = parse_args(&[
+            "needle",
+            "--owner",
+            &uid,
+            "--perm",
+            "-002",
+            dir.path().to_str().unwrap(),
+        ]);
+        let searcher = Searcher::new(&args)?;
+        searcher.search_dir(dir.path())?;
+        let results = searcher.results.lock().unwrap();
+        assert_eq!(results.len(), 1);
+        assert!(results[0].file_path.ends_with("deploy.sh"));
+        drop(results);
+
+        let other = (fs::metadata(dir.path())?.uid() + 1).to_string();
+        let args = parse_args(&["needle", "--owner", &other, dir.path().to_str().unwrap()]);
+        let searcher = Searcher::new(&args)?;
+        searcher.search_dir(dir.path())?;
+        assert!(searcher.results.lock().unwrap().is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_threads() -> Result<(), Box<dyn Error>> {
+        let dir = tempdir()?;
+        for name in ["a.txt", "b.txt", "c.txt"] {
+            fs::write(dir.path().join(name), "needle\n")?;
+        }
+
+        let args = parse_args(&["needle", "-j", "1", dir.path().to_str().unwrap()]);
+        let searcher = Searcher::new(&args)?;
+        assert_eq!(searcher.threads, 1);
+        searcher.search_dir(dir.path())?;
+        assert_eq!(searcher.results.lock().unwrap().len(), 3);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_batch_files() -> Result<(), Box<dyn Error>> {
+        // 1600 bytes over 4 threads makes batches of about 25 bytes.
+        let mut sizes = vec![1; 1000];
+        sizes.push(600);
+        let batches = batch_files(&sizes, 4);
+        assert_eq!(batches[0], 0..25);
+        assert_eq!(batches.iter().map(|b| b.len()).sum::<usize>(), sizes.len());
+        assert!(batches.contains(&(1000..1001)));
+        // Empty files are still capped per batch.
+        assert_eq!(batch_files(&[0; 300], 4), [0..256, 256..300]);
+
+        let dir = tempdir()?;
+        let paths: Vec<_> = (0..500)
+            .map(|i| {
+                let path = dir.path().join(format!("{}.txt", i));
+                fs::write(&path, "needle\n").map(|_| path)
+            })
+            .collect::<Result<_, _>>()?;
+        let args = parse_args(&["needle"]);
+        let searcher = Searcher::new(&args)?;
+        searcher.search_files(&paths, &ProgressBar::hidden(), "test");
+        assert_eq!(searcher.results.lock().unwrap().len(), 500);
+
+        Ok(())
+    }
+
+    #[cfg(all(feature = "io-uring", target_os = "linux"))]
+    #[test]
+    fn test_io_backend_uring() -> Result<(), Box<dyn Error>> {
+        let dir = tempdir()?;
+        for i in 0..300 {
+            let text = if i % 3 == 0 { "a needle\n" } else { "hay\n" };
+            fs::write(dir.path().join(format!("{}.txt", i)), text)?;
+        }
+
+        // Without io_uring the reads fall back to the standard backend, so
+        // either way every match is found.
+        let args = parse_args(&[
+            "needle",
+            "--io-backend",
+            "uring",
+            dir.path().to_str().unwrap(),
+        ]);
+        let searcher = Searcher::new(&args)?;
+        searcher.search_dir(dir.path())?;
+        assert_eq!(searcher.results.lock().unwrap().len(), 100);
+        assert_eq!(searcher.stats.snapshot().bytes, 100 * 9 + 200 * 4);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_list_files() -> Result<(), Box<dyn Error>> {
+        let dir = tempdir()?;
+        fs::create_dir(dir.path().join(".cache"))?;
+        fs::write(dir.path().join(".cache").join("hidden.txt"), "")?;
+        fs::write(dir.path().join("main.rs"), "fn main() {}\n")?;
+        fs::write(dir.path().join("notes.md"), "")?;
+
+        let args = parse_args(&["--files", "-e", "rs", dir.path().to_str().unwrap()]);
+        assert_eq!(args.paths, [dir.path()]);
+        let searcher = Searcher::new(&args)?;
+        searcher.search_dir(dir.path())?;
+
+        let results = searcher.results.lock().unwrap();
+        assert_eq!(results.len(), 1);
+        assert!(results[0].file_path.ends_with("main.rs"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_path_pattern() -> Result<(), Box<dyn Error>> {
+        let dir = tempdir()?;
+        fs::create_dir(dir.path().join("src"))?;
+        fs::write(
+            dir.path().join("src").join("user_handler.rs"),
+            "x.unwrap()\n",
+        )?;
+        fs::write(dir.path().join("src").join("main.rs"), "x.unwrap()\n")?;
+
+        let args = parse_args(&[
+            "--path-pattern",
+            r"src/.*handler.*\.rs",
+            "-p",
+            "unwrap",
+            "-d",
+            dir.path().to_str().unwrap(),
+        ]);
+        let searcher = Searcher::new(&args)?;
+        assert!(!searcher.should_search_file(&dir.path().join("src").join("main.rs")));
+        searcher.search_dir(dir.path())?;
+
+        let results = searcher.results.lock().unwrap();
+        assert_eq!(results.len(), 1);
+        assert!(results[0].file_path.ends_with("user_handler.rs"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_interrupted() -> Result<(), Box<dyn Error>> {
+        let dir = tempdir()?;
+        fs::write(dir.path().join("a.txt"), "needle\n")?;
+
+        let args = parse_args(&["needle", dir.path().to_str().unwrap()]);
+        let searcher = Searcher::new(&args)?;
+        searcher.interrupted.store(true, Ordering::Relaxed);
+        assert!(searcher.is_cancelled());
+        searcher.search_dir(dir.path())?;
+        assert!(searcher.results.lock().unwrap().is_empty());
+
+        Ok(())
+    }
+}
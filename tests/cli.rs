@@ -0,0 +1,28 @@
+use std::fs;
+use std::process::Command;
+use tempfile::tempdir;
+
+/// What `rusty-scout` prints to stdout with `args`.
+fn stdout(args: &[&str]) -> String {
+    let output = Command::new(env!("CARGO_BIN_EXE_rusty-scout"))
+        .args(args)
+        .output()
+        .unwrap();
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn test_grep_style_output() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = tempdir()?;
+    fs::write(dir.path().join("a.txt"), "needle\nneedle\n")?;
+    fs::write(dir.path().join("b.txt"), "hay\n")?;
+    let root = dir.path().to_str().unwrap();
+    let a = dir.path().join("a.txt").display().to_string();
+
+    // Only what a pipe into xargs or sort expects, without the banner or
+    // the summary.
+    assert_eq!(stdout(&["-l", "needle", root]), format!("{}\n", a));
+    assert_eq!(stdout(&["-c", "needle", root]), format!("{}:2\n", a));
+    assert_eq!(stdout(&["-l", "nothing", root]), "");
+    Ok(())
+}